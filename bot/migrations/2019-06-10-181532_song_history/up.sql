@@ -0,0 +1,23 @@
+CREATE TABLE song_history (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    -- the track that was played.
+    track_id VARCHAR NOT NULL,
+    -- human readable name of the track at the time it was played.
+    name VARCHAR NOT NULL,
+    -- artists of the track, if known.
+    artists VARCHAR,
+    -- the user that requested the track, if any.
+    user VARCHAR,
+    -- when the track started playing.
+    started_at TIMESTAMP NOT NULL,
+    -- when the track stopped playing.
+    ended_at TIMESTAMP NOT NULL,
+    -- how much of the track was played when it ended, in milliseconds.
+    elapsed INTEGER NOT NULL,
+    -- the duration of the track, in milliseconds.
+    duration INTEGER NOT NULL,
+    -- why the track ended, like `finished` or `skipped`.
+    reason VARCHAR NOT NULL
+);
+
+CREATE INDEX idx_song_history_started_at ON song_history(started_at);
@@ -0,0 +1,14 @@
+CREATE TABLE song_bans (
+    -- the kind of thing that is banned, one of `track`, `artist`, or `channel`.
+    kind VARCHAR NOT NULL,
+    -- the id of the thing that is banned.
+    id VARCHAR NOT NULL,
+    -- the reason for the ban.
+    reason VARCHAR,
+    -- the user that added the ban.
+    banned_by VARCHAR,
+    -- when the ban was added.
+    banned_at TIMESTAMP NOT NULL,
+
+    PRIMARY KEY(kind, id)
+);
@@ -0,0 +1,30 @@
+DROP INDEX songs_deleted_added_at;
+
+CREATE TEMPORARY TABLE tmp_songs (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    deleted BOOLEAN NOT NULL DEFAULT FALSE,
+    track_id VARCHAR NOT NULL,
+    added_at TIMESTAMP NOT NULL,
+    user VARCHAR,
+    promoted_at TIMESTAMP DEFAULT NULL,
+    promoted_by VARCHAR DEFAULT NULL
+);
+
+INSERT INTO tmp_songs SELECT id, deleted, track_id, added_at, user, promoted_at, promoted_by FROM songs;
+DROP TABLE songs;
+
+CREATE TABLE songs (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    deleted BOOLEAN NOT NULL DEFAULT FALSE,
+    track_id VARCHAR NOT NULL,
+    added_at TIMESTAMP NOT NULL,
+    user VARCHAR,
+    promoted_at TIMESTAMP DEFAULT NULL,
+    promoted_by VARCHAR DEFAULT NULL
+);
+
+INSERT INTO songs SELECT id, deleted, track_id, added_at, user, promoted_at, promoted_by FROM tmp_songs;
+DROP TABLE tmp_songs;
+
+CREATE INDEX songs_deleted_added_at ON songs (deleted, track_id);
+CREATE INDEX idx_songs_added_at_id ON songs(added_at, id);
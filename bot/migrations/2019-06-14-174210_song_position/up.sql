@@ -0,0 +1 @@
+ALTER TABLE songs ADD COLUMN position INTEGER DEFAULT NULL;
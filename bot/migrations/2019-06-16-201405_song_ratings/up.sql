@@ -0,0 +1,12 @@
+CREATE TABLE song_ratings (
+    -- the track that was rated.
+    track_id VARCHAR NOT NULL,
+    -- the user that rated the track.
+    user VARCHAR NOT NULL,
+    -- the rating, 1 for a like and -1 for a dislike.
+    rating INTEGER NOT NULL,
+    -- when the track was last rated.
+    rated_at TIMESTAMP NOT NULL,
+
+    PRIMARY KEY(track_id, user)
+);
@@ -0,0 +1,22 @@
+CREATE TABLE moderation_log (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    -- the channel the action was taken in.
+    channel VARCHAR NOT NULL,
+    -- the kind of action, like `timeout`, `ban`, `delete`, `clear`, or `room-state`.
+    kind VARCHAR NOT NULL,
+    -- the user the action was taken against, if any.
+    user VARCHAR,
+    -- the moderator that took the action, if known.
+    moderator VARCHAR,
+    -- the duration of a timeout, in seconds.
+    duration INTEGER,
+    -- the reason for the action, if known.
+    reason VARCHAR,
+    -- the deleted message, or the room mode that changed.
+    message VARCHAR,
+    -- when the action was taken.
+    created_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX idx_moderation_log_user ON moderation_log(channel, user);
+CREATE INDEX idx_moderation_log_created_at ON moderation_log(created_at);
@@ -0,0 +1,13 @@
+CREATE TEMPORARY TABLE song_order (
+    position INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    id INTEGER NOT NULL
+);
+
+INSERT INTO song_order (id)
+SELECT id FROM songs WHERE deleted = FALSE ORDER BY position ASC, promoted_at DESC, added_at ASC;
+
+UPDATE songs
+SET position = (SELECT o.position - 1 FROM song_order AS o WHERE o.id = songs.id)
+WHERE deleted = FALSE;
+
+DROP TABLE song_order;
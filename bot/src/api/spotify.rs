@@ -11,12 +11,13 @@ use reqwest::{
 use rspotify::spotify::model::search;
 pub use rspotify::spotify::{
     model::{
+        album::FullAlbum,
         artist::SimplifiedArtist,
         context::FullPlayingContext,
         device::Device,
         page::Page,
         playlist::{FullPlaylist, SimplifiedPlaylist},
-        track::{FullTrack, SavedTrack},
+        track::{FullTrack, SavedTrack, SimplifiedTrack},
     },
     senum::DeviceType,
 };
@@ -28,6 +29,41 @@ use std::{
 
 const API_URL: &'static str = "https://api.spotify.com/v1";
 
+/// Response from the recommendations endpoint.
+#[derive(serde::Deserialize)]
+struct Recommendations {
+    tracks: Vec<RecommendedTrack>,
+}
+
+/// A track in a recommendations response, of which we only need the id.
+#[derive(serde::Deserialize)]
+struct RecommendedTrack {
+    #[serde(default)]
+    id: Option<String>,
+}
+
+/// A track in a playlist, of which we only need the id.
+#[derive(serde::Deserialize)]
+pub struct PlaylistTrackRef {
+    #[serde(default)]
+    track: Option<TrackRef>,
+}
+
+impl PlaylistTrackRef {
+    /// Get the id of the track.
+    ///
+    /// Local files in playlists don't have an id.
+    pub fn id(self) -> Option<String> {
+        self.track.and_then(|t| t.id)
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct TrackRef {
+    #[serde(default)]
+    id: Option<String>,
+}
+
 /// API integration.
 #[derive(Clone, Debug)]
 pub struct Spotify {
@@ -68,6 +104,40 @@ impl Spotify {
             .await
     }
 
+    /// Get the tracks of a playlist.
+    pub fn playlist_tracks_stream(&self, id: String) -> PageStream<PlaylistTrackRef> {
+        self.page_stream(
+            self.request(Method::GET, &["playlists", id.as_str(), "tracks"])
+                .execute(),
+        )
+    }
+
+    /// Add the given tracks to the end of a playlist.
+    pub async fn playlist_add_tracks(&self, id: String, uris: Vec<String>) -> Result<(), Error> {
+        let request = Request { uris };
+
+        let r = self
+            .request(Method::POST, &["playlists", id.as_str(), "tracks"])
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::ACCEPT, "application/json");
+
+        let body = Bytes::from(serde_json::to_vec(&request)?);
+        r.body(body).execute::<serde::de::IgnoredAny>().await?;
+        return Ok(());
+
+        #[derive(serde::Serialize)]
+        struct Request {
+            uris: Vec<String>,
+        }
+    }
+
+    /// Get an album.
+    pub async fn album(&self, id: String) -> Result<FullAlbum, Error> {
+        self.request(Method::GET, &["albums", id.as_str()])
+            .execute()
+            .await
+    }
+
     /// Get my devices.
     pub async fn my_player_devices(&self) -> Result<Vec<Device>, Error> {
         let r = self
@@ -181,6 +251,28 @@ impl Spotify {
             .map(|r| r.tracks)
     }
 
+    /// Get the ids of tracks recommended based on the given seed tracks.
+    ///
+    /// Spotify permits at most five seeds.
+    pub async fn recommendations(
+        &self,
+        seed_tracks: Vec<String>,
+        limit: u32,
+    ) -> Result<Vec<String>, Error> {
+        let recommendations = self
+            .request(Method::GET, &["recommendations"])
+            .query_param("seed_tracks", &seed_tracks.join(","))
+            .query_param("limit", &limit.to_string())
+            .execute::<Recommendations>()
+            .await?;
+
+        Ok(recommendations
+            .tracks
+            .into_iter()
+            .filter_map(|t| t.id)
+            .collect())
+    }
+
     /// Convert a page object into a stream.
     pub fn page_as_stream<T>(&self, page: Page<T>) -> PageStream<T>
     where
@@ -68,6 +68,32 @@ impl YouTube {
         async move { Ok(req.await?.and_then(|v| v.items.into_iter().next())) }
     }
 
+    /// Get a page of the videos in a playlist.
+    pub fn playlist_items(
+        &self,
+        playlist_id: String,
+        page_token: Option<String>,
+    ) -> impl Future<Output = Result<PlaylistItems, failure::Error>> {
+        let mut req = self
+            .v3(Method::GET, &["playlistItems"])
+            .query_param("part", "contentDetails")
+            .query_param("maxResults", "50")
+            .query_param("playlistId", playlist_id.as_str());
+
+        if let Some(page_token) = page_token {
+            req = req.query_param("pageToken", page_token.as_str());
+        }
+
+        let req = req.json::<PlaylistItems>();
+
+        async move {
+            match req.await? {
+                Some(result) => Ok(result),
+                None => failure::bail!("playlist not found"),
+            }
+        }
+    }
+
     /// Search YouTube.
     pub fn search(&self, q: String) -> impl Future<Output = Result<SearchResults, failure::Error>> {
         let req = self
@@ -251,6 +277,33 @@ pub struct SearchResults {
     pub items: Vec<SearchResult>,
 }
 
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistItemContentDetails {
+    pub video_id: String,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistItem {
+    pub kind: String,
+    pub etag: String,
+    pub id: String,
+    #[serde(default)]
+    pub content_details: Option<PlaylistItemContentDetails>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistItems {
+    pub kind: String,
+    pub etag: String,
+    pub next_page_token: Option<String>,
+    pub page_info: PageInfo,
+    #[serde(default)]
+    pub items: Vec<PlaylistItem>,
+}
+
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Thumbnail {
@@ -297,6 +350,62 @@ pub struct ContentDetails {
     pub licensed_content: bool,
     #[serde(default)]
     pub projection: Option<String>,
+    #[serde(default)]
+    pub content_rating: Option<ContentRating>,
+    #[serde(default)]
+    pub region_restriction: Option<RegionRestriction>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentRating {
+    #[serde(default)]
+    pub yt_rating: Option<String>,
+}
+
+impl ContentRating {
+    /// Test if the content is age restricted.
+    pub fn is_age_restricted(&self) -> bool {
+        self.yt_rating
+            .as_ref()
+            .map(|r| r == "ytAgeRestricted")
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegionRestriction {
+    /// If present, the video is only available in these regions.
+    #[serde(default)]
+    pub allowed: Option<Vec<String>>,
+    /// The video is not available in these regions.
+    #[serde(default)]
+    pub blocked: Vec<String>,
+}
+
+impl RegionRestriction {
+    /// Test if the video is available in the given region, as an ISO 3166-1 alpha-2 code.
+    pub fn is_available_in(&self, region: &str) -> bool {
+        if let Some(allowed) = self.allowed.as_ref() {
+            if !allowed.iter().any(|r| r.eq_ignore_ascii_case(region)) {
+                return false;
+            }
+        }
+
+        !self.blocked.iter().any(|r| r.eq_ignore_ascii_case(region))
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Status {
+    #[serde(default)]
+    pub upload_status: Option<String>,
+    #[serde(default)]
+    pub privacy_status: Option<String>,
+    #[serde(default)]
+    pub embeddable: bool,
 }
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
@@ -309,6 +418,8 @@ pub struct Video {
     pub snippet: Option<Snippet>,
     #[serde(default)]
     pub content_details: Option<ContentDetails>,
+    #[serde(default)]
+    pub status: Option<Status>,
 }
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
@@ -345,6 +345,8 @@ scopes! {
     (Song, "song"),
     (SongYouTube, "song/youtube"),
     (SongSpotify, "song/spotify"),
+    (SongLocal, "song/local"),
+    (SongSave, "song/save"),
     (SwearJar, "swearjar"),
     (Uptime, "uptime"),
     (Game, "game"),
@@ -357,6 +359,7 @@ scopes! {
     (Command, "command"),
     (GtavBypassCooldown, "gtav/bypass-cooldown"),
     (Speedrun, "speedrun"),
+    (ModLog, "modlog"),
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -1,4 +1,4 @@
-use crate::{player, track_id::TrackId};
+use crate::{irc, player, track_id::TrackId};
 use hashbrown::HashMap;
 use parking_lot::Mutex;
 use std::sync::Arc;
@@ -84,6 +84,54 @@ pub enum YouTubeEvent {
     Stop,
 }
 
+/// State of the YouTube player in the overlay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+pub enum YouTubeState {
+    #[serde(rename = "unstarted")]
+    Unstarted,
+    #[serde(rename = "ended")]
+    Ended,
+    #[serde(rename = "playing")]
+    Playing,
+    #[serde(rename = "paused")]
+    Paused,
+    #[serde(rename = "buffering")]
+    Buffering,
+    #[serde(rename = "cued")]
+    Cued,
+}
+
+/// Reports sent back by the YouTube player in the overlay.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(tag = "type")]
+pub enum YouTubeReport {
+    /// The state and position of the player.
+    #[serde(rename = "state")]
+    State {
+        video_id: String,
+        state: YouTubeState,
+        /// Elapsed time of the video in seconds.
+        elapsed: f64,
+    },
+    /// The video failed to play.
+    #[serde(rename = "error")]
+    Error {
+        video_id: String,
+        /// Error code from the YouTube player.
+        code: u32,
+    },
+}
+
+impl YouTubeReport {
+    /// Get the video the report is about.
+    pub fn video_id(&self) -> &str {
+        match *self {
+            YouTubeReport::State { ref video_id, .. } => video_id,
+            YouTubeReport::Error { ref video_id, .. } => video_id,
+        }
+    }
+}
+
 /// Events for driving the YouTube player.
 #[derive(Debug, Clone, serde::Serialize)]
 #[serde(tag = "type")]
@@ -106,6 +154,47 @@ impl Message for YouTube {
     }
 }
 
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "type")]
+pub enum LocalEvent {
+    /// Play a new song.
+    #[serde(rename = "play")]
+    Play {
+        /// The id of the local track.
+        id: String,
+        elapsed: u64,
+        duration: u64,
+    },
+    /// Pause the player.
+    #[serde(rename = "pause")]
+    Pause,
+    /// Stop the player.
+    #[serde(rename = "stop")]
+    Stop,
+}
+
+/// Events for driving the player of local files.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "type")]
+pub enum Local {
+    #[serde(rename = "local/current")]
+    LocalCurrent { event: LocalEvent },
+    #[serde(rename = "local/volume")]
+    LocalVolume { volume: u32 },
+}
+
+impl Message for Local {
+    /// Whether a message should be cached or not and under what key.
+    fn id(&self) -> Option<&'static str> {
+        use self::Local::*;
+
+        match *self {
+            LocalCurrent { .. } => Some("local/current"),
+            LocalVolume { .. } => Some("local/volume"),
+        }
+    }
+}
+
 /// Messages that go on the global bus.
 #[derive(Debug, Clone, serde::Serialize)]
 #[serde(tag = "type")]
@@ -130,6 +219,12 @@ pub enum Global {
         elapsed: u64,
         duration: u64,
     },
+    /// Health of the Spotify device used for playback.
+    #[serde(rename = "player/device-health")]
+    DeviceHealth { health: player::DeviceHealth },
+    /// An event in a channel, like a subscription or a raid.
+    #[serde(rename = "irc/event")]
+    ChannelEvent { channel: String, event: irc::Event },
 }
 
 impl Message for Global {
@@ -140,6 +235,7 @@ impl Message for Global {
         match *self {
             SongProgress { .. } => Some("song/progress"),
             SongCurrent { .. } => Some("song/current"),
+            DeviceHealth { .. } => Some("player/device-health"),
             _ => None,
         }
     }
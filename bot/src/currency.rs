@@ -150,6 +150,21 @@ impl Backend {
         }
     }
 
+    /// Subtract from the balance of a single user, unless they don't have enough.
+    pub async fn balance_spend(
+        &self,
+        channel: String,
+        user: String,
+        amount: i64,
+    ) -> Result<(), BalanceTransferError> {
+        use self::Backend::*;
+
+        match *self {
+            BuiltIn(ref backend) => backend.balance_spend(channel, user, amount).await,
+            Honkos(ref backend) => backend.balance_spend(channel, user, amount).await,
+        }
+    }
+
     /// Get balances for all users.
     pub async fn export_balances(&self) -> Result<Vec<Balance>, Error> {
         use self::Backend::*;
@@ -269,6 +284,19 @@ impl Currency {
             .await
     }
 
+    /// Subtract from the balance of a single user, unless they don't have enough.
+    pub async fn balance_spend(
+        &self,
+        channel: String,
+        user: String,
+        amount: i64,
+    ) -> Result<(), BalanceTransferError> {
+        self.inner
+            .backend
+            .balance_spend(channel, user, amount)
+            .await
+    }
+
     /// Get balances for all users.
     pub async fn export_balances(&self) -> Result<Vec<Balance>, Error> {
         self.inner.backend.export_balances().await
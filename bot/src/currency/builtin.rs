@@ -61,6 +61,42 @@ impl Backend {
         future.compat().await
     }
 
+    /// Subtract from the balance of a single user, unless they don't have enough.
+    pub async fn balance_spend(
+        &self,
+        channel: String,
+        user: String,
+        amount: i64,
+    ) -> Result<(), BalanceTransferError> {
+        use self::schema::balances::dsl;
+
+        let user = user_id(&user);
+        let pool = self.db.pool.clone();
+
+        let future = self.db.thread_pool.spawn_handle(future01::lazy(move || {
+            let c = pool.lock();
+            let c = &*c;
+
+            c.transaction(move || {
+                let balance = dsl::balances
+                    .filter(dsl::channel.eq(channel.as_str()).and(dsl::user.eq(&user)))
+                    .select(dsl::amount)
+                    .first::<i64>(&*c)
+                    .optional()?
+                    .unwrap_or_default();
+
+                if balance < amount {
+                    return Err(BalanceTransferError::NoBalance);
+                }
+
+                modify_balance(c, &channel, &user, -amount)?;
+                Ok(())
+            })
+        }));
+
+        future.compat().await
+    }
+
     /// Get balances for all users.
     pub async fn export_balances(&self) -> Result<Vec<models::Balance>, Error> {
         use self::schema::balances::dsl;
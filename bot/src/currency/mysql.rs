@@ -182,6 +182,42 @@ impl Backend {
         future.compat().await
     }
 
+    /// Subtract from the balance of a single user, unless they don't have enough.
+    pub async fn balance_spend(
+        &self,
+        _channel: String,
+        user: String,
+        amount: i64,
+    ) -> Result<(), BalanceTransferError> {
+        let amount: i32 = amount.try_into()?;
+
+        let user = user_id(&user);
+        let pool = self.pool.clone();
+        let queries = self.queries.clone();
+
+        let future = self.thread_pool.spawn_handle(future01::lazy(move || {
+            let c = pool
+                .get()
+                .map_err(|e| BalanceTransferError::Other(e.into()))?;
+
+            c.transaction(|| {
+                let balance = queries
+                    .select_balance(&*c, &user)
+                    .map_err(|e| BalanceTransferError::Other(e.into()))?
+                    .unwrap_or_default();
+
+                if balance < amount {
+                    return Err(BalanceTransferError::NoBalance);
+                }
+
+                modify_balance(&c, &*queries, &user, -amount)?;
+                Ok(())
+            })
+        }));
+
+        future.compat().await
+    }
+
     /// Get balances for all users.
     pub async fn export_balances(&self) -> Result<Vec<Balance>, Error> {
         let channel = self.channel.to_string();
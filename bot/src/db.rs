@@ -4,9 +4,13 @@ mod after_streams;
 mod aliases;
 mod cache;
 mod commands;
+mod history;
 pub(crate) mod models;
+mod moderation_log;
 mod promotions;
 pub(crate) mod schema;
+mod song_bans;
+mod song_stats;
 mod themes;
 mod words;
 
@@ -18,7 +22,11 @@ pub use self::{
     aliases::{Alias, Aliases},
     cache::Cache,
     commands::{Command, Commands},
+    history::{History, HistoryEntry},
+    moderation_log::{InsertModerationEntry, ModerationEntry, ModerationKind, ModerationLog},
     promotions::{Promotion, Promotions},
+    song_bans::{BanKind, SongBan, SongBans},
+    song_stats::{Rating, Ratings, SongStats, TrackCount, UserCount},
     themes::{Theme, Themes},
     words::{Word, Words},
 };
@@ -57,6 +65,12 @@ impl Database {
         })
     }
 
+    /// Open a database which only lives in memory, for tests.
+    #[cfg(test)]
+    pub(crate) fn memory() -> Result<Database, Error> {
+        Self::open(Path::new(":memory:"), Arc::new(ThreadPool::new()))
+    }
+
     /// Access auth from the database.
     pub fn auth(&self, schema: crate::auth::Schema) -> Result<crate::auth::Auth, Error> {
         Ok(crate::auth::Auth::new(self.clone(), schema)?)
@@ -128,9 +142,15 @@ impl player::Backend for Database {
     fn list(&self) -> Result<Vec<models::Song>, Error> {
         use self::schema::songs::dsl;
         let c = self.pool.lock();
+        // NB: songs which haven't been given a position yet are added last.
         let songs = dsl::songs
             .filter(dsl::deleted.eq(false))
-            .order((dsl::promoted_at.desc(), dsl::added_at.asc()))
+            .order((
+                dsl::position.is_null().asc(),
+                dsl::position.asc(),
+                dsl::promoted_at.desc(),
+                dsl::added_at.asc(),
+            ))
             .load::<models::Song>(&*c)?;
         Ok(songs)
     }
@@ -192,6 +212,23 @@ impl player::Backend for Database {
         Ok(count == 1)
     }
 
+    fn song_order(&self, order: &[TrackId]) -> Result<(), Error> {
+        use self::schema::songs::dsl;
+        let c = self.pool.lock();
+
+        c.transaction(|| {
+            for (position, track_id) in order.iter().enumerate() {
+                diesel::update(
+                    dsl::songs.filter(dsl::deleted.eq(false).and(dsl::track_id.eq(track_id))),
+                )
+                .set(dsl::position.eq(position as i32))
+                .execute(&*c)?;
+            }
+
+            Ok(())
+        })
+    }
+
     fn last_song_within(
         &self,
         track_id: &TrackId,
@@ -214,6 +251,30 @@ impl player::Backend for Database {
 
         Ok(song)
     }
+
+    fn user_requests_within(
+        &self,
+        user: &str,
+        duration: utils::Duration,
+    ) -> Result<Vec<chrono::NaiveDateTime>, Error> {
+        use self::schema::songs::dsl;
+        let c = self.pool.lock();
+
+        let since = match Utc::now().checked_sub_signed(duration.as_chrono()) {
+            Some(since) => since,
+            None => failure::bail!("duration too long"),
+        };
+
+        let since = since.naive_utc();
+
+        let requests = dsl::songs
+            .select(dsl::added_at)
+            .filter(dsl::added_at.gt(&since).and(dsl::user.eq(user)))
+            .order(dsl::added_at.asc())
+            .load::<chrono::NaiveDateTime>(&*c)?;
+
+        Ok(requests)
+    }
 }
 
 #[derive(Debug, err_derive::Error)]
@@ -0,0 +1,86 @@
+use crate::{
+    db::{self, models, schema},
+    track_id::TrackId,
+    utils,
+};
+use chrono::Utc;
+use diesel::prelude::*;
+
+pub use self::models::HistoryEntry;
+
+/// Persistent history of songs that have been played.
+#[derive(Clone)]
+pub struct History {
+    db: db::Database,
+}
+
+impl History {
+    /// Open the song history database.
+    pub fn load(db: db::Database) -> Result<Self, failure::Error> {
+        Ok(History { db })
+    }
+
+    /// Push the given entry to the history.
+    pub fn push(&self, entry: &models::InsertHistoryEntry) -> Result<(), failure::Error> {
+        use self::schema::song_history::dsl;
+        let c = self.db.pool.lock();
+
+        diesel::insert_into(dsl::song_history)
+            .values(entry)
+            .execute(&*c)?;
+
+        Ok(())
+    }
+
+    /// Push the given entry to the history, but only log on issues.
+    pub fn push_log(&self, entry: &models::InsertHistoryEntry) {
+        if let Err(e) = self.push(entry) {
+            log::warn!("{}: failed to store song history: {}", entry.track_id, e);
+        }
+    }
+
+    /// List history entries, with the most recently played first.
+    pub fn list(&self, offset: i64, limit: i64) -> Result<Vec<HistoryEntry>, failure::Error> {
+        use self::schema::song_history::dsl;
+        let c = self.db.pool.lock();
+
+        Ok(dsl::song_history
+            .order((dsl::started_at.desc(), dsl::id.desc()))
+            .offset(offset)
+            .limit(limit)
+            .load::<models::HistoryEntry>(&*c)?)
+    }
+
+    /// Test if the given track has been played within the given duration.
+    pub fn played_within(
+        &self,
+        track_id: &TrackId,
+        duration: utils::Duration,
+    ) -> Result<bool, failure::Error> {
+        use self::schema::song_history::dsl;
+        let c = self.db.pool.lock();
+
+        let since = match Utc::now().checked_sub_signed(duration.as_chrono()) {
+            Some(since) => since,
+            None => failure::bail!("duration too long"),
+        };
+
+        let count = dsl::song_history
+            .filter(
+                dsl::started_at
+                    .gt(&since.naive_utc())
+                    .and(dsl::track_id.eq(track_id)),
+            )
+            .count()
+            .get_result::<i64>(&*c)?;
+
+        Ok(count > 0)
+    }
+
+    /// Count the number of entries in the history.
+    pub fn count(&self) -> Result<i64, failure::Error> {
+        use self::schema::song_history::dsl;
+        let c = self.db.pool.lock();
+        Ok(dsl::song_history.count().get_result::<i64>(&*c)?)
+    }
+}
@@ -1,5 +1,6 @@
 use super::schema::{
-    after_streams, aliases, bad_words, balances, cache, commands, promotions, songs, themes,
+    after_streams, aliases, bad_words, balances, cache, commands, moderation_log, promotions,
+    song_bans, song_history, song_ratings, songs, themes,
 };
 use crate::track_id::TrackId;
 use chrono::NaiveDateTime;
@@ -123,6 +124,8 @@ pub struct Song {
     pub promoted_by: Option<String>,
     /// The user that requested the song.
     pub user: Option<String>,
+    /// The position of the song in the queue, if it has been explicitly ordered.
+    pub position: Option<i32>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, diesel::Insertable)]
@@ -207,3 +210,104 @@ pub struct CacheEntry {
 pub struct UpdateCacheEntry {
     pub value: String,
 }
+
+#[derive(Debug, Clone, serde::Serialize, diesel::Queryable)]
+pub struct HistoryEntry {
+    /// ID of the history entry.
+    pub id: i32,
+    /// The track that was played.
+    pub track_id: TrackId,
+    /// Name of the track when it was played.
+    pub name: String,
+    /// Artists of the track, if known.
+    pub artists: Option<String>,
+    /// The user that requested the track, if any.
+    pub user: Option<String>,
+    /// When the track started playing.
+    pub started_at: NaiveDateTime,
+    /// When the track stopped playing.
+    pub ended_at: NaiveDateTime,
+    /// How much of the track was played, in milliseconds.
+    pub elapsed: i32,
+    /// Duration of the track, in milliseconds.
+    pub duration: i32,
+    /// Why the track stopped playing.
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, diesel::Insertable)]
+#[table_name = "song_history"]
+pub struct InsertHistoryEntry {
+    pub track_id: TrackId,
+    pub name: String,
+    pub artists: Option<String>,
+    pub user: Option<String>,
+    pub started_at: NaiveDateTime,
+    pub ended_at: NaiveDateTime,
+    pub elapsed: i32,
+    pub duration: i32,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, diesel::Queryable, diesel::Insertable)]
+#[table_name = "song_bans"]
+pub struct SongBan {
+    /// The kind of thing that is banned.
+    pub kind: String,
+    /// The id of the thing that is banned.
+    pub id: String,
+    /// The reason for the ban.
+    pub reason: Option<String>,
+    /// The user that added the ban.
+    pub banned_by: Option<String>,
+    /// When the ban was added.
+    pub banned_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, diesel::Queryable, diesel::Insertable)]
+#[table_name = "song_ratings"]
+pub struct SongRating {
+    /// The track that was rated.
+    pub track_id: TrackId,
+    /// The user that rated the track.
+    pub user: String,
+    /// The rating, `1` for a like and `-1` for a dislike.
+    pub rating: i32,
+    /// When the track was last rated.
+    pub rated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, serde::Serialize, diesel::Queryable)]
+pub struct ModerationEntry {
+    /// ID of the log entry.
+    pub id: i32,
+    /// The channel the action was taken in.
+    pub channel: String,
+    /// The kind of action.
+    pub kind: String,
+    /// The user the action was taken against, if any.
+    pub user: Option<String>,
+    /// The moderator that took the action, if known.
+    pub moderator: Option<String>,
+    /// The duration of a timeout, in seconds.
+    pub duration: Option<i32>,
+    /// The reason for the action, if known.
+    pub reason: Option<String>,
+    /// The deleted message, or the room mode that changed.
+    pub message: Option<String>,
+    /// When the action was taken.
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, diesel::Insertable)]
+#[table_name = "moderation_log"]
+pub struct InsertModerationEntry {
+    pub channel: String,
+    pub kind: String,
+    pub user: Option<String>,
+    pub moderator: Option<String>,
+    pub duration: Option<i32>,
+    pub reason: Option<String>,
+    pub message: Option<String>,
+    pub created_at: NaiveDateTime,
+}
@@ -0,0 +1,137 @@
+use crate::db::{self, models, schema};
+use chrono::Utc;
+use diesel::{prelude::*, sqlite::Sqlite};
+
+pub use self::models::{InsertModerationEntry, ModerationEntry};
+
+/// The kind of moderation action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModerationKind {
+    /// A user was timed out.
+    Timeout,
+    /// A user was banned.
+    Ban,
+    /// A single message was deleted.
+    Delete,
+    /// The whole chat was cleared.
+    Clear,
+    /// A mode of the channel changed, like slow mode or subscribers only.
+    RoomState,
+}
+
+impl ModerationKind {
+    /// Get the kind as a string.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            ModerationKind::Timeout => "timeout",
+            ModerationKind::Ban => "ban",
+            ModerationKind::Delete => "delete",
+            ModerationKind::Clear => "clear",
+            ModerationKind::RoomState => "room-state",
+        }
+    }
+}
+
+impl InsertModerationEntry {
+    /// Construct an entry for an action of the given kind taken now, without any details.
+    pub fn new(channel: &str, kind: ModerationKind) -> Self {
+        InsertModerationEntry {
+            channel: channel.to_string(),
+            kind: kind.as_str().to_string(),
+            user: None,
+            moderator: None,
+            duration: None,
+            reason: None,
+            message: None,
+            created_at: Utc::now().naive_utc(),
+        }
+    }
+}
+
+/// Persistent log of moderation actions taken in chat.
+#[derive(Clone)]
+pub struct ModerationLog {
+    db: db::Database,
+}
+
+impl ModerationLog {
+    /// Open the moderation log database.
+    pub fn load(db: db::Database) -> Result<Self, failure::Error> {
+        Ok(ModerationLog { db })
+    }
+
+    /// Push the given entry to the log.
+    pub fn push(&self, entry: &InsertModerationEntry) -> Result<(), failure::Error> {
+        use self::schema::moderation_log::dsl;
+        let c = self.db.pool.lock();
+
+        diesel::insert_into(dsl::moderation_log)
+            .values(entry)
+            .execute(&*c)?;
+
+        Ok(())
+    }
+
+    /// Push the given entry to the log, but only log on issues.
+    pub fn push_log(&self, entry: &InsertModerationEntry) {
+        if let Err(e) = self.push(entry) {
+            log::warn!(
+                "{}: failed to store moderation action: {}",
+                entry.channel,
+                e
+            );
+        }
+    }
+
+    /// List actions, with the most recent first.
+    ///
+    /// Optionally only lists actions in the given channel, or against the given user.
+    pub fn list(
+        &self,
+        channel: Option<&str>,
+        user: Option<&str>,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<ModerationEntry>, failure::Error> {
+        use self::schema::moderation_log::dsl;
+        let c = self.db.pool.lock();
+
+        let user = user.map(|u| u.to_lowercase());
+
+        Ok(filter(channel, user.as_ref().map(String::as_str))
+            .order((dsl::created_at.desc(), dsl::id.desc()))
+            .offset(offset)
+            .limit(limit)
+            .load::<models::ModerationEntry>(&*c)?)
+    }
+
+    /// Count the number of actions, with the same filters as `list`.
+    pub fn count(&self, channel: Option<&str>, user: Option<&str>) -> Result<i64, failure::Error> {
+        let c = self.db.pool.lock();
+        let user = user.map(|u| u.to_lowercase());
+
+        Ok(filter(channel, user.as_ref().map(String::as_str))
+            .count()
+            .get_result::<i64>(&*c)?)
+    }
+}
+
+/// Build a query for actions in the given channel and against the given user.
+fn filter<'a>(
+    channel: Option<&'a str>,
+    user: Option<&'a str>,
+) -> schema::moderation_log::BoxedQuery<'a, Sqlite> {
+    use self::schema::moderation_log::dsl;
+
+    let mut query = dsl::moderation_log.into_boxed();
+
+    if let Some(channel) = channel {
+        query = query.filter(dsl::channel.eq(channel));
+    }
+
+    if let Some(user) = user {
+        query = query.filter(dsl::user.eq(user));
+    }
+
+    query
+}
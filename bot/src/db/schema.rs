@@ -43,6 +43,7 @@ table! {
         promoted_at -> Nullable<Timestamp>,
         promoted_by -> Nullable<Text>,
         user -> Nullable<Text>,
+        position -> Nullable<Integer>,
     }
 }
 
@@ -111,3 +112,55 @@ table! {
         value -> Text,
     }
 }
+
+// Songs that have been played.
+table! {
+    song_history (id) {
+        id -> Integer,
+        track_id -> Text,
+        name -> Text,
+        artists -> Nullable<Text>,
+        user -> Nullable<Text>,
+        started_at -> Timestamp,
+        ended_at -> Timestamp,
+        elapsed -> Integer,
+        duration -> Integer,
+        reason -> Text,
+    }
+}
+
+// Tracks, artists, and channels that are banned from being requested.
+table! {
+    song_bans (kind, id) {
+        kind -> Text,
+        id -> Text,
+        reason -> Nullable<Text>,
+        banned_by -> Nullable<Text>,
+        banned_at -> Timestamp,
+    }
+}
+
+// Likes and dislikes of songs by users.
+table! {
+    song_ratings (track_id, user) {
+        track_id -> Text,
+        user -> Text,
+        rating -> Integer,
+        rated_at -> Timestamp,
+    }
+}
+
+// Moderation actions taken in chat.
+table! {
+    moderation_log (id) {
+        id -> Integer,
+        channel -> Text,
+        kind -> Text,
+        user -> Nullable<Text>,
+        moderator -> Nullable<Text>,
+        duration -> Nullable<Integer>,
+        reason -> Nullable<Text>,
+        message -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
@@ -0,0 +1,285 @@
+use crate::{
+    db::{self, models},
+    track_id::TrackId,
+};
+use chrono::Utc;
+use diesel::prelude::*;
+use failure::{bail, format_err};
+use hashbrown::HashMap;
+use parking_lot::RwLock;
+use std::{fmt, sync::Arc};
+
+pub use self::models::SongBan;
+
+/// The kind of thing that can be banned from being requested.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum BanKind {
+    /// A single Spotify track or YouTube video.
+    #[serde(rename = "track")]
+    Track,
+    /// A Spotify artist.
+    #[serde(rename = "artist")]
+    Artist,
+    /// A YouTube channel.
+    #[serde(rename = "channel")]
+    Channel,
+}
+
+impl BanKind {
+    /// Get the kind as a string.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            BanKind::Track => "track",
+            BanKind::Artist => "artist",
+            BanKind::Channel => "channel",
+        }
+    }
+
+    /// Parse the id of something to ban, accepting URIs and URLs.
+    ///
+    /// The returned id is in the form that is stored in the database.
+    pub fn parse_id(&self, s: &str) -> Result<String, failure::Error> {
+        match *self {
+            BanKind::Track => Ok(TrackId::parse_with_urls(s)?.to_string()),
+            BanKind::Artist => parse_spotify_artist(s),
+            BanKind::Channel => parse_youtube_channel(s),
+        }
+    }
+}
+
+impl std::str::FromStr for BanKind {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "track" => Ok(BanKind::Track),
+            "artist" => Ok(BanKind::Artist),
+            "channel" => Ok(BanKind::Channel),
+            other => bail!(
+                "bad ban kind `{}`, expected one of: track, artist, or channel",
+                other
+            ),
+        }
+    }
+}
+
+impl fmt::Display for BanKind {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_str().fmt(fmt)
+    }
+}
+
+#[derive(Clone)]
+pub struct SongBans {
+    inner: Arc<RwLock<HashMap<(BanKind, String), Arc<SongBan>>>>,
+    db: db::Database,
+}
+
+impl SongBans {
+    /// Load all song bans from the database.
+    pub fn load(db: db::Database) -> Result<SongBans, failure::Error> {
+        use db::schema::song_bans::dsl;
+
+        let mut inner = HashMap::new();
+
+        let bans = dsl::song_bans.load::<SongBan>(&*db.pool.lock())?;
+
+        for ban in bans {
+            let kind = match str::parse::<BanKind>(&ban.kind) {
+                Ok(kind) => kind,
+                Err(e) => {
+                    log::warn!("ignoring song ban `{}`: {}", ban.id, e);
+                    continue;
+                }
+            };
+
+            inner.insert((kind, ban.id.clone()), Arc::new(ban));
+        }
+
+        Ok(SongBans {
+            inner: Arc::new(RwLock::new(inner)),
+            db,
+        })
+    }
+
+    /// Ban the given id, replacing any existing ban.
+    pub fn insert(
+        &self,
+        kind: BanKind,
+        id: &str,
+        reason: Option<&str>,
+        banned_by: Option<&str>,
+    ) -> Result<(), failure::Error> {
+        use db::schema::song_bans::dsl;
+
+        let ban = SongBan {
+            kind: kind.to_string(),
+            id: id.to_string(),
+            reason: reason.map(String::from),
+            banned_by: banned_by.map(String::from),
+            banned_at: Utc::now().naive_utc(),
+        };
+
+        let mut inner = self.inner.write();
+
+        diesel::replace_into(dsl::song_bans)
+            .values(&ban)
+            .execute(&*self.db.pool.lock())?;
+
+        inner.insert((kind, id.to_string()), Arc::new(ban));
+        Ok(())
+    }
+
+    /// Remove the ban for the given id.
+    pub fn delete(&self, kind: BanKind, id: &str) -> Result<bool, failure::Error> {
+        use db::schema::song_bans::dsl;
+
+        let mut inner = self.inner.write();
+
+        let count =
+            diesel::delete(dsl::song_bans.filter(dsl::kind.eq(kind.as_str()).and(dsl::id.eq(id))))
+                .execute(&*self.db.pool.lock())?;
+
+        inner.remove(&(kind, id.to_string()));
+        Ok(count == 1)
+    }
+
+    /// Get the ban for the given id, if it is banned.
+    pub fn get(&self, kind: BanKind, id: &str) -> Option<Arc<SongBan>> {
+        self.inner.read().get(&(kind, id.to_string())).cloned()
+    }
+
+    /// List all bans, with the most recent first.
+    pub fn list(&self) -> Vec<Arc<SongBan>> {
+        let mut out = self.inner.read().values().cloned().collect::<Vec<_>>();
+        out.sort_by(|a, b| b.banned_at.cmp(&a.banned_at));
+        out
+    }
+}
+
+/// Parse a Spotify artist from an URI, an URL, or a plain id.
+fn parse_spotify_artist(s: &str) -> Result<String, failure::Error> {
+    if s.starts_with("spotify:artist:") {
+        return Ok(s.trim_start_matches("spotify:artist:").to_string());
+    }
+
+    if let Ok(url) = str::parse::<url::Url>(s) {
+        if url.host() != Some(url::Host::Domain("open.spotify.com")) {
+            bail!("bad host, expected: open.spotify.com");
+        }
+
+        let parts = url.path().split("/").collect::<Vec<_>>();
+
+        return match parts.as_slice() {
+            &["", "artist", id] => Ok(id.to_string()),
+            _ => Err(format_err!(
+                "bad URL, expected: https://open.spotify.com/artist/<id>"
+            )),
+        };
+    }
+
+    Ok(s.to_string())
+}
+
+/// Parse a YouTube channel from an URL or a plain id.
+fn parse_youtube_channel(s: &str) -> Result<String, failure::Error> {
+    if let Ok(url) = str::parse::<url::Url>(s) {
+        let is_youtube = match url.host() {
+            Some(url::Host::Domain("youtube.com")) => true,
+            Some(url::Host::Domain("www.youtube.com")) => true,
+            _ => false,
+        };
+
+        if !is_youtube {
+            bail!("bad host, expected: youtube.com");
+        }
+
+        let parts = url.path().split("/").collect::<Vec<_>>();
+
+        return match parts.as_slice() {
+            &["", "channel", id] => Ok(id.to_string()),
+            _ => Err(format_err!(
+                "bad URL, expected: https://youtube.com/channel/<id>"
+            )),
+        };
+    }
+
+    Ok(s.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BanKind;
+
+    #[test]
+    fn test_parse_kind() {
+        assert_eq!(BanKind::Track, str::parse::<BanKind>("track").unwrap());
+        assert_eq!(BanKind::Artist, str::parse::<BanKind>("artist").unwrap());
+        assert_eq!(BanKind::Channel, str::parse::<BanKind>("channel").unwrap());
+        assert!(str::parse::<BanKind>("album").is_err());
+    }
+
+    #[test]
+    fn test_parse_track() {
+        let uri = "spotify:track:4uLU6hMCjMI75M1A2tKUQC";
+
+        assert_eq!(uri, BanKind::Track.parse_id(uri).unwrap());
+        assert_eq!(
+            uri,
+            BanKind::Track
+                .parse_id("https://open.spotify.com/track/4uLU6hMCjMI75M1A2tKUQC")
+                .unwrap()
+        );
+        assert_eq!(
+            "youtube:video:dQw4w9WgXcQ",
+            BanKind::Track
+                .parse_id("https://youtu.be/dQw4w9WgXcQ")
+                .unwrap()
+        );
+        assert!(BanKind::Track.parse_id("4uLU6hMCjMI75M1A2tKUQC").is_err());
+    }
+
+    #[test]
+    fn test_parse_artist() {
+        let id = "0gxyHStUsqpMadRV0Di1Qt";
+
+        assert_eq!(id, BanKind::Artist.parse_id(id).unwrap());
+        assert_eq!(
+            id,
+            BanKind::Artist
+                .parse_id("spotify:artist:0gxyHStUsqpMadRV0Di1Qt")
+                .unwrap()
+        );
+        assert_eq!(
+            id,
+            BanKind::Artist
+                .parse_id("https://open.spotify.com/artist/0gxyHStUsqpMadRV0Di1Qt")
+                .unwrap()
+        );
+        assert!(BanKind::Artist
+            .parse_id("https://example.com/artist/0gxyHStUsqpMadRV0Di1Qt")
+            .is_err());
+        assert!(BanKind::Artist
+            .parse_id("https://open.spotify.com/album/0gxyHStUsqpMadRV0Di1Qt")
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_channel() {
+        let id = "UC_x5XG1OV2P6uZZ5FSM9Ttw";
+
+        assert_eq!(id, BanKind::Channel.parse_id(id).unwrap());
+        assert_eq!(
+            id,
+            BanKind::Channel
+                .parse_id("https://www.youtube.com/channel/UC_x5XG1OV2P6uZZ5FSM9Ttw")
+                .unwrap()
+        );
+        assert!(BanKind::Channel
+            .parse_id("https://youtube.com/user/GoogleDevelopers")
+            .is_err());
+        assert!(BanKind::Channel
+            .parse_id("https://vimeo.com/channel/UC_x5XG1OV2P6uZZ5FSM9Ttw")
+            .is_err());
+    }
+}
@@ -0,0 +1,360 @@
+use crate::{
+    db::{self, models},
+    track_id::TrackId,
+};
+use chrono::{NaiveDateTime, Utc};
+use diesel::{dsl::sql, prelude::*, sql_types::BigInt};
+use hashbrown::HashMap;
+
+/// A rating of a song.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rating {
+    Like,
+    Dislike,
+}
+
+impl Rating {
+    /// Get the value of the rating as it is stored in the database.
+    fn value(self) -> i32 {
+        match self {
+            Rating::Like => 1,
+            Rating::Dislike => -1,
+        }
+    }
+}
+
+/// Likes and dislikes of a single track.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct Ratings {
+    pub likes: i64,
+    pub dislikes: i64,
+}
+
+/// A track together with a count, used for statistics.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TrackCount {
+    pub track_id: TrackId,
+    /// The name of the track, if it has been played before.
+    pub name: Option<String>,
+    pub count: i64,
+}
+
+/// A user together with a count, used for statistics.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct UserCount {
+    pub user: String,
+    pub count: i64,
+}
+
+/// Song ratings and statistics over requested and played songs.
+#[derive(Clone)]
+pub struct SongStats {
+    db: db::Database,
+}
+
+impl SongStats {
+    /// Open the song statistics database.
+    pub fn load(db: db::Database) -> Result<Self, failure::Error> {
+        Ok(SongStats { db })
+    }
+
+    /// Rate the given track on behalf of the given user, replacing any earlier rating.
+    pub fn rate(
+        &self,
+        track_id: &TrackId,
+        user: &str,
+        rating: Rating,
+    ) -> Result<(), failure::Error> {
+        use db::schema::song_ratings::dsl;
+        let c = self.db.pool.lock();
+
+        let rating = models::SongRating {
+            track_id: track_id.clone(),
+            user: user.to_string(),
+            rating: rating.value(),
+            rated_at: Utc::now().naive_utc(),
+        };
+
+        diesel::replace_into(dsl::song_ratings)
+            .values(&rating)
+            .execute(&*c)?;
+
+        Ok(())
+    }
+
+    /// Get the likes and dislikes of the given track.
+    ///
+    /// If `since` is specified, only ratings made since then are counted.
+    pub fn ratings(
+        &self,
+        track_id: &TrackId,
+        since: Option<NaiveDateTime>,
+    ) -> Result<Ratings, failure::Error> {
+        use db::schema::song_ratings::dsl;
+        let c = self.db.pool.lock();
+
+        let mut query = dsl::song_ratings
+            .select((dsl::rating, sql::<BigInt>("COUNT(*)")))
+            .filter(dsl::track_id.eq(track_id))
+            .group_by(dsl::rating)
+            .into_boxed();
+
+        if let Some(since) = since {
+            query = query.filter(dsl::rated_at.ge(since));
+        }
+
+        let counts = query.load::<(i32, i64)>(&*c)?;
+
+        let mut ratings = Ratings::default();
+
+        for (rating, count) in counts {
+            if rating > 0 {
+                ratings.likes += count;
+            } else {
+                ratings.dislikes += count;
+            }
+        }
+
+        Ok(ratings)
+    }
+
+    /// Get the tracks which have been requested the most.
+    pub fn most_requested(&self, limit: i64) -> Result<Vec<TrackCount>, failure::Error> {
+        use db::schema::songs::dsl;
+
+        let counts = {
+            let c = self.db.pool.lock();
+
+            dsl::songs
+                .select((dsl::track_id, sql::<BigInt>("COUNT(*) AS count")))
+                .filter(dsl::user.is_not_null())
+                .group_by(dsl::track_id)
+                .order(sql::<BigInt>("count").desc())
+                .limit(limit)
+                .load::<(TrackId, i64)>(&*c)?
+        };
+
+        self.with_names(counts)
+    }
+
+    /// Get the tracks with the most likes, after subtracting dislikes.
+    pub fn most_liked(&self, limit: i64) -> Result<Vec<TrackCount>, failure::Error> {
+        use db::schema::song_ratings::dsl;
+
+        let counts = {
+            let c = self.db.pool.lock();
+
+            dsl::song_ratings
+                .select((dsl::track_id, sql::<BigInt>("SUM(rating) AS score")))
+                .group_by(dsl::track_id)
+                .order(sql::<BigInt>("score").desc())
+                .limit(limit)
+                .load::<(TrackId, i64)>(&*c)?
+        };
+
+        self.with_names(counts)
+    }
+
+    /// Get the tracks which have been played the most.
+    pub fn most_played(&self, limit: i64) -> Result<Vec<TrackCount>, failure::Error> {
+        use db::schema::song_history::dsl;
+
+        let counts = {
+            let c = self.db.pool.lock();
+
+            dsl::song_history
+                .select((dsl::track_id, sql::<BigInt>("COUNT(*) AS count")))
+                .group_by(dsl::track_id)
+                .order(sql::<BigInt>("count").desc())
+                .limit(limit)
+                .load::<(TrackId, i64)>(&*c)?
+        };
+
+        self.with_names(counts)
+    }
+
+    /// Get the tracks which have most recently been requested by users, most recent first.
+    pub fn recent_requests(&self, limit: i64) -> Result<Vec<TrackId>, failure::Error> {
+        use db::schema::songs::dsl;
+        let c = self.db.pool.lock();
+
+        Ok(dsl::songs
+            .select(dsl::track_id)
+            .filter(dsl::user.is_not_null())
+            .group_by(dsl::track_id)
+            .order(sql::<BigInt>("MAX(added_at)").desc())
+            .limit(limit)
+            .load::<TrackId>(&*c)?)
+    }
+
+    /// Get the number of times the given track has been played.
+    pub fn play_count(&self, track_id: &TrackId) -> Result<i64, failure::Error> {
+        use db::schema::song_history::dsl;
+        let c = self.db.pool.lock();
+
+        Ok(dsl::song_history
+            .filter(dsl::track_id.eq(track_id))
+            .count()
+            .get_result::<i64>(&*c)?)
+    }
+
+    /// Get the users which have requested the most songs.
+    pub fn top_requesters(&self, limit: i64) -> Result<Vec<UserCount>, failure::Error> {
+        use db::schema::songs::dsl;
+        let c = self.db.pool.lock();
+
+        let counts = dsl::songs
+            .select((dsl::user, sql::<BigInt>("COUNT(*) AS count")))
+            .filter(dsl::user.is_not_null())
+            .group_by(dsl::user)
+            .order(sql::<BigInt>("count").desc())
+            .limit(limit)
+            .load::<(Option<String>, i64)>(&*c)?;
+
+        Ok(counts
+            .into_iter()
+            .filter_map(|(user, count)| Some(UserCount { user: user?, count }))
+            .collect())
+    }
+
+    /// Look up the names of the given tracks from the play history.
+    fn with_names(&self, counts: Vec<(TrackId, i64)>) -> Result<Vec<TrackCount>, failure::Error> {
+        use db::schema::song_history::dsl;
+
+        let ids = counts
+            .iter()
+            .map(|(track_id, _)| track_id)
+            .collect::<Vec<_>>();
+
+        let names = {
+            let c = self.db.pool.lock();
+
+            // NB: ordered so that the most recently played name of each track is inserted last.
+            dsl::song_history
+                .select((dsl::track_id, dsl::name))
+                .filter(dsl::track_id.eq_any(ids))
+                .order(dsl::started_at.asc())
+                .load::<(TrackId, String)>(&*c)?
+        };
+
+        let mut names = names.into_iter().collect::<HashMap<_, _>>();
+
+        Ok(counts
+            .into_iter()
+            .map(|(track_id, count)| {
+                let name = names.remove(&track_id);
+
+                TrackCount {
+                    track_id,
+                    name,
+                    count,
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Rating, SongStats};
+    use crate::{
+        db::{self, models},
+        track_id::TrackId,
+    };
+    use chrono::{Duration, Utc};
+
+    fn track(id: &str) -> TrackId {
+        format!("spotify:track:{}", id)
+            .parse::<TrackId>()
+            .expect("track id")
+    }
+
+    fn played(db: &db::Database, track_id: &TrackId, name: &str, minutes_ago: i64) {
+        let started_at = (Utc::now() - Duration::minutes(minutes_ago)).naive_utc();
+
+        db::History::load(db.clone())
+            .expect("history")
+            .push(&models::InsertHistoryEntry {
+                track_id: track_id.clone(),
+                name: name.to_string(),
+                artists: None,
+                user: None,
+                started_at,
+                ended_at: started_at,
+                elapsed: 0,
+                duration: 0,
+                reason: String::from("end of track"),
+            })
+            .expect("push history");
+    }
+
+    #[test]
+    fn test_ratings() {
+        let stats = SongStats::load(db::Database::memory().expect("database")).expect("stats");
+        let a = track("4uLU6hMCjMI75M1A2tKUQC");
+
+        stats.rate(&a, "alice", Rating::Like).expect("rate");
+        stats.rate(&a, "bob", Rating::Dislike).expect("rate");
+        stats.rate(&a, "carol", Rating::Dislike).expect("rate");
+        // NB: rating again replaces the earlier rating.
+        stats.rate(&a, "alice", Rating::Dislike).expect("rate");
+
+        let ratings = stats.ratings(&a, None).expect("ratings");
+        assert_eq!(0, ratings.likes);
+        assert_eq!(3, ratings.dislikes);
+
+        let past = (Utc::now() - Duration::hours(1)).naive_utc();
+        let ratings = stats.ratings(&a, Some(past)).expect("ratings");
+        assert_eq!(3, ratings.dislikes);
+
+        let future = (Utc::now() + Duration::hours(1)).naive_utc();
+        let ratings = stats.ratings(&a, Some(future)).expect("ratings");
+        assert_eq!(0, ratings.likes);
+        assert_eq!(0, ratings.dislikes);
+    }
+
+    #[test]
+    fn test_most_liked() {
+        let db = db::Database::memory().expect("database");
+        let stats = SongStats::load(db.clone()).expect("stats");
+        let a = track("4uLU6hMCjMI75M1A2tKUQC");
+        let b = track("6rqhFgbbKwnb9MLmUQDhG6");
+
+        played(&db, &a, "Old Name", 20);
+        played(&db, &a, "New Name", 10);
+
+        stats.rate(&a, "alice", Rating::Like).expect("rate");
+        stats.rate(&a, "bob", Rating::Like).expect("rate");
+        stats.rate(&b, "alice", Rating::Like).expect("rate");
+        stats.rate(&b, "bob", Rating::Dislike).expect("rate");
+
+        let liked = stats.most_liked(5).expect("most liked");
+        assert_eq!(2, liked.len());
+
+        assert_eq!(a, liked[0].track_id);
+        assert_eq!(Some("New Name"), liked[0].name.as_ref().map(String::as_str));
+        assert_eq!(2, liked[0].count);
+
+        assert_eq!(b, liked[1].track_id);
+        assert_eq!(None, liked[1].name);
+        assert_eq!(0, liked[1].count);
+    }
+
+    #[test]
+    fn test_most_played() {
+        let db = db::Database::memory().expect("database");
+        let stats = SongStats::load(db.clone()).expect("stats");
+        let a = track("4uLU6hMCjMI75M1A2tKUQC");
+        let b = track("6rqhFgbbKwnb9MLmUQDhG6");
+
+        played(&db, &a, "A", 30);
+        played(&db, &b, "B", 20);
+        played(&db, &b, "B", 10);
+
+        let played = stats.most_played(1).expect("most played");
+        assert_eq!(1, played.len());
+        assert_eq!(b, played[0].track_id);
+        assert_eq!(2, played[0].count);
+        assert_eq!(1, stats.play_count(&a).expect("play count"));
+    }
+}
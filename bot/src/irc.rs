@@ -5,13 +5,15 @@ use crate::{
     currency::{Currency, CurrencyBuilder},
     db, idle,
     injector::Injector,
+    last_seen::LastSeen,
     module, oauth2,
+    player::Player,
     prelude::*,
     settings, stream_info, template, timer,
     utils::{self, Cooldown, Duration},
 };
 use failure::{bail, format_err, Error, ResultExt as _};
-use hashbrown::HashSet;
+use hashbrown::{HashMap, HashSet};
 use irc::{
     client::{self, ext::ClientExt, Client, IrcClient, PackedIrcClient},
     proto::{
@@ -19,19 +21,26 @@ use irc::{
         message::{Message, Tag},
     },
 };
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use std::{fmt, sync::Arc, time};
 use tokio_threadpool::ThreadPool;
 
 // re-exports
-pub use self::sender::Sender;
+pub use self::{
+    events::Event,
+    sender::{Priority, Sender, Window},
+};
 
 mod currency_admin;
+mod events;
 mod sender;
+mod strikes;
 
 const SERVER: &'static str = "irc.chat.twitch.tv";
 const TWITCH_TAGS_CAP: &'static str = "twitch.tv/tags";
 const TWITCH_COMMANDS_CAP: &'static str = "twitch.tv/commands";
+/// How long to remember why the bot took a moderation action which Twitch hasn't confirmed.
+const MODERATION_REASON_TIMEOUT: time::Duration = time::Duration::from_secs(60);
 
 /// Configuration for twitch integration.
 #[derive(Debug, Default, serde::Deserialize)]
@@ -75,6 +84,7 @@ pub struct Irc {
     pub themes: db::Themes,
     pub bad_words: db::Words,
     pub after_streams: db::AfterStreams,
+    pub moderation_log: db::ModerationLog,
     pub global_bus: Arc<bus::Bus<bus::Global>>,
     pub modules: Vec<Box<dyn module::Module>>,
     pub shutdown: utils::Shutdown,
@@ -82,32 +92,13 @@ pub struct Irc {
     pub auth: Auth,
     pub global_channel: Arc<RwLock<Option<String>>>,
     pub injector: Injector,
+    pub last_seen: LastSeen,
 }
 
 impl Irc {
     pub async fn run(self) -> Result<(), Error> {
-        let Irc {
-            db,
-            youtube,
-            nightbot,
-            streamer_twitch,
-            bot_twitch,
-            config,
-            token,
-            commands,
-            aliases,
-            promotions,
-            themes,
-            bad_words,
-            after_streams,
-            global_bus,
-            modules,
-            shutdown,
-            settings,
-            auth,
-            global_channel,
-            injector,
-        } = self;
+        let config = &self.config;
+        let settings = &self.settings;
 
         if config.streamer.is_some() {
             log::warn!("`streamer` setting has been deprecated from the configuration");
@@ -141,18 +132,25 @@ impl Irc {
             log::warn!("`[currency]` setting has been deprecated from the configuration");
         }
 
+        let (mut channels_stream, mut channels) = settings
+            .stream::<Vec<String>>("irc/channels")
+            .or_default()?;
+
+        // NB: Twitch rate limits messages across all channels where the bot is not a moderator.
+        let window = Arc::new(Mutex::new(Window::default()));
+
         loop {
             log::trace!("Waiting for token to become ready");
 
             future::try_join(
-                streamer_twitch.token.wait_until_ready(),
-                token.wait_until_ready(),
+                self.streamer_twitch.token.wait_until_ready(),
+                self.token.wait_until_ready(),
             )
             .await?;
 
             let (bot_info, streamer_info) = future::try_join(
-                bot_twitch.validate_token(),
-                streamer_twitch.validate_token(),
+                self.bot_twitch.validate_token(),
+                self.streamer_twitch.validate_token(),
             )
             .await?;
 
@@ -162,7 +160,10 @@ impl Irc {
             let streamer = streamer_info.login.to_lowercase();
             let streamer = streamer.as_str();
 
-            let channel = Arc::new(format!("#{}", streamer));
+            let channel = format!("#{}", streamer);
+
+            // NB: the streamer is always around for their own requests.
+            self.last_seen.exempt(streamer);
 
             // TODO: remove this migration next major release.
             if !config.aliases.is_empty() {
@@ -176,7 +177,8 @@ impl Irc {
 
                     for alias in &config.aliases {
                         let template = template::Template::compile(&alias.replace)?;
-                        aliases.edit(channel.as_str(), &alias.r#match, template)?;
+                        self.aliases
+                            .edit(channel.as_str(), &alias.r#match, template)?;
                     }
 
                     settings.set("migration/aliases-migrated", true)?;
@@ -195,8 +197,9 @@ impl Irc {
 
                     for (name, theme) in &config.themes.themes {
                         let track_id = theme.track.clone();
-                        themes.edit(channel.as_str(), name.as_str(), track_id)?;
-                        themes.edit_duration(
+                        self.themes
+                            .edit(channel.as_str(), name.as_str(), track_id)?;
+                        self.themes.edit_duration(
                             channel.as_str(),
                             name.as_str(),
                             theme.offset.clone(),
@@ -208,223 +211,419 @@ impl Irc {
                 }
             }
 
-            *global_channel.write() = Some(channel.to_string());
+            if !config.whitelisted_hosts.is_empty() {
+                if !settings
+                    .get::<bool>("migration/whitelisted-hosts-migrated")?
+                    .unwrap_or_default()
+                {
+                    log::warn!("Performing a one time migration of hosts from configuration.");
+                    settings.set("irc/whitelisted-hosts", &config.whitelisted_hosts)?;
+                    settings.set("migration/whitelisted-hosts-migrated", true)?;
+                }
+            }
 
-            let access_token = token.read()?.access_token().to_string();
+            *self.global_channel.write() = Some(channel);
 
-            let irc_client_config = client::data::config::Config {
-                nickname: Some(bot.to_string()),
-                channels: Some(vec![(*channel).clone()]),
-                password: Some(format!("oauth:{}", access_token)),
-                server: Some(String::from(SERVER)),
-                port: Some(6697),
-                use_ssl: Some(true),
-                ..client::data::config::Config::default()
-            };
+            // NB: the channel of the streamer always comes first, and uses the global settings which
+            // every other channel falls back to.
+            let mut logins = vec![streamer.to_string()];
 
-            let client = IrcClient::new_future(irc_client_config)?;
+            for login in &channels {
+                let login = login.trim_start_matches('#').to_lowercase();
 
-            let PackedIrcClient(client, send_future) = client.compat().await?;
-            client.identify()?;
+                if !login.is_empty() && !logins.contains(&login) {
+                    logins.push(login);
+                }
+            }
 
-            let mut vars = settings.scoped("irc").vars();
-            let url_whitelist_enabled = vars.var("url-whitelist/enabled", true)?;
-            let bad_words_enabled = vars.var("bad-words/enabled", false)?;
-            let sender_ty = vars.var("sender-type", sender::Type::Chat)?;
-            let threshold = vars.var("idle-detection/threshold", 5)?;
-            let idle = idle::Idle::new(threshold);
+            let mut futures = Vec::new();
 
-            let sender = Sender::new(sender_ty, channel.clone(), client.clone(), nightbot.clone());
+            for login in &logins {
+                let primary = login.as_str() == streamer;
 
-            let mut futures = Vec::<future::BoxFuture<'_, Result<(), Error>>>::new();
-            futures.push(vars.run().boxed());
+                let settings = match primary {
+                    true => settings.clone(),
+                    false => settings.channel(login),
+                };
 
-            let stream_info = {
-                let interval = time::Duration::from_secs(60 * 5);
-                let (stream_info, future) =
-                    stream_info::setup(streamer, interval, streamer_twitch.clone());
-                futures.push(future.boxed());
-                stream_info
-            };
+                let future = self.supervise_channel(bot, login, settings, window.clone(), primary);
 
-            let mut handlers = module::Handlers::default();
+                futures.push(future.boxed());
+            }
 
-            futures.push(refresh_mods_future(sender.clone()).boxed());
+            let mut future = future::try_join_all(futures).fuse();
 
-            for module in modules.iter() {
-                if log::log_enabled!(log::Level::Trace) {
-                    log::trace!("initializing module: {}", module.ty());
+            futures::select! {
+                result = future => {
+                    match result {
+                        Ok(_) => break,
+                        Err(e) => {
+                            log::warn!("IRC component errored, restarting in 5 seconds: {}", e);
+                            let delay = time::Instant::now() + time::Duration::from_secs(5);
+                            timer::Delay::new(delay).await?;
+                            continue;
+                        }
+                    }
+                }
+                update = channels_stream.select_next_some() => {
+                    log::info!("Channels to join changed, reconnecting");
+                    channels = update;
+                    continue;
                 }
+            }
+        }
+
+        Ok(())
+    }
 
-                let result = module.hook(module::HookContext {
-                    handlers: &mut handlers,
-                    futures: &mut futures,
-                    stream_info: &stream_info,
-                    idle: &idle,
-                    config: &*config,
-                    db: &db,
-                    commands: &commands,
-                    aliases: &aliases,
-                    promotions: &promotions,
-                    themes: &themes,
-                    after_streams: &after_streams,
-                    youtube: &youtube,
-                    twitch: &bot_twitch,
-                    streamer_twitch: &streamer_twitch,
-                    sender: &sender,
-                    settings: &settings,
-                    injector: &injector,
-                });
-
-                result.with_context(|_| {
-                    format_err!("failed to initialize module: {}", module.ty())
-                })?;
+    /// Join and serve a single channel, restarting it if it errors without affecting any other
+    /// channels.
+    async fn supervise_channel<'a>(
+        &'a self,
+        bot: &'a str,
+        streamer: &'a str,
+        settings: settings::Settings,
+        window: Arc<Mutex<Window>>,
+        primary: bool,
+    ) -> Result<(), Error> {
+        loop {
+            let future = self.run_channel(bot, streamer, settings.clone(), window.clone(), primary);
+
+            match future.await {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    log::warn!(
+                        "IRC component for #{} errored, restarting in 5 seconds: {}",
+                        streamer,
+                        e
+                    );
+
+                    let delay = time::Instant::now() + time::Duration::from_secs(5);
+                    timer::Delay::new(delay).await?;
+                }
             }
+        }
+    }
+
+    /// Join and serve a single channel.
+    ///
+    /// `primary` indicates that this is the channel of the streamer, which is also responsible for
+    /// things that are shared across channels, like the player and the currency.
+    async fn run_channel<'a>(
+        &'a self,
+        bot: &'a str,
+        streamer: &'a str,
+        settings: settings::Settings,
+        window: Arc<Mutex<Window>>,
+        primary: bool,
+    ) -> Result<(), Error> {
+        let channel = Arc::new(format!("#{}", streamer));
+
+        let access_token = self.token.read()?.access_token().to_string();
+
+        let irc_client_config = client::data::config::Config {
+            nickname: Some(bot.to_string()),
+            channels: Some(vec![(*channel).clone()]),
+            password: Some(format!("oauth:{}", access_token)),
+            server: Some(String::from(SERVER)),
+            port: Some(6697),
+            use_ssl: Some(true),
+            ..client::data::config::Config::default()
+        };
+
+        let client = IrcClient::new_future(irc_client_config)?;
+
+        let PackedIrcClient(client, send_future) = client.compat().await?;
+        client.identify()?;
+
+        let mut vars = settings.scoped("irc").vars();
+        let url_whitelist_enabled = vars.var("url-whitelist/enabled", true)?;
+        let bad_words_enabled = vars.var("bad-words/enabled", false)?;
+        let url_whitelist_strikes =
+            vars.var("url-whitelist/strikes", strikes::Action::default_policy())?;
+        let bad_words_strikes = vars.var("bad-words/strikes", strikes::Action::default_policy())?;
+        let strikes_decay = vars.var("strikes/decay", utils::Duration::hours(1))?;
+        let sender_ty = vars.var("sender-type", sender::Type::Chat)?;
+        let threshold = vars.var("idle-detection/threshold", 5)?;
+        let event_settings = events::settings(&mut vars)?;
+        let idle = idle::Idle::new(threshold);
+
+        let (sender, queue_future) = Sender::new(
+            sender_ty,
+            channel.clone(),
+            client.clone(),
+            self.nightbot.clone(),
+            window,
+        );
 
-            let (future, currency_handler) = currency_admin::setup(&injector, &db)?;
+        let mut futures = Vec::<future::BoxFuture<'_, Result<(), Error>>>::new();
+        futures.push(vars.run().boxed());
+        futures.push(queue_future.boxed());
 
+        let (stream_info, stream_states) = {
+            let interval = time::Duration::from_secs(60 * 5);
+            // NB: subscriptions are only visible to the streamer.
+            let (stream_info, stream_states, future) =
+                stream_info::setup(streamer, interval, self.streamer_twitch.clone(), primary);
             futures.push(future.boxed());
+            (stream_info, stream_states)
+        };
 
-            let future = currency_loop(
-                &mut futures,
-                db.clone(),
-                streamer_twitch.clone(),
-                channel.clone(),
-                sender.clone(),
-                idle.clone(),
-                &injector,
-                &settings,
-            )?;
+        if primary {
+            let future = stream_state_loop(&mut futures, stream_states, &self.injector, &settings)?;
+            futures.push(future.boxed());
 
+            let future = last_seen_loop(
+                channel.clone(),
+                self.streamer_twitch.clone(),
+                self.last_seen.clone(),
+            );
             futures.push(future.boxed());
-            futures.push(send_future.compat().map_err(Error::from).boxed());
+        }
 
-            if !config.whitelisted_hosts.is_empty() {
-                if !settings
-                    .get::<bool>("migration/whitelisted-hosts-migrated")?
-                    .unwrap_or_default()
-                {
-                    log::warn!("Performing a one time migration of hosts from configuration.");
-                    settings.set("irc/whitelisted-hosts", &config.whitelisted_hosts)?;
-                    settings.set("migration/whitelisted-hosts-migrated", true)?;
-                }
+        let mut handlers = module::Handlers::default();
+
+        futures.push(refresh_mods_future(sender.clone()).boxed());
+
+        let disabled_modules = settings
+            .get::<Vec<String>>("irc/disabled-modules")?
+            .unwrap_or_default();
+
+        for module in self.modules.iter() {
+            if disabled_modules.iter().any(|m| m == module.ty()) {
+                log::trace!("module disabled in {}: {}", channel, module.ty());
+                continue;
             }
 
-            let (mut whitelisted_hosts_stream, whitelisted_hosts) =
-                settings.stream("irc/whitelisted-hosts").or_default()?;
-
-            let (mut moderator_cooldown_stream, moderator_cooldown) =
-                settings.stream("irc/moderator-cooldown").optional()?;
-
-            let startup_message = settings.get::<String>("irc/startup-message")?;
-
-            let mut pong_timeout = None;
-
-            let mut handler = Handler {
-                streamer,
-                sender: sender.clone(),
-                moderators: HashSet::default(),
-                vips: HashSet::default(),
-                whitelisted_hosts,
-                commands: &commands,
-                bad_words: &bad_words,
-                global_bus: &global_bus,
-                aliases: &aliases,
-                api_url: config.api_url.clone(),
-                thread_pool: Arc::new(ThreadPool::new()),
-                moderator_cooldown,
-                handlers,
-                shutdown: &shutdown,
-                idle: &idle,
-                pong_timeout: &mut pong_timeout,
-                token: &token,
-                handler_shutdown: false,
+            if log::log_enabled!(log::Level::Trace) {
+                log::trace!("initializing module: {}", module.ty());
+            }
+
+            let result = module.hook(module::HookContext {
+                handlers: &mut handlers,
+                futures: &mut futures,
                 stream_info: &stream_info,
-                auth: &auth,
-                currency_handler,
-                url_whitelist_enabled,
-                bad_words_enabled,
-            };
+                idle: &idle,
+                config: &*self.config,
+                db: &self.db,
+                commands: &self.commands,
+                aliases: &self.aliases,
+                promotions: &self.promotions,
+                themes: &self.themes,
+                after_streams: &self.after_streams,
+                moderation_log: &self.moderation_log,
+                youtube: &self.youtube,
+                twitch: &self.bot_twitch,
+                streamer_twitch: &self.streamer_twitch,
+                sender: &sender,
+                settings: &settings,
+                injector: &self.injector,
+            });
+
+            result.with_context(|_| format_err!("failed to initialize module: {}", module.ty()))?;
+        }
 
-            let mut client_stream = client.stream().compat().fuse();
-            let mut ping_interval = timer::Interval::new_interval(time::Duration::from_secs(10));
+        let (future, currency_handler) = currency_admin::setup(&self.injector, &self.db)?;
 
-            let future = async move {
-                handler.sender.cap_req(TWITCH_TAGS_CAP);
-                handler.sender.cap_req(TWITCH_COMMANDS_CAP);
+        futures.push(future.boxed());
 
-                if let Some(startup_message) = startup_message.as_ref() {
-                    // greeting when bot joins
-                    handler.sender.privmsg(startup_message);
-                }
+        if primary {
+            let future = currency_loop(
+                self.db.clone(),
+                self.streamer_twitch.clone(),
+                &self.injector,
+                &settings,
+            )?;
 
-                loop {
-                    futures::select! {
-                        update = moderator_cooldown_stream.select_next_some() => {
-                            handler.moderator_cooldown = update;
-                        }
-                        _ = ping_interval.select_next_some() => {
-                            handler.send_ping()?;
-                        }
-                        timeout = handler.pong_timeout.current() => {
-                            bail!("server not responding");
+            futures.push(future.boxed());
+        }
+
+        let future = reward_loop(
+            &mut futures,
+            channel.clone(),
+            sender.clone(),
+            idle.clone(),
+            &self.injector,
+            &settings,
+        )?;
+
+        futures.push(future.boxed());
+        futures.push(send_future.compat().map_err(Error::from).boxed());
+
+        let (mut whitelisted_hosts_stream, whitelisted_hosts) =
+            settings.stream("irc/whitelisted-hosts").or_default()?;
+
+        let (mut moderator_cooldown_stream, moderator_cooldown) =
+            settings.stream("irc/moderator-cooldown").optional()?;
+
+        let startup_message = settings.get::<String>("irc/startup-message")?;
+
+        let mut pong_timeout = None;
+
+        let mut handler = Handler {
+            streamer,
+            bot,
+            sender: sender.clone(),
+            moderators: HashSet::default(),
+            vips: HashSet::default(),
+            whitelisted_hosts,
+            commands: &self.commands,
+            bad_words: &self.bad_words,
+            global_bus: &self.global_bus,
+            aliases: &self.aliases,
+            api_url: self.config.api_url.clone(),
+            thread_pool: Arc::new(ThreadPool::new()),
+            moderator_cooldown,
+            handlers,
+            shutdown: &self.shutdown,
+            idle: &idle,
+            last_seen: &self.last_seen,
+            pong_timeout: &mut pong_timeout,
+            token: &self.token,
+            handler_shutdown: false,
+            stream_info: &stream_info,
+            auth: &self.auth,
+            currency_handler,
+            event_settings,
+            moderation_log: &self.moderation_log,
+            deletions: Default::default(),
+            timeouts: Default::default(),
+            room_state: None,
+            url_whitelist_enabled,
+            bad_words_enabled,
+            url_whitelist_strikes,
+            bad_words_strikes,
+            strikes_decay,
+            strikes: Default::default(),
+        };
+
+        let mut client_stream = client.stream().compat().fuse();
+        let mut ping_interval = timer::Interval::new_interval(time::Duration::from_secs(10));
+
+        let future = async move {
+            handler.sender.cap_req(TWITCH_TAGS_CAP);
+            handler.sender.cap_req(TWITCH_COMMANDS_CAP);
+
+            if let Some(startup_message) = startup_message.as_ref() {
+                // greeting when bot joins
+                handler.sender.privmsg(startup_message);
+            }
+
+            loop {
+                futures::select! {
+                    update = moderator_cooldown_stream.select_next_some() => {
+                        handler.moderator_cooldown = update;
+                    }
+                    _ = ping_interval.select_next_some() => {
+                        handler.send_ping()?;
+                    }
+                    timeout = handler.pong_timeout.current() => {
+                        bail!("server not responding");
+                    }
+                    update = whitelisted_hosts_stream.next() => {
+                        if let Some(update) = update {
+                            handler.whitelisted_hosts = update;
                         }
-                        update = whitelisted_hosts_stream.next() => {
-                            if let Some(update) = update {
-                                handler.whitelisted_hosts = update;
-                            }
-                        },
-                        message = client_stream.next() => {
-                            if let Some(m) = message.transpose()? {
-                                if let Err(e) = handler.handle(&m) {
-                                    log::error!("Failed to handle message: {}", e);
-                                }
+                    },
+                    message = client_stream.next() => {
+                        if let Some(m) = message.transpose()? {
+                            if let Err(e) = handler.handle(&m) {
+                                log::error!("Failed to handle message: {}", e);
                             }
+                        }
 
-                            if handler.handler_shutdown {
-                                bail!("handler forcibly shut down");
-                            }
+                        if handler.handler_shutdown {
+                            bail!("handler forcibly shut down");
                         }
                     }
                 }
+            }
+        };
+
+        future::try_join(future, future::try_join_all(futures)).await?;
+        Ok(())
+    }
+}
+
+/// Set up a loop which starts and stops the player as the stream goes live or offline.
+fn stream_state_loop<'a>(
+    futures: &mut utils::Futures,
+    mut stream_states: mpsc::UnboundedReceiver<stream_info::StreamState>,
+    injector: &'a Injector,
+    settings: &settings::Settings,
+) -> Result<impl Future<Output = Result<(), Error>> + 'a, Error> {
+    let player = injector.var::<Player, _>(futures);
+
+    let settings = settings.scoped("player");
+    let mut vars = settings.vars();
+    let auto_play = vars.var("auto-play-on-live", false)?;
+    let auto_pause = vars.var("auto-pause-on-offline", false)?;
+    let close_queue = vars.var("close-queue-on-offline", false)?;
+    futures.push(vars.run().boxed());
+
+    Ok(async move {
+        // NB: only reopen the queue if we were the ones closing it.
+        let mut closed_by_us = false;
+        let mut previous = None;
+
+        while let Some(state) = stream_states.next().await {
+            // NB: the first state is whatever the stream was in when the bot started.
+            let initial = previous.replace(state).is_none();
+
+            let player = match player.read().as_ref() {
+                Some(player) => player.clone(),
+                None => continue,
             };
 
-            match future::try_join(future, future::try_join_all(futures)).await {
-                Ok(((), _)) => break,
-                Err(e) => {
-                    log::warn!("IRC component errored, restarting in 5 seconds: {}", e);
-                    timer::Delay::new(time::Instant::now() + time::Duration::from_secs(5)).await?;
-                    continue;
+            match state {
+                stream_info::StreamState::Started => {
+                    if closed_by_us {
+                        log::info!("stream is live, opening song queue");
+                        player.open();
+                        closed_by_us = false;
+                    }
+
+                    // NB: don't start playing just because the bot started while the stream is
+                    // live.
+                    if *auto_play.read() && !initial {
+                        log::info!("stream is live, starting player");
+
+                        if let Err(e) = player.play() {
+                            log_err!(e, "failed to start player");
+                        }
+                    }
+                }
+                stream_info::StreamState::Stopped => {
+                    if *auto_pause.read() {
+                        log::info!("stream is offline, pausing player");
+
+                        if let Err(e) = player.pause() {
+                            log_err!(e, "failed to pause player");
+                        }
+                    }
+
+                    if *close_queue.read() && !player.is_closed() {
+                        log::info!("stream is offline, closing song queue");
+                        player.close(Some(String::from(
+                            "Song requests are closed while the stream is offline.",
+                        )));
+                        closed_by_us = true;
+                    }
                 }
             }
         }
 
         Ok(())
-    }
+    })
 }
 
-/// Set up a reward loop.
+/// Set up a loop which builds the currency as its settings change.
 fn currency_loop<'a>(
-    futures: &mut utils::Futures,
     db: db::Database,
     twitch: api::Twitch,
-    channel: Arc<String>,
-    sender: Sender,
-    idle: idle::Idle,
     injector: &'a Injector,
     settings: &settings::Settings,
 ) -> Result<impl Future<Output = Result<(), Error>> + 'a, Error> {
-    let reward = 10;
-    let interval_duration = time::Duration::from_secs(60 * 10);
-
-    let mut variables = settings.vars();
-    let reward_percentage = variables.var("irc/viewer-reward%", 100)?;
-    let (mut viewer_reward_stream, viewer_reward) = settings
-        .stream("irc/viewer-reward/enabled")
-        .or_with(false)?;
-    let (mut notify_rewards_stream, mut notify_rewards) =
-        settings.stream("currency/notify-rewards").or_with(true)?;
-
     let (mut ty_stream, ty) = settings.stream("currency/type").or_default()?;
     let (mut enabled_stream, enabled) = settings.stream("currency/enabled").or_default()?;
     let (mut name_stream, name) = settings.stream("currency/name").optional()?;
@@ -434,7 +633,7 @@ fn currency_loop<'a>(
     let (mut mysql_schema_stream, mysql_schema) =
         settings.stream("currency/mysql/schema").or_default()?;
 
-    let mut builder = CurrencyBuilder::new(db.clone(), twitch.clone(), mysql_schema);
+    let mut builder = CurrencyBuilder::new(db, twitch, mysql_schema);
     builder.ty = ty;
     builder.enabled = enabled;
     builder.command_enabled = command_enabled;
@@ -442,63 +641,88 @@ fn currency_loop<'a>(
     builder.mysql_url = mysql_url;
 
     let build = |injector: &Injector, builder: &CurrencyBuilder| match builder.build() {
-        Some(currency) => {
-            injector.update(currency.clone());
-            Some(currency)
-        }
-        None => {
-            injector.clear::<Currency>();
-            None
-        }
+        Some(currency) => injector.update(currency),
+        None => injector.clear::<Currency>(),
     };
 
-    let mut currency = build(injector, &builder);
-    futures.push(variables.run().boxed());
+    build(injector, &builder);
 
     return Ok(async move {
-        let mut interval = match viewer_reward {
-            true => Some(timer::Interval::new_interval(interval_duration.clone())),
-            false => None,
-        };
-
         loop {
             futures::select! {
-                update = notify_rewards_stream.select_next_some() => {
-                    notify_rewards = update;
-                }
                 enabled = enabled_stream.select_next_some() => {
                     builder.enabled = enabled;
-                    currency = build(injector, &builder);
+                    build(injector, &builder);
                 }
                 update = ty_stream.select_next_some() => {
                     builder.ty = update;
-                    currency = build(injector, &builder);
+                    build(injector, &builder);
                 }
                 name = name_stream.select_next_some() => {
                     builder.name = name.map(Arc::new);
-                    currency = build(injector, &builder);
+                    build(injector, &builder);
                 }
                 mysql_url = mysql_url_stream.select_next_some() => {
                     builder.mysql_url = mysql_url;
-                    currency = build(injector, &builder);
+                    build(injector, &builder);
                 }
                 update = mysql_schema_stream.select_next_some() => {
                     builder.mysql_schema = update;
-                    currency = build(injector, &builder);
+                    build(injector, &builder);
                 }
                 command_enabled = command_enabled_stream.select_next_some() => {
                     builder.command_enabled = command_enabled;
-                    currency = build(injector, &builder);
+                    build(injector, &builder);
+                }
+            }
+        }
+    });
+}
+
+/// Set up a loop which rewards the viewers of a channel.
+fn reward_loop<'a>(
+    futures: &mut utils::Futures,
+    channel: Arc<String>,
+    sender: Sender,
+    idle: idle::Idle,
+    injector: &'a Injector,
+    settings: &settings::Settings,
+) -> Result<impl Future<Output = Result<(), Error>> + 'a, Error> {
+    let reward = 10;
+    let interval_duration = time::Duration::from_secs(60 * 10);
+
+    let currency = injector.var::<Currency, _>(futures);
+
+    let mut variables = settings.vars();
+    let reward_percentage = variables.var("irc/viewer-reward%", 100)?;
+    let (mut viewer_reward_stream, viewer_reward) = settings
+        .stream("irc/viewer-reward/enabled")
+        .or_with(false)?;
+    let (mut notify_rewards_stream, mut notify_rewards) =
+        settings.stream("currency/notify-rewards").or_with(true)?;
+
+    futures.push(variables.run().boxed());
+
+    return Ok(async move {
+        let mut interval = match viewer_reward {
+            true => Some(timer::Interval::new_interval(interval_duration.clone())),
+            false => None,
+        };
+
+        loop {
+            futures::select! {
+                update = notify_rewards_stream.select_next_some() => {
+                    notify_rewards = update;
                 }
                 viewer_reward = viewer_reward_stream.select_next_some() => {
-                    let mut interval = match viewer_reward {
+                    interval = match viewer_reward {
                         true => Some(timer::Interval::new_interval(interval_duration.clone())),
                         false => None,
                     };
                 }
                 i = interval.select_next_some() => {
-                    let currency = match currency.as_ref() {
-                        Some(currency) => currency,
+                    let currency = match currency.read().as_ref() {
+                        Some(currency) => currency.clone(),
                         None => continue,
                     };
 
@@ -512,10 +736,10 @@ fn currency_loop<'a>(
                         .await?;
 
                     if notify_rewards && count > 0 && !idle.is_idle() {
-                        sender.privmsg(format!(
-                            "/me has given {} {} to all viewers!",
-                            reward, currency.name
-                        ));
+                        sender.privmsg_with(
+                            Priority::Low,
+                            format!("/me has given {} {} to all viewers!", reward, currency.name),
+                        );
                     }
                 }
             }
@@ -527,6 +751,8 @@ fn currency_loop<'a>(
 struct Handler<'a: 'h, 'to, 'h> {
     /// Current Streamer.
     streamer: &'a str,
+    /// Name of the bot.
+    bot: &'a str,
     /// Queue for sending messages.
     sender: Sender,
     /// Moderators.
@@ -555,6 +781,8 @@ struct Handler<'a: 'h, 'to, 'h> {
     shutdown: &'a utils::Shutdown,
     /// Build idle detection.
     idle: &'a idle::Idle,
+    /// When users were last seen in chat.
+    last_seen: &'a LastSeen,
     /// Pong timeout currently running.
     pong_timeout: &'to mut Option<timer::Delay>,
     /// OAuth 2.0 Token used to authenticate with IRC.
@@ -567,8 +795,26 @@ struct Handler<'a: 'h, 'to, 'h> {
     auth: &'a Auth,
     /// Handler for currencies.
     currency_handler: currency_admin::Handler<'a>,
+    /// How to respond to events in the channel, by kind of event.
+    event_settings: HashMap<&'static str, events::EventSettings>,
+    /// Log of moderation actions.
+    moderation_log: &'a db::ModerationLog,
+    /// Why the bot deleted messages, by message id, until Twitch confirms the deletion.
+    deletions: ModerationReasons,
+    /// Why the bot timed out or banned users, by user, until Twitch confirms it.
+    timeouts: ModerationReasons,
+    /// Modes of the channel, once Twitch has told us about them.
+    room_state: Option<HashMap<String, String>>,
     bad_words_enabled: Arc<RwLock<bool>>,
     url_whitelist_enabled: Arc<RwLock<bool>>,
+    /// Actions to take for each strike caught by the URL whitelist.
+    url_whitelist_strikes: Arc<RwLock<Vec<strikes::Action>>>,
+    /// Actions to take for each strike caught by the bad words filter.
+    bad_words_strikes: Arc<RwLock<Vec<strikes::Action>>>,
+    /// How long it takes for strikes to be forgotten.
+    strikes_decay: Arc<RwLock<utils::Duration>>,
+    /// Strikes against users in the channel.
+    strikes: strikes::Strikes,
 }
 
 impl Handler<'_, '_, '_> {
@@ -684,30 +930,110 @@ impl Handler<'_, '_, '_> {
         Tags { id, msg_id }
     }
 
+    /// Test if the tags of a USERSTATE message mark us as a moderator or the broadcaster.
+    fn is_moderator(m: &Message) -> bool {
+        let tags = match m.tags.as_ref() {
+            Some(tags) => tags,
+            None => return false,
+        };
+
+        tags.iter().any(|t| match *t {
+            Tag(ref name, Some(ref value)) if name == "mod" => value == "1",
+            Tag(ref name, Some(ref value)) if name == "badges" => value
+                .split(',')
+                .any(|b| b.starts_with("broadcaster/") || b.starts_with("moderator/")),
+            _ => false,
+        })
+    }
+
     /// Delete the given message.
-    fn delete_message<'local>(&mut self, tags: Tags<'local>) -> Result<(), Error> {
+    fn delete_message<'local>(&mut self, tags: Tags<'local>, reason: &str) -> Result<(), Error> {
         let id = match tags.id {
             Some(id) => id,
             None => return Ok(()),
         };
 
-        log::info!("Attempting to delete message: {}", id);
+        log::info!("Attempting to delete message: {}: {}", id, reason);
 
+        self.deletions.insert(id.to_string(), reason.to_string());
         self.sender.delete(id);
         Ok(())
     }
 
-    /// Test if the message should be deleted.
-    fn should_be_deleted(&mut self, m: &Message, message: &str) -> bool {
+    /// Act on a message caught by the given filter.
+    ///
+    /// The action escalates with the number of recent strikes against the user for the filter.
+    fn moderate<'local>(
+        &mut self,
+        m: &Message,
+        tags: Tags<'local>,
+        filter: strikes::Filter,
+        warning: Option<String>,
+    ) -> Result<(), Error> {
+        let user = match m.source_nickname() {
+            Some(user) => user,
+            None => return self.delete_message(tags, filter.reason()),
+        };
+
+        let policy = match filter {
+            strikes::Filter::BadWords => self.bad_words_strikes.read().clone(),
+            strikes::Filter::UrlWhitelist => self.url_whitelist_strikes.read().clone(),
+        };
+
+        let strike = self
+            .strikes
+            .strike(filter, user, &*self.strikes_decay.read());
+        let action = strikes::Action::for_strike(&policy, strike);
+        let reason = format!("{} (strike {})", filter.reason(), strike);
+
+        log::info!(
+            "{}: {}: {}: {}",
+            self.sender.channel(),
+            user,
+            reason,
+            action
+        );
+
+        match action {
+            strikes::Action::Delete => {
+                if let Some(warning) = warning {
+                    self.sender.privmsg(warning);
+                }
+
+                self.delete_message(tags, &reason)?;
+            }
+            strikes::Action::Timeout(duration) => {
+                self.sender.timeout(user, &duration, &reason);
+                self.timeouts.insert(user.to_lowercase(), reason);
+            }
+            strikes::Action::Ban => {
+                self.sender.ban(user, &reason);
+                self.timeouts.insert(user.to_lowercase(), reason);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Test if the message is caught by any filter.
+    ///
+    /// Returns the filter together with the warning to give the user, if any.
+    fn test_filters(
+        &mut self,
+        m: &Message,
+        message: &str,
+    ) -> Option<(strikes::Filter, Option<String>)> {
         let user = m.source_nickname();
 
         // Moderators can say whatever they want.
         if user.map(|u| self.moderators.contains(u)).unwrap_or(false) {
-            return false;
+            return None;
         }
 
         if *self.bad_words_enabled.read() {
             if let Some(word) = self.test_bad_words(message) {
+                let mut warning = None;
+
                 if let (Some(why), Some(user), Some(target)) =
                     (word.why.as_ref(), user, m.response_target())
                 {
@@ -718,7 +1044,7 @@ impl Handler<'_, '_, '_> {
 
                     match why {
                         Ok(why) => {
-                            self.sender.privmsg(&why);
+                            warning = Some(why);
                         }
                         Err(e) => {
                             log_err!(e, "failed to render response");
@@ -726,17 +1052,20 @@ impl Handler<'_, '_, '_> {
                     }
                 }
 
-                return true;
+                return Some((strikes::Filter::BadWords, warning));
             }
         }
 
         if *self.url_whitelist_enabled.read() {
             if self.has_bad_link(message) {
-                return true;
+                let warning =
+                    user.map(|user| format!("{} -> Links to that site aren't allowed here.", user));
+
+                return Some((strikes::Filter::UrlWhitelist, warning));
             }
         }
 
-        false
+        None
     }
 
     /// Test the message for bad words.
@@ -777,6 +1106,145 @@ impl Handler<'_, '_, '_> {
         Ok(())
     }
 
+    /// Handle a channel event, like a subscription or a raid.
+    fn handle_event(&mut self, event: Event) -> Result<(), Error> {
+        log::info!(
+            "{}: {} by {}",
+            self.sender.channel(),
+            event.kind(),
+            event.user()
+        );
+
+        let settings = match event.is_part_of_mystery_gift() {
+            // NB: the mystery gift is responded to and rewarded as a whole.
+            true => None,
+            false => self.event_settings.get(event.kind()),
+        };
+
+        let (template, reward) = match settings {
+            Some(settings) => (settings.template.read().clone(), *settings.reward.read()),
+            None => (None, 0),
+        };
+
+        if let Some(template) = template {
+            self.sender
+                .privmsg_with(Priority::Low, template.render_to_string(&event)?);
+        }
+
+        let currency = self.currency_handler.currency.read().clone();
+
+        // NB: anonymous gifters can't be rewarded.
+        if let (Some(currency), true) = (currency, reward > 0 && !event.is_anonymous()) {
+            let channel = self.sender.channel().to_string();
+            let user = event.user().to_string();
+            let amount = reward * event.units();
+
+            let future = async move {
+                if let Err(e) = currency.balance_add(channel, user, amount).await {
+                    log_err!(e, "failed to reward user for event");
+                }
+            };
+
+            self.thread_pool
+                .spawn(Compat::new(Box::pin(future.unit_error())));
+        }
+
+        self.global_bus.send(bus::Global::ChannelEvent {
+            channel: self.sender.channel().to_string(),
+            event,
+        });
+
+        Ok(())
+    }
+
+    /// Log a timeout or a ban of the given user, or that the chat was cleared.
+    fn handle_clear_chat(&mut self, m: &Message, user: Option<&str>) {
+        let user = match user {
+            Some(user) => user,
+            None => {
+                let entry = db::InsertModerationEntry::new(
+                    self.sender.channel(),
+                    db::ModerationKind::Clear,
+                );
+                self.moderation_log.push_log(&entry);
+                return;
+            }
+        };
+
+        // NB: bans don't have a duration.
+        let duration = events::tags(m)
+            .get("ban-duration")
+            .and_then(|d| d.parse::<i32>().ok());
+
+        let kind = match duration {
+            Some(..) => db::ModerationKind::Timeout,
+            None => db::ModerationKind::Ban,
+        };
+
+        let mut entry = db::InsertModerationEntry::new(self.sender.channel(), kind);
+        entry.user = Some(user.to_string());
+        entry.duration = duration;
+
+        if let Some(reason) = self.timeouts.remove(user) {
+            entry.moderator = Some(self.bot.to_string());
+            entry.reason = Some(reason);
+        }
+
+        self.moderation_log.push_log(&entry);
+    }
+
+    /// Log the deletion of a single message.
+    fn handle_clear_message(&mut self, m: &Message, message: Option<&str>) {
+        let mut entry =
+            db::InsertModerationEntry::new(self.sender.channel(), db::ModerationKind::Delete);
+        let tags = events::tags(m);
+        entry.user = tags.get("login").map(|user| user.to_string());
+        entry.message = message.map(String::from);
+
+        if let Some(id) = tags.get("target-msg-id") {
+            if let Some(reason) = self.deletions.remove(id) {
+                entry.moderator = Some(self.bot.to_string());
+                entry.reason = Some(reason);
+            }
+        }
+
+        self.moderation_log.push_log(&entry);
+    }
+
+    /// Log changes to the modes of the channel, like slow mode.
+    ///
+    /// The first ROOMSTATE after joining has all modes, and is not logged.
+    fn handle_room_state(&mut self, m: &Message) {
+        const MODES: &[&str] = &["emote-only", "followers-only", "r9k", "slow", "subs-only"];
+
+        let initial = self.room_state.is_none();
+        let room_state = self.room_state.get_or_insert_with(HashMap::new);
+
+        for t in m.tags.iter().flatten() {
+            let (name, value) = match *t {
+                Tag(ref name, Some(ref value)) if MODES.contains(&name.as_str()) => (name, value),
+                _ => continue,
+            };
+
+            if room_state.get(name) == Some(value) {
+                continue;
+            }
+
+            room_state.insert(name.clone(), value.clone());
+
+            if initial {
+                continue;
+            }
+
+            let mut entry = db::InsertModerationEntry::new(
+                self.sender.channel(),
+                db::ModerationKind::RoomState,
+            );
+            entry.message = Some(format!("{}={}", name, value));
+            self.moderation_log.push_log(&entry);
+        }
+    }
+
     /// Handle the given command.
     pub fn handle<'local>(&mut self, m: &'local Message) -> Result<(), Error> {
         match m.command {
@@ -784,6 +1252,8 @@ impl Handler<'_, '_, '_> {
                 let tags = Self::tags(&m);
                 let user = self.as_user(tags.clone(), m)?;
 
+                self.last_seen.seen(user.name);
+
                 // only non-moderators and non-streamer bumps the idle counter.
                 if !self.moderators.contains(user.name) && user.name != self.streamer {
                     self.idle.seen();
@@ -825,8 +1295,12 @@ impl Handler<'_, '_, '_> {
                     }
                 }
 
-                if self.should_be_deleted(m, message) {
-                    self.delete_message(tags)?;
+                if let Some((filter, warning)) = self.test_filters(m, message) {
+                    self.moderate(m, tags, filter, warning)?;
+                }
+
+                if let Some(event) = Event::from_privmsg(m, message) {
+                    self.handle_event(event)?;
                 }
             }
             Command::CAP(_, CapSubCommand::ACK, _, ref what) => {
@@ -849,6 +1323,7 @@ impl Handler<'_, '_, '_> {
             Command::JOIN(ref channel, _, _) => {
                 let user = m.source_nickname().unwrap_or("?");
                 log::trace!("{} joined {}", user, channel);
+                self.last_seen.seen(&user.to_lowercase());
             }
             Command::Response(..) => {
                 log::trace!("Response: {}", m);
@@ -862,6 +1337,24 @@ impl Handler<'_, '_, '_> {
                 log::trace!("Received PONG, clearing PING timeout");
                 *self.pong_timeout = None;
             }
+            Command::Raw(ref command, ..) if command == "USERSTATE" => {
+                self.sender.set_moderator(Self::is_moderator(&m));
+            }
+            Command::Raw(ref command, _, ref user) if command == "CLEARCHAT" => {
+                self.handle_clear_chat(m, user.as_ref().map(|s| s.as_str()));
+            }
+            Command::Raw(ref command, _, ref message) if command == "CLEARMSG" => {
+                self.handle_clear_message(m, message.as_ref().map(|s| s.as_str()));
+            }
+            Command::Raw(ref command, ..) if command == "ROOMSTATE" => {
+                self.handle_room_state(m);
+            }
+            Command::Raw(ref command, _, ref message) if command == "USERNOTICE" => {
+                match Event::from_user_notice(m, message.as_ref().map(|s| s.as_str())) {
+                    Some(event) => self.handle_event(event)?,
+                    None => log::trace!("unhandled user notice: {:?}", m),
+                }
+            }
             Command::Raw(..) => {
                 log::trace!("Raw: {:?}", m);
             }
@@ -904,6 +1397,32 @@ impl Handler<'_, '_, '_> {
     }
 }
 
+/// Why the bot took moderation actions, until Twitch confirms them.
+///
+/// Reasons for actions which are never confirmed are forgotten after a while.
+#[derive(Default)]
+struct ModerationReasons {
+    reasons: HashMap<String, (time::Instant, String)>,
+}
+
+impl ModerationReasons {
+    /// Remember the reason for the action on the given key.
+    fn insert(&mut self, key: String, reason: String) {
+        self.insert_at(time::Instant::now(), key, reason);
+    }
+
+    fn insert_at(&mut self, now: time::Instant, key: String, reason: String) {
+        self.reasons
+            .retain(|_, (at, _)| now.duration_since(*at) < MODERATION_REASON_TIMEOUT);
+        self.reasons.insert(key, (now, reason));
+    }
+
+    /// Take the reason for the action on the given key, once it has been confirmed.
+    fn remove(&mut self, key: &str) -> Option<String> {
+        self.reasons.remove(key).map(|(_, reason)| reason)
+    }
+}
+
 #[derive(Clone)]
 pub struct OwnedUser {
     tags: OwnedTags,
@@ -1008,6 +1527,44 @@ async fn refresh_mods_future(sender: Sender) -> Result<(), Error> {
     Ok(())
 }
 
+/// Refresh when users were last seen from the list of chatters, since users who only lurk never
+/// show up in chat.
+async fn last_seen_loop(
+    channel: Arc<String>,
+    twitch: api::Twitch,
+    last_seen: LastSeen,
+) -> Result<(), Error> {
+    let mut interval = timer::Interval::new_interval(time::Duration::from_secs(60 * 2));
+
+    while let Some(i) = interval.next().await {
+        let _ = i?;
+        log::trace!("refreshing chatters");
+
+        let chatters = match twitch.chatters(channel.to_string()).await {
+            Ok(chatters) => chatters,
+            Err(e) => {
+                log_err!(e, "failed to get chatters");
+                continue;
+            }
+        };
+
+        last_seen.seen_all(
+            chatters
+                .broadcaster
+                .iter()
+                .chain(chatters.vips.iter())
+                .chain(chatters.moderators.iter())
+                .chain(chatters.staff.iter())
+                .chain(chatters.admins.iter())
+                .chain(chatters.global_mods.iter())
+                .chain(chatters.viewers.iter())
+                .map(String::as_str),
+        );
+    }
+
+    Ok(())
+}
+
 /// Parse the `room_mods` message.
 fn parse_room_members(message: &str) -> HashSet<String> {
     let mut out = HashSet::default();
@@ -1030,8 +1587,26 @@ fn parse_room_members(message: &str) -> HashSet<String> {
 
 #[cfg(test)]
 mod tests {
-    use super::parse_room_members;
+    use super::{parse_room_members, ModerationReasons, MODERATION_REASON_TIMEOUT};
     use hashbrown::HashSet;
+    use std::time::Instant;
+
+    #[test]
+    fn test_moderation_reasons() {
+        let mut reasons = ModerationReasons::default();
+        let now = Instant::now();
+
+        reasons.insert_at(now, String::from("a"), String::from("first"));
+        reasons.insert_at(now, String::from("b"), String::from("second"));
+        assert_eq!(Some(String::from("first")), reasons.remove("a"));
+        assert_eq!(None, reasons.remove("a"));
+
+        // NB: reasons which were never confirmed are forgotten.
+        let later = now + MODERATION_REASON_TIMEOUT;
+        reasons.insert_at(later, String::from("c"), String::from("third"));
+        assert_eq!(1, reasons.reasons.len());
+        assert_eq!(Some(String::from("third")), reasons.remove("c"));
+    }
 
     #[test]
     fn test_parse_room_mods() {
@@ -0,0 +1,325 @@
+//! Channel events which Twitch sends as USERNOTICE messages, and cheers.
+
+use crate::{settings, template::Template};
+use failure::Error;
+use hashbrown::HashMap;
+use irc::proto::message::{Message, Tag};
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+/// Every kind of event, as used in settings.
+const KINDS: &[&str] = &[
+    "subscription",
+    "resubscription",
+    "gift-subscription",
+    "mystery-gift-subscription",
+    "raid",
+    "cheer",
+];
+
+/// Login Twitch uses for the gifter of anonymous gift subscriptions.
+const ANONYMOUS_GIFTER: &str = "ananonymousgifter";
+
+/// An event in a channel, like a subscription or a raid.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "kind")]
+pub enum Event {
+    #[serde(rename = "subscription")]
+    Subscription { user: String, plan: String },
+    #[serde(rename = "resubscription")]
+    Resubscription {
+        user: String,
+        plan: String,
+        /// Total number of months subscribed.
+        months: u32,
+        /// Message shared with the resubscription, if any.
+        message: Option<String>,
+    },
+    #[serde(rename = "gift-subscription")]
+    GiftSubscription {
+        user: String,
+        recipient: String,
+        plan: String,
+        /// The gifter chose to stay anonymous.
+        anonymous: bool,
+        /// The subscription is part of a mystery gift, which is responded to as a whole.
+        mystery: bool,
+    },
+    #[serde(rename = "mystery-gift-subscription")]
+    MysteryGiftSubscription {
+        user: String,
+        /// Number of subscriptions gifted to random users in chat.
+        count: u32,
+        plan: String,
+        /// The gifter chose to stay anonymous.
+        anonymous: bool,
+    },
+    #[serde(rename = "raid")]
+    Raid { user: String, viewers: u32 },
+    #[serde(rename = "cheer")]
+    Cheer {
+        user: String,
+        bits: u32,
+        message: String,
+    },
+}
+
+impl Event {
+    /// Parse an event out of a USERNOTICE message.
+    ///
+    /// Returns `None` for notices we don't handle, like rituals.
+    pub fn from_user_notice(m: &Message, message: Option<&str>) -> Option<Event> {
+        let tags = tags(m);
+        let tag = |name: &str| tags.get(name).map(|v| v.to_string());
+        let number = |name: &str| tags.get(name).and_then(|v| v.parse::<u32>().ok());
+
+        let user = tag("login")?;
+
+        let event = match *tags.get("msg-id")? {
+            "sub" => Event::Subscription {
+                user,
+                plan: tag("msg-param-sub-plan")?,
+            },
+            "resub" => Event::Resubscription {
+                user,
+                plan: tag("msg-param-sub-plan")?,
+                months: number("msg-param-cumulative-months")
+                    .or_else(|| number("msg-param-months"))
+                    .unwrap_or(1),
+                message: message.map(String::from),
+            },
+            // NB: mystery gifts are followed by one subgift per recipient, which refers back to
+            // the mystery gift.
+            id @ "subgift" | id @ "anonsubgift" => Event::GiftSubscription {
+                anonymous: id == "anonsubgift" || user == ANONYMOUS_GIFTER,
+                mystery: tags.contains_key("msg-param-community-gift-id"),
+                user,
+                recipient: tag("msg-param-recipient-user-name")?,
+                plan: tag("msg-param-sub-plan")?,
+            },
+            id @ "submysterygift" | id @ "anonsubmysterygift" => Event::MysteryGiftSubscription {
+                anonymous: id == "anonsubmysterygift" || user == ANONYMOUS_GIFTER,
+                user,
+                count: number("msg-param-mass-gift-count").unwrap_or(1),
+                plan: tag("msg-param-sub-plan")?,
+            },
+            "raid" => Event::Raid {
+                user,
+                viewers: number("msg-param-viewerCount").unwrap_or_default(),
+            },
+            _ => return None,
+        };
+
+        Some(event)
+    }
+
+    /// Parse a cheer out of a PRIVMSG, if it has bits attached.
+    pub fn from_privmsg(m: &Message, message: &str) -> Option<Event> {
+        let bits = tags(m).get("bits")?.parse::<u32>().ok()?;
+
+        Some(Event::Cheer {
+            user: m.source_nickname()?.to_string(),
+            bits,
+            message: message.to_string(),
+        })
+    }
+
+    /// The kind of the event, as used in settings.
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            Event::Subscription { .. } => "subscription",
+            Event::Resubscription { .. } => "resubscription",
+            Event::GiftSubscription { .. } => "gift-subscription",
+            Event::MysteryGiftSubscription { .. } => "mystery-gift-subscription",
+            Event::Raid { .. } => "raid",
+            Event::Cheer { .. } => "cheer",
+        }
+    }
+
+    /// The user responsible for the event.
+    pub fn user(&self) -> &str {
+        match *self {
+            Event::Subscription { ref user, .. }
+            | Event::Resubscription { ref user, .. }
+            | Event::GiftSubscription { ref user, .. }
+            | Event::MysteryGiftSubscription { ref user, .. }
+            | Event::Raid { ref user, .. }
+            | Event::Cheer { ref user, .. } => user,
+        }
+    }
+
+    /// Test if the user responsible for the event is anonymous, in which case they can't be
+    /// rewarded.
+    pub fn is_anonymous(&self) -> bool {
+        match *self {
+            Event::GiftSubscription { anonymous, .. }
+            | Event::MysteryGiftSubscription { anonymous, .. } => anonymous,
+            _ => false,
+        }
+    }
+
+    /// Test if the event is a gifted subscription which is part of a mystery gift.
+    ///
+    /// These are not responded to or rewarded on their own, since the mystery gift is.
+    pub fn is_part_of_mystery_gift(&self) -> bool {
+        match *self {
+            Event::GiftSubscription { mystery, .. } => mystery,
+            _ => false,
+        }
+    }
+
+    /// The number of units to reward the user for, which is the number of bits for cheers and the
+    /// number of subscriptions for mystery gifts.
+    pub fn units(&self) -> i64 {
+        match *self {
+            Event::Cheer { bits, .. } => i64::from(bits),
+            Event::MysteryGiftSubscription { count, .. } => i64::from(count),
+            _ => 1,
+        }
+    }
+}
+
+/// How to respond to a single kind of event.
+pub struct EventSettings {
+    /// Message to send in response to the event.
+    pub template: Arc<RwLock<Option<Template>>>,
+    /// Stream currency to reward the user with, for every unit.
+    pub reward: Arc<RwLock<i64>>,
+}
+
+/// Set up synchronized settings for every kind of event, by kind.
+pub fn settings(vars: &mut settings::Vars) -> Result<HashMap<&'static str, EventSettings>, Error> {
+    let mut out = HashMap::new();
+
+    for kind in KINDS {
+        let settings = EventSettings {
+            template: vars.optional(&format!("events/{}/template", kind))?,
+            reward: vars.var(&format!("events/{}/reward", kind), 0)?,
+        };
+
+        out.insert(*kind, settings);
+    }
+
+    Ok(out)
+}
+
+/// Collect the tags of a message which have values.
+pub fn tags(m: &Message) -> HashMap<&str, &str> {
+    let mut out = HashMap::new();
+
+    for t in m.tags.iter().flatten() {
+        if let Tag(ref name, Some(ref value)) = *t {
+            out.insert(name.as_str(), value.as_str());
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Event;
+    use irc::proto::message::Message;
+
+    fn notice(tags: &str, message: Option<&str>) -> Option<Event> {
+        let m = format!("@{} :tmi.twitch.tv USERNOTICE #setbac", tags);
+        let m = m.parse::<Message>().expect("message");
+        Event::from_user_notice(&m, message)
+    }
+
+    fn gift(id: &str, login: &str) -> Event {
+        let tags = format!(
+            "login={};msg-id={};msg-param-recipient-user-name=bob;msg-param-sub-plan=1000",
+            login, id
+        );
+
+        notice(&tags, None).expect("event")
+    }
+
+    #[test]
+    fn test_subscription() {
+        match notice("login=alice;msg-id=sub;msg-param-sub-plan=Prime", None) {
+            Some(Event::Subscription { user, plan }) => {
+                assert_eq!("alice", user);
+                assert_eq!("Prime", plan);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_resubscription() {
+        let tags = "login=alice;msg-id=resub;msg-param-sub-plan=1000;msg-param-cumulative-months=7";
+
+        match notice(tags, Some("hello")) {
+            Some(Event::Resubscription {
+                user,
+                months,
+                message,
+                ..
+            }) => {
+                assert_eq!("alice", user);
+                assert_eq!(7, months);
+                assert_eq!(Some(String::from("hello")), message);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_raid() {
+        match notice("login=alice;msg-id=raid;msg-param-viewerCount=42", None) {
+            Some(Event::Raid { user, viewers }) => {
+                assert_eq!("alice", user);
+                assert_eq!(42, viewers);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+
+        assert!(notice("login=alice;msg-id=ritual", None).is_none());
+    }
+
+    #[test]
+    fn test_cheer() {
+        let m = "@bits=100 :alice!alice@alice.tmi.twitch.tv PRIVMSG #setbac :cheer100 hello";
+        let m = m.parse::<Message>().expect("message");
+
+        match Event::from_privmsg(&m, "cheer100 hello") {
+            Some(event @ Event::Cheer { .. }) => {
+                assert_eq!("alice", event.user());
+                assert_eq!(100, event.units());
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+
+        let m = ":alice!alice@alice.tmi.twitch.tv PRIVMSG #setbac :hello";
+        let m = m.parse::<Message>().expect("message");
+        assert!(Event::from_privmsg(&m, "hello").is_none());
+    }
+
+    #[test]
+    fn test_anonymous_gift() {
+        assert!(!gift("subgift", "alice").is_anonymous());
+        assert!(gift("anonsubgift", "alice").is_anonymous());
+        assert!(gift("subgift", "ananonymousgifter").is_anonymous());
+    }
+
+    #[test]
+    fn test_mystery_gift() {
+        let tags = "login=alice;msg-id=submysterygift;msg-param-mass-gift-count=50;\
+                    msg-param-sub-plan=1000";
+        let event = notice(tags, None).expect("event");
+        assert_eq!("mystery-gift-subscription", event.kind());
+        assert_eq!(50, event.units());
+        assert!(!event.is_part_of_mystery_gift());
+
+        // NB: the subgifts which follow are part of the mystery gift.
+        let tags = "login=alice;msg-id=subgift;msg-param-recipient-user-name=bob;\
+                    msg-param-sub-plan=1000;msg-param-community-gift-id=123";
+        let event = notice(tags, None).expect("event");
+        assert_eq!("gift-subscription", event.kind());
+        assert!(event.is_part_of_mystery_gift());
+
+        assert!(!gift("subgift", "alice").is_part_of_mystery_gift());
+    }
+}
@@ -1,4 +1,6 @@
-use crate::{api, prelude::*};
+use crate::{api, prelude::*, timer, utils};
+use failure::Error;
+use hashbrown::HashMap;
 use irc::{
     client::{Client, IrcClient},
     proto::{
@@ -7,9 +9,19 @@ use irc::{
     },
 };
 use parking_lot::{Mutex, RwLock};
-use std::{fmt, sync::Arc, time};
+use std::{collections::VecDeque, fmt, sync::Arc, thread, time};
 use tokio_threadpool::ThreadPool;
 
+/// The window in which Twitch counts messages towards the rate limit.
+const WINDOW: time::Duration = time::Duration::from_secs(30);
+/// Number of messages permitted within the window, across all channels where the bot is not a
+/// moderator.
+const LIMIT: usize = 20;
+/// Number of messages permitted within the window when moderator in the channel.
+const MODERATOR_LIMIT: usize = 100;
+/// The longest message that Twitch accepts, in characters.
+const MAX_MESSAGE_LENGTH: usize = 500;
+
 #[derive(Debug, Clone, Copy, serde::Deserialize, serde::Serialize)]
 pub enum Type {
     #[serde(rename = "chat")]
@@ -24,11 +36,143 @@ impl Default for Type {
     }
 }
 
+/// Priority of an outgoing message.
+///
+/// Messages with a higher priority are sent before any queued messages with a lower priority.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Notices nobody is waiting for, like promotions and rewards.
+    Low,
+    /// Responses to commands.
+    Normal,
+    /// Moderation actions.
+    High,
+}
+
+/// Messages which have been sent within the window, which Twitch counts towards the rate limit.
+///
+/// Twitch counts messages in channels where the bot is not a moderator towards the account, so a
+/// single window is shared by all of them.
+#[derive(Default)]
+pub struct Window {
+    /// When messages were sent, oldest first.
+    sent: VecDeque<time::Instant>,
+}
+
+impl Window {
+    /// Forget messages which were sent before the window.
+    fn prune(&mut self, now: time::Instant) {
+        while let Some(sent) = self.sent.front() {
+            if now.duration_since(*sent) < WINDOW {
+                break;
+            }
+
+            self.sent.pop_front();
+        }
+    }
+
+    /// How long to wait before another message can be sent with the given limit, if at all.
+    fn wait(&self, now: time::Instant, limit: usize) -> Option<time::Duration> {
+        if self.sent.len() < limit {
+            return None;
+        }
+
+        let wait = self
+            .sent
+            .front()
+            .map(|sent| WINDOW - now.duration_since(*sent))
+            .unwrap_or_default();
+
+        Some(wait)
+    }
+}
+
+/// Messages waiting to be sent.
+#[derive(Default)]
+struct Queue {
+    /// Queued messages by priority.
+    queued: [VecDeque<Message>; 3],
+    /// When chat messages were last sent, by text.
+    recent: HashMap<String, time::Instant>,
+}
+
+impl Queue {
+    /// Push a message to the queue.
+    ///
+    /// Returns `false` if the message was coalesced with an identical one.
+    fn push(
+        &mut self,
+        now: time::Instant,
+        priority: Priority,
+        m: Message,
+        moderator: bool,
+    ) -> bool {
+        if let Some(text) = privmsg_text(&m) {
+            let queued = self
+                .queued
+                .iter()
+                .flatten()
+                .any(|q| privmsg_text(q) == Some(text));
+
+            if queued {
+                return false;
+            }
+
+            // NB: moderators are permitted to repeat themselves.
+            if !moderator {
+                if let Some(sent) = self.recent.get(text) {
+                    if now.duration_since(*sent) < WINDOW {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        self.queued[priority as usize].push_back(m);
+        true
+    }
+
+    /// Take the next message to send if the limit of the given window permits it.
+    ///
+    /// Otherwise returns how long to wait before trying again.
+    fn pop(
+        &mut self,
+        now: time::Instant,
+        window: &mut Window,
+        limit: usize,
+    ) -> Result<Option<Message>, time::Duration> {
+        window.prune(now);
+
+        self.recent
+            .retain(|_, sent| now.duration_since(*sent) < WINDOW);
+
+        if let Some(wait) = window.wait(now, limit) {
+            return Err(wait);
+        }
+
+        let m = match self.queued.iter_mut().rev().find_map(VecDeque::pop_front) {
+            Some(m) => m,
+            None => return Ok(None),
+        };
+
+        if let Some(text) = privmsg_text(&m) {
+            self.recent.insert(text.to_string(), now);
+        }
+
+        window.sent.push_back(now);
+        Ok(Some(m))
+    }
+}
+
 struct Inner {
     target: Arc<String>,
     client: IrcClient,
     thread_pool: ThreadPool,
-    limiter: Mutex<ratelimit::Limiter>,
+    queue: Arc<Mutex<Queue>>,
+    /// Wakes up the task which sends queued messages.
+    queue_tx: mpsc::UnboundedSender<()>,
+    /// If the bot is a moderator in the channel, which raises the rate limit.
+    moderator: Arc<RwLock<bool>>,
     nightbot_limiter: Mutex<ratelimit::Limiter>,
     nightbot: Arc<api::NightBot>,
 }
@@ -40,17 +184,17 @@ pub struct Sender {
 }
 
 impl Sender {
-    /// Create a new sender.
+    /// Create a new sender, and the future which sends queued messages.
+    ///
+    /// `window` is shared by the senders of every channel, and is used for channels where the bot
+    /// is not a moderator.
     pub fn new(
         ty: Arc<RwLock<Type>>,
         target: Arc<String>,
         client: IrcClient,
         nightbot: Arc<api::NightBot>,
-    ) -> Sender {
-        // limiter to use for IRC chat messages.
-        let limiter = ratelimit::Builder::new().frequency(10).capacity(95).build();
-        let limiter = Mutex::new(limiter);
-
+        window: Arc<Mutex<Window>>,
+    ) -> (Sender, impl Future<Output = Result<(), Error>>) {
         let nightbot_limiter = ratelimit::Builder::new()
             .quantum(1)
             .capacity(1)
@@ -58,17 +202,33 @@ impl Sender {
             .build();
         let nightbot_limiter = Mutex::new(nightbot_limiter);
 
-        Sender {
+        let queue = Arc::new(Mutex::new(Queue::default()));
+        let moderator = Arc::new(RwLock::new(false));
+        let (queue_tx, queue_rx) = mpsc::unbounded();
+
+        let future = send_queued(
+            client.clone(),
+            queue.clone(),
+            moderator.clone(),
+            window,
+            queue_rx,
+        );
+
+        let sender = Sender {
             ty,
             inner: Arc::new(Inner {
                 target,
                 client,
                 thread_pool: ThreadPool::new(),
-                limiter,
+                queue,
+                queue_tx,
+                moderator,
                 nightbot_limiter,
                 nightbot,
             }),
-        }
+        };
+
+        (sender, future)
     }
 
     /// Get the channel this sender is associated with.
@@ -76,35 +236,76 @@ impl Sender {
         self.inner.target.as_str()
     }
 
+    /// Set if the bot is a moderator in the channel.
+    pub fn set_moderator(&self, moderator: bool) {
+        let mut current = self.inner.moderator.write();
+
+        if *current != moderator {
+            log::info!(
+                "{}: sending up to {} messages every {} seconds",
+                self.inner.target,
+                if moderator { MODERATOR_LIMIT } else { LIMIT },
+                WINDOW.as_secs()
+            );
+        }
+
+        *current = moderator;
+    }
+
     /// Delete the given message by id.
     pub fn delete(&self, id: &str) {
-        self.privmsg_immediate(format!("/delete {}", id));
+        self.command(format!("/delete {}", id));
+    }
+
+    /// Time out the given user for the given duration, with a reason which is shown to them.
+    pub fn timeout(&self, user: &str, duration: &utils::Duration, reason: &str) {
+        self.command(format!(
+            "/timeout {} {} {}",
+            user,
+            duration.num_seconds(),
+            reason
+        ));
+    }
+
+    /// Ban the given user, with a reason which is shown to them.
+    pub fn ban(&self, user: &str, reason: &str) {
+        self.command(format!("/ban {} {}", user, reason));
     }
 
     /// Get list of mods.
     pub fn mods(&self) {
-        self.privmsg_immediate("/mods");
+        self.command("/mods");
     }
 
     /// Get list of VIPs.
     pub fn vips(&self) {
-        self.privmsg_immediate("/vips");
+        self.command("/vips");
     }
 
     /// Only send to chat, with rate limiting.
     pub fn send(&self, m: impl Into<Message>) {
-        let inner = self.inner.clone();
-        let m = m.into();
+        self.send_with(Priority::Normal, m);
+    }
 
-        self.inner.thread_pool.spawn(future01::lazy(move || {
-            inner.limiter.lock().wait();
+    /// Send to chat with the given priority, with rate limiting.
+    pub fn send_with(&self, priority: Priority, m: impl Into<Message>) {
+        let moderator = *self.inner.moderator.read();
 
-            if let Err(e) = inner.client.send(m) {
-                log_err!(e, "failed to send message");
-            }
+        let now = time::Instant::now();
+
+        if !self
+            .inner
+            .queue
+            .lock()
+            .push(now, priority, m.into(), moderator)
+        {
+            log::trace!("coalesced duplicate message");
+            return;
+        }
 
-            Ok(())
-        }));
+        if let Err(e) = self.inner.queue_tx.unbounded_send(()) {
+            log::error!("failed to wake up message queue: {}", e);
+        }
     }
 
     /// Send an immediate message, without taking rate limiting into account.
@@ -116,6 +317,13 @@ impl Sender {
 
     /// Send a PRIVMSG.
     pub fn privmsg(&self, f: impl fmt::Display) {
+        self.privmsg_with(Priority::Normal, f);
+    }
+
+    /// Send a PRIVMSG with the given priority.
+    ///
+    /// Messages which are too long for Twitch are split up.
+    pub fn privmsg_with(&self, priority: Priority, f: impl fmt::Display) {
         match *self.ty.read() {
             Type::NightBot => {
                 let inner = self.inner.clone();
@@ -123,14 +331,21 @@ impl Sender {
                 return;
             }
             Type::Chat => {
-                self.send(Command::PRIVMSG(
-                    (*self.inner.target).clone(),
-                    f.to_string(),
-                ));
+                for m in split_message(&f.to_string(), MAX_MESSAGE_LENGTH) {
+                    self.send_with(priority, Command::PRIVMSG((*self.inner.target).clone(), m));
+                }
             }
         }
     }
 
+    /// Send a Twitch command, like `/delete`, with rate limiting.
+    ///
+    /// Commands always go to chat regardless of the sender type, and are never split up.
+    fn command(&self, command: impl fmt::Display) {
+        let m = Command::PRIVMSG((*self.inner.target).clone(), command.to_string());
+        self.send_with(Priority::High, m);
+    }
+
     /// Send a PRIVMSG without rate limiting.
     pub fn privmsg_immediate(&self, f: impl fmt::Display) {
         self.send_immediate(Command::PRIVMSG(
@@ -141,7 +356,7 @@ impl Sender {
 
     /// Send a capability request.
     pub fn cap_req(&self, cap: &str) {
-        self.send(Command::CAP(
+        self.send_immediate(Command::CAP(
             None,
             CapSubCommand::REQ,
             Some(String::from(cap)),
@@ -152,7 +367,6 @@ impl Sender {
     /// Send message via nightbot.
     fn send_nightbot(&self, inner: Arc<Inner>, m: String) {
         use futures::executor;
-        use std::thread;
 
         let m = m.to_string();
 
@@ -184,3 +398,213 @@ impl Sender {
         self.inner.thread_pool.spawn(future);
     }
 }
+
+/// Send queued messages as fast as the rate limit permits, every time a message is queued.
+async fn send_queued(
+    client: IrcClient,
+    queue: Arc<Mutex<Queue>>,
+    moderator: Arc<RwLock<bool>>,
+    window: Arc<Mutex<Window>>,
+    mut queue_rx: mpsc::UnboundedReceiver<()>,
+) -> Result<(), Error> {
+    // NB: the higher limit for moderators applies to each channel on its own.
+    let mut moderator_window = Window::default();
+
+    while let Some(()) = queue_rx.next().await {
+        loop {
+            let now = time::Instant::now();
+            let moderator = *moderator.read();
+
+            let result = match moderator {
+                true => queue
+                    .lock()
+                    .pop(now, &mut moderator_window, MODERATOR_LIMIT),
+                false => queue.lock().pop(now, &mut *window.lock(), LIMIT),
+            };
+
+            match result {
+                Ok(Some(m)) => {
+                    if let Err(e) = client.send(m) {
+                        log_err!(e, "failed to send message");
+                    }
+                }
+                // NB: messages queued while sending were already sent.
+                Ok(None) => break,
+                Err(wait) => {
+                    timer::Delay::new(time::Instant::now() + wait).await?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the text of a message if it's a PRIVMSG.
+fn privmsg_text(m: &Message) -> Option<&str> {
+    match m.command {
+        Command::PRIVMSG(_, ref text) => Some(text.as_str()),
+        _ => None,
+    }
+}
+
+/// Split a message into parts that are at most `max` characters long.
+///
+/// Splits on whitespace where possible.
+fn split_message(message: &str, max: usize) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut current = String::new();
+    let mut len = 0;
+
+    for word in message.split_whitespace() {
+        let mut word = word;
+        let mut word_len = word.chars().count();
+
+        if len > 0 && len + 1 + word_len > max {
+            out.push(std::mem::replace(&mut current, String::new()));
+            len = 0;
+        }
+
+        // NB: words which don't fit in a message on their own have to be split up.
+        while word_len > max {
+            let (index, _) = word.char_indices().nth(max).expect("longer than max");
+            out.push(word[..index].to_string());
+            word = &word[index..];
+            word_len -= max;
+        }
+
+        if len > 0 {
+            current.push(' ');
+            len += 1;
+        }
+
+        current.push_str(word);
+        len += word_len;
+    }
+
+    if !current.is_empty() || out.is_empty() {
+        out.push(current);
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split_message, Priority, Queue, Window, LIMIT, WINDOW};
+    use irc::proto::{command::Command, message::Message};
+    use std::time::{Duration, Instant};
+
+    fn privmsg(text: &str) -> Message {
+        Message::from(Command::PRIVMSG(
+            String::from("#setbac"),
+            String::from(text),
+        ))
+    }
+
+    fn text(m: Option<Message>) -> Option<String> {
+        match m?.command {
+            Command::PRIVMSG(_, text) => Some(text),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_queue_priority() {
+        let now = Instant::now();
+        let mut queue = Queue::default();
+        let mut window = Window::default();
+
+        assert!(queue.push(now, Priority::Low, privmsg("low"), false));
+        assert!(queue.push(now, Priority::Normal, privmsg("normal"), false));
+        assert!(queue.push(now, Priority::High, privmsg("high"), false));
+        assert!(queue.push(now, Priority::Normal, privmsg("normal 2"), false));
+
+        let mut pop = || text(queue.pop(now, &mut window, LIMIT).expect("permitted"));
+
+        assert_eq!(Some(String::from("high")), pop());
+        assert_eq!(Some(String::from("normal")), pop());
+        assert_eq!(Some(String::from("normal 2")), pop());
+        assert_eq!(Some(String::from("low")), pop());
+        assert_eq!(None, pop());
+    }
+
+    #[test]
+    fn test_queue_coalesce() {
+        let now = Instant::now();
+        let mut queue = Queue::default();
+        let mut window = Window::default();
+
+        assert!(queue.push(now, Priority::Normal, privmsg("hello"), false));
+        // NB: identical to a queued message, regardless of priority.
+        assert!(!queue.push(now, Priority::High, privmsg("hello"), true));
+
+        assert!(queue
+            .pop(now, &mut window, LIMIT)
+            .expect("permitted")
+            .is_some());
+
+        // NB: identical to a message which was recently sent.
+        assert!(!queue.push(now, Priority::Normal, privmsg("hello"), false));
+        // NB: moderators are permitted to repeat themselves.
+        assert!(queue.push(now, Priority::Normal, privmsg("hello"), true));
+        assert!(queue
+            .pop(now, &mut window, LIMIT)
+            .expect("permitted")
+            .is_some());
+
+        let later = now + WINDOW;
+        assert!(queue.push(later, Priority::Normal, privmsg("hello"), false));
+    }
+
+    #[test]
+    fn test_queue_limit() {
+        let now = Instant::now();
+        let mut queue = Queue::default();
+        let mut window = Window::default();
+
+        for i in 0..3 {
+            assert!(queue.push(now, Priority::Normal, privmsg(&i.to_string()), false));
+        }
+
+        let later = now + Duration::from_secs(10);
+
+        assert!(queue.pop(now, &mut window, 2).is_ok());
+        assert!(queue.pop(later, &mut window, 2).is_ok());
+
+        // NB: wait until the first message falls out of the window.
+        assert_eq!(
+            Err(Duration::from_secs(20)),
+            queue.pop(later, &mut window, 2).map(text)
+        );
+
+        let m = queue.pop(now + WINDOW, &mut window, 2).map(text);
+        assert_eq!(Ok(Some(String::from("2"))), m);
+    }
+
+    #[test]
+    fn test_shared_window() {
+        let now = Instant::now();
+        let mut a = Queue::default();
+        let mut b = Queue::default();
+        let mut window = Window::default();
+
+        assert!(a.push(now, Priority::Normal, privmsg("a"), false));
+        assert!(b.push(now, Priority::Normal, privmsg("b"), false));
+
+        assert!(a.pop(now, &mut window, 1).is_ok());
+        assert_eq!(Err(WINDOW), b.pop(now, &mut window, 1).map(text));
+    }
+
+    #[test]
+    fn test_split_message() {
+        assert_eq!(vec![String::from("foo bar")], split_message("foo bar", 10));
+        assert_eq!(vec!["foo", "bar", "baz"], split_message("foo bar baz", 5));
+        assert_eq!(vec!["foo bar", "baz"], split_message("foo bar baz", 7));
+        assert_eq!(
+            vec!["abcd", "efgh", "ij k"],
+            split_message("abcdefghij k", 4)
+        );
+        assert_eq!(vec!["åäö", "åäö"], split_message("åäöåäö", 3));
+    }
+}
@@ -0,0 +1,177 @@
+//! Strikes against users for messages caught by the chat filters, which escalate the action taken.
+
+use crate::utils;
+use hashbrown::HashMap;
+use std::{collections::VecDeque, fmt, time};
+
+/// A filter which catches messages, each with its own strike policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Filter {
+    /// The message has a bad word in it.
+    BadWords,
+    /// The message links to a host which isn't whitelisted.
+    UrlWhitelist,
+}
+
+impl Filter {
+    /// The reason for acting on a message caught by the filter.
+    pub fn reason(self) -> &'static str {
+        match self {
+            Filter::BadWords => "bad word",
+            Filter::UrlWhitelist => "link to a host which isn't whitelisted",
+        }
+    }
+}
+
+/// An action to take against a user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Delete the message and warn the user.
+    Delete,
+    /// Time out the user for the given duration.
+    Timeout(utils::Duration),
+    /// Ban the user.
+    Ban,
+}
+
+impl Action {
+    /// The default policy: delete and warn for every strike, which doesn't escalate.
+    pub fn default_policy() -> Vec<Action> {
+        vec![Action::Delete]
+    }
+
+    /// Pick the action for the given strike in a policy, counting from one.
+    ///
+    /// Strikes past the end of the policy repeat the last action.
+    pub fn for_strike(policy: &[Action], strike: usize) -> Action {
+        let index = usize::min(strike.saturating_sub(1), policy.len().saturating_sub(1));
+        policy.get(index).cloned().unwrap_or(Action::Delete)
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Action::Delete => write!(fmt, "delete"),
+            Action::Timeout(ref duration) => write!(fmt, "timeout {}", duration),
+            Action::Ban => write!(fmt, "ban"),
+        }
+    }
+}
+
+impl std::str::FromStr for Action {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut it = s.split_whitespace();
+
+        let action = match (it.next(), it.next()) {
+            (Some("delete"), None) => Action::Delete,
+            (Some("timeout"), Some(duration)) => Action::Timeout(str::parse(duration)?),
+            (Some("ban"), None) => Action::Ban,
+            _ => failure::bail!(
+                "bad action `{}`, expected one of: delete, timeout <duration>, or ban",
+                s
+            ),
+        };
+
+        if it.next().is_some() {
+            failure::bail!("bad action `{}`, unexpected trailing input", s);
+        }
+
+        Ok(action)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Action {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        str::parse(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+impl serde::Serialize for Action {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+/// Strikes against users, by filter.
+#[derive(Default)]
+pub struct Strikes {
+    strikes: HashMap<(Filter, String), VecDeque<time::Instant>>,
+}
+
+impl Strikes {
+    /// Add a strike against the given user.
+    ///
+    /// Returns the number of strikes the user has for the filter, including this one.
+    /// Strikes older than `decay` are forgotten.
+    pub fn strike(&mut self, filter: Filter, user: &str, decay: &utils::Duration) -> usize {
+        let now = time::Instant::now();
+        let decay = decay.as_std();
+
+        // NB: forget about users which haven't been struck in a while.
+        self.strikes.retain(|_, strikes| {
+            while let Some(strike) = strikes.front() {
+                if now.duration_since(*strike) < decay {
+                    break;
+                }
+
+                strikes.pop_front();
+            }
+
+            !strikes.is_empty()
+        });
+
+        let strikes = self
+            .strikes
+            .entry((filter, user.to_lowercase()))
+            .or_default();
+
+        strikes.push_back(now);
+        strikes.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Action;
+    use crate::utils::Duration;
+
+    #[test]
+    fn test_parse_action() {
+        assert_eq!(Action::Delete, str::parse::<Action>("delete").unwrap());
+        assert_eq!(
+            Action::Timeout(Duration::seconds(90)),
+            str::parse::<Action>("timeout 1m30s").unwrap()
+        );
+        assert_eq!(Action::Ban, str::parse::<Action>("ban").unwrap());
+        assert!(str::parse::<Action>("timeout").is_err());
+        assert!(str::parse::<Action>("ban 10m").is_err());
+    }
+
+    #[test]
+    fn test_for_strike() {
+        let policy = vec![
+            Action::Delete,
+            Action::Timeout(Duration::seconds(10 * 60)),
+            Action::Timeout(Duration::hours(1)),
+        ];
+
+        assert_eq!(Action::Delete, Action::for_strike(&policy, 1));
+        assert_eq!(policy[1], Action::for_strike(&policy, 2));
+        assert_eq!(policy[2], Action::for_strike(&policy, 3));
+        assert_eq!(policy[2], Action::for_strike(&policy, 4));
+        assert_eq!(Action::Delete, Action::for_strike(&[], 2));
+
+        let policy = Action::default_policy();
+        assert_eq!(Action::Delete, Action::for_strike(&policy, 3));
+    }
+}
@@ -0,0 +1,126 @@
+//! Index of when users were last seen in chat.
+//!
+//! Users are seen when they chat, and when they show up in the list of chatters of the channel
+//! which includes lurkers.
+
+use hashbrown::{HashMap, HashSet};
+use parking_lot::RwLock;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+#[derive(Default)]
+struct Inner {
+    /// When each user was last seen.
+    seen: HashMap<String, Instant>,
+    /// Users which are always considered present, like the streamer.
+    exempt: HashSet<String>,
+}
+
+#[derive(Clone)]
+pub struct LastSeen {
+    inner: Arc<RwLock<Inner>>,
+    /// When the index was created.
+    ///
+    /// Users which have not been seen are treated as if they were seen at this time, since we
+    /// have no way of knowing if they were in chat before it.
+    started_at: Instant,
+}
+
+impl LastSeen {
+    /// Construct a new, empty index.
+    pub fn new() -> Self {
+        LastSeen {
+            inner: Default::default(),
+            started_at: Instant::now(),
+        }
+    }
+
+    /// Indicate that the given user has been seen in chat.
+    pub fn seen(&self, user: &str) {
+        self.seen_at(user, Instant::now());
+    }
+
+    /// Indicate that all the given users have been seen in chat.
+    pub fn seen_all<'a>(&self, users: impl IntoIterator<Item = &'a str>) {
+        let now = Instant::now();
+
+        for user in users {
+            self.seen_at(user, now);
+        }
+    }
+
+    fn seen_at(&self, user: &str, now: Instant) {
+        let mut inner = self.inner.write();
+
+        match inner.seen.get_mut(user) {
+            Some(seen) => *seen = now,
+            None => {
+                inner.seen.insert(user.to_string(), now);
+            }
+        }
+    }
+
+    /// Mark the given user as always being present.
+    pub fn exempt(&self, user: &str) {
+        self.inner.write().exempt.insert(user.to_string());
+    }
+
+    /// Get how long the given user has been absent from chat, or `None` if they are exempt.
+    pub fn absent_for(&self, user: &str) -> Option<Duration> {
+        self.absent_at(user, Instant::now())
+    }
+
+    fn absent_at(&self, user: &str, now: Instant) -> Option<Duration> {
+        let inner = self.inner.read();
+
+        if inner.exempt.contains(user) {
+            return None;
+        }
+
+        let seen = inner.seen.get(user).cloned().unwrap_or(self.started_at);
+        Some(now.duration_since(seen))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LastSeen;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_absent() {
+        let last_seen = LastSeen::new();
+        let now = last_seen.started_at + Duration::from_secs(60);
+
+        // NB: users which haven't been seen count from when the index was created.
+        assert_eq!(
+            Some(Duration::from_secs(60)),
+            last_seen.absent_at("alice", now)
+        );
+
+        last_seen.seen_at("alice", now);
+        assert_eq!(
+            Some(Duration::from_secs(30)),
+            last_seen.absent_at("alice", now + Duration::from_secs(30))
+        );
+
+        last_seen.exempt("streamer");
+        assert_eq!(None, last_seen.absent_at("streamer", now));
+    }
+
+    #[test]
+    fn test_seen_all() {
+        let last_seen = LastSeen::new();
+        let before = Instant::now();
+        last_seen.seen_all(vec!["alice", "bob"]);
+
+        let later = Instant::now() + Duration::from_secs(10);
+
+        for user in &["alice", "bob"] {
+            let absent = last_seen.absent_at(user, later).expect("absent");
+            assert!(absent <= later.duration_since(before));
+        }
+    }
+}
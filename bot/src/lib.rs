@@ -22,6 +22,7 @@ pub mod features;
 mod idle;
 pub mod injector;
 pub mod irc;
+pub mod last_seen;
 pub mod module;
 pub mod oauth2;
 pub mod obs;
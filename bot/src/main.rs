@@ -4,8 +4,8 @@
 use failure::{format_err, Error, ResultExt};
 use parking_lot::RwLock;
 use setmod_bot::{
-    api, auth, bus, config, db, injector, irc, module, oauth2, obs, player, prelude::*, secrets,
-    settings, utils, web,
+    api, auth, bus, config, db, injector, irc, last_seen, module, oauth2, obs, player, prelude::*,
+    secrets, settings, utils, web,
 };
 use std::{
     fs,
@@ -166,6 +166,10 @@ async fn try_main(root: PathBuf, web_root: Option<PathBuf>, config: PathBuf) ->
     let aliases = db::Aliases::load(db.clone())?;
     let promotions = db::Promotions::load(db.clone())?;
     let themes = db::Themes::load(db.clone())?;
+    let history = db::History::load(db.clone())?;
+    let song_bans = db::SongBans::load(db.clone())?;
+    let song_stats = db::SongStats::load(db.clone())?;
+    let moderation_log = db::ModerationLog::load(db.clone())?;
 
     if !config.whitelisted_hosts.is_empty() {
         log::warn!("The `whitelisted_hosts` section in the configuration is now deprecated.");
@@ -207,7 +211,9 @@ async fn try_main(root: PathBuf, web_root: Option<PathBuf>, config: PathBuf) ->
     }
 
     let global_bus = Arc::new(bus::Bus::new());
+    let last_seen = last_seen::LastSeen::new();
     let youtube_bus = Arc::new(bus::Bus::new());
+    let local_bus = Arc::new(bus::Bus::new());
     let global_channel = Arc::new(RwLock::new(None));
 
     let mut futures = Vec::<future::BoxFuture<'_, Result<(), Error>>>::new();
@@ -222,6 +228,7 @@ async fn try_main(root: PathBuf, web_root: Option<PathBuf>, config: PathBuf) ->
         web_root.as_ref().map(|p| p.as_path()),
         global_bus.clone(),
         youtube_bus.clone(),
+        local_bus.clone(),
         after_streams.clone(),
         db.clone(),
         settings.clone(),
@@ -230,6 +237,10 @@ async fn try_main(root: PathBuf, web_root: Option<PathBuf>, config: PathBuf) ->
         commands.clone(),
         promotions.clone(),
         themes.clone(),
+        history.clone(),
+        song_bans.clone(),
+        song_stats.clone(),
+        moderation_log.clone(),
         global_channel.clone(),
         currency,
     )?;
@@ -270,6 +281,8 @@ async fn try_main(root: PathBuf, web_root: Option<PathBuf>, config: PathBuf) ->
         .with_scopes(vec![
             String::from("playlist-read-collaborative"),
             String::from("playlist-read-private"),
+            String::from("playlist-modify-public"),
+            String::from("playlist-modify-private"),
             String::from("user-library-read"),
             String::from("user-modify-playback-state"),
             String::from("user-read-playback-state"),
@@ -358,8 +371,14 @@ async fn try_main(root: PathBuf, web_root: Option<PathBuf>, config: PathBuf) ->
         config.clone(),
         global_bus.clone(),
         youtube_bus.clone(),
+        local_bus.clone(),
         settings.clone(),
         themes.clone(),
+        history.clone(),
+        song_bans.clone(),
+        bad_words.clone(),
+        song_stats.clone(),
+        last_seen.clone(),
     )?;
 
     futures.push(future.boxed());
@@ -386,6 +405,7 @@ async fn try_main(root: PathBuf, web_root: Option<PathBuf>, config: PathBuf) ->
     modules.push(Box::new(module::clip::Module));
     modules.push(Box::new(module::eight_ball::Module));
     modules.push(Box::new(module::speedrun::Module));
+    modules.push(Box::new(module::modlog::Module));
 
     if config.obs.is_some() {
         log::warn!("`[obs]` setting has been deprecated from the configuration");
@@ -412,6 +432,7 @@ async fn try_main(root: PathBuf, web_root: Option<PathBuf>, config: PathBuf) ->
         themes,
         bad_words,
         after_streams,
+        moderation_log,
         global_bus,
         modules,
         shutdown,
@@ -419,6 +440,7 @@ async fn try_main(root: PathBuf, web_root: Option<PathBuf>, config: PathBuf) ->
         auth,
         global_channel,
         injector: injector.clone(),
+        last_seen,
     };
 
     futures.push(irc.run().boxed());
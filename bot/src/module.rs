@@ -13,6 +13,7 @@ pub mod countdown;
 pub mod eight_ball;
 pub mod gtav;
 pub mod misc;
+pub mod modlog;
 pub mod promotions;
 pub mod song;
 pub mod speedrun;
@@ -67,6 +68,7 @@ pub struct HookContext<'a: 'm, 'm> {
     pub promotions: &'a db::Promotions,
     pub themes: &'a db::Themes,
     pub after_streams: &'a db::AfterStreams,
+    pub moderation_log: &'a db::ModerationLog,
     pub youtube: &'a Arc<api::YouTube>,
     pub twitch: &'a api::Twitch,
     pub streamer_twitch: &'a api::Twitch,
@@ -0,0 +1,105 @@
+use crate::{auth, command, db, module, prelude::*, utils};
+use parking_lot::RwLock;
+use std::{sync::Arc, time};
+
+/// Number of entries to show for a user.
+const LIMIT: i64 = 5;
+
+/// Handler for the `!modlog` command.
+pub struct ModLog<'a> {
+    pub enabled: Arc<RwLock<bool>>,
+    pub moderation_log: &'a db::ModerationLog,
+}
+
+impl command::Handler for ModLog<'_> {
+    fn scope(&self) -> Option<auth::Scope> {
+        Some(auth::Scope::ModLog)
+    }
+
+    fn handle<'m>(&mut self, mut ctx: command::Context<'_, 'm>) -> Result<(), failure::Error> {
+        if !*self.enabled.read() {
+            return Ok(());
+        }
+
+        let user = ctx_try!(ctx.next_str("<user>", "!modlog"));
+        let user = user.trim_start_matches('@');
+
+        let channel = ctx.user.target;
+        let entries = self
+            .moderation_log
+            .list(Some(channel), Some(user), 0, LIMIT)?;
+
+        if entries.is_empty() {
+            ctx.respond(format!("No moderation actions against {}.", user));
+            return Ok(());
+        }
+
+        let total = self.moderation_log.count(Some(channel), Some(user))?;
+
+        let entries = entries
+            .iter()
+            .map(display_entry)
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        ctx.respond(format!(
+            "{} moderation action(s) against {}, latest first: {}",
+            total, user, entries
+        ));
+
+        Ok(())
+    }
+}
+
+/// Format a single entry for chat.
+fn display_entry(e: &db::ModerationEntry) -> String {
+    let mut out = format!("{} {}", e.created_at.format("%Y-%m-%d %H:%M"), e.kind);
+
+    if let Some(duration) = e.duration {
+        let duration = time::Duration::from_secs(duration as u64);
+        out.push_str(&format!(" {}", utils::compact_duration(&duration)));
+    }
+
+    if let Some(moderator) = e.moderator.as_ref() {
+        out.push_str(&format!(" by {}", moderator));
+    }
+
+    if let Some(reason) = e.reason.as_ref() {
+        out.push_str(&format!(" ({})", reason));
+    }
+
+    out
+}
+
+pub struct Module;
+
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "modlog"
+    }
+
+    /// Set up command handlers for this module.
+    fn hook(
+        &self,
+        module::HookContext {
+            handlers,
+            settings,
+            futures,
+            moderation_log,
+            ..
+        }: module::HookContext<'_, '_>,
+    ) -> Result<(), failure::Error> {
+        let mut vars = settings.vars();
+
+        handlers.insert(
+            "modlog",
+            ModLog {
+                enabled: vars.var("modlog/enabled", true)?,
+                moderation_log,
+            },
+        );
+
+        futures.push(vars.run().boxed());
+        Ok(())
+    }
+}
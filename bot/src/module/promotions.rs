@@ -152,7 +152,7 @@ fn promote(promotions: db::Promotions, sender: irc::Sender) -> Result<(), failur
     if let Some(p) = pick(promotions.list(channel)) {
         let text = p.render(&PromoData { channel })?;
         promotions.bump_promoted_at(&*p)?;
-        sender.privmsg(text);
+        sender.privmsg_with(irc::Priority::Low, text);
     }
 
     Ok(())
@@ -1,9 +1,10 @@
 use crate::{
+    api,
     auth::Scope,
     command,
-    currency::Currency,
-    irc, module, player,
-    player::{AddTrackError, Event, Item, PlayThemeError, Player},
+    currency::{BalanceTransferError, Currency},
+    db, irc, module, player,
+    player::{AddTrackError, Event, Item, PlayThemeError, Player, Requester},
     prelude::*,
     settings, stream_info, track_id,
     track_id::TrackId,
@@ -11,6 +12,7 @@ use crate::{
 };
 use chrono::Utc;
 use failure::{Error, ResultExt as _};
+use hashbrown::HashSet;
 use parking_lot::RwLock;
 use std::sync::Arc;
 
@@ -24,9 +26,15 @@ pub struct Handler {
     request_help_cooldown: Cooldown,
     subscriber_only: Arc<RwLock<bool>>,
     request_reward: Arc<RwLock<u32>>,
+    promote_price: Arc<RwLock<u32>>,
     currency: Arc<RwLock<Option<Currency>>>,
     spotify: Constraint,
     youtube: Constraint,
+    local: Constraint,
+    voteskip: VoteSkip,
+    dislike_skip: DislikeSkip,
+    save: Save,
+    twitch: api::Twitch,
 }
 
 impl Handler {
@@ -48,10 +56,14 @@ impl Handler {
         let request_reward = *self.request_reward.read();
         let spotify = self.spotify.clone();
         let youtube = self.youtube.clone();
+        let local = self.local.clone();
         let user = ctx.user.as_owned_user();
         let is_moderator = ctx.is_moderator();
+        let is_subscriber = ctx.is_subscriber();
+        let is_vip = ctx.is_vip();
         let has_spotify_scope = ctx.has_scope(Scope::SongSpotify);
         let has_youtube_scope = ctx.has_scope(Scope::SongYouTube);
+        let has_local_scope = ctx.has_scope(Scope::SongLocal);
 
         let track_id = match TrackId::parse_with_urls(&q) {
             Ok(track_id) => Some(track_id),
@@ -76,7 +88,11 @@ impl Handler {
         let future = async move {
             let track_id = match track_id {
                 Some(track_id) => Some(track_id),
-                None => player.search_track(q).await?,
+                None => {
+                    // NB: only prefer the local library if local requests are possible.
+                    let local = *local.enabled.read() && has_local_scope;
+                    player.search_track(q, local).await?
+                }
             };
 
             let track_id = match track_id {
@@ -96,6 +112,10 @@ impl Handler {
                     let enabled = *youtube.enabled.read();
                     ("YouTube", has_youtube_scope, enabled)
                 }
+                TrackId::Local(..) => {
+                    let enabled = *local.enabled.read();
+                    ("Local", has_local_scope, enabled)
+                }
             };
 
             if !enabled {
@@ -117,6 +137,7 @@ impl Handler {
             let subscriber_only_by_track = match track_id {
                 TrackId::Spotify(..) => *spotify.subscriber_only.read(),
                 TrackId::YouTube(..) => *youtube.subscriber_only.read(),
+                TrackId::Local(..) => *local.subscriber_only.read(),
             };
 
             let subscriber_only = subscriber_only_by_track || *subscriber_only.read();
@@ -134,27 +155,53 @@ impl Handler {
             let max_duration = match track_id {
                 TrackId::Spotify(_) => Some(spotify.max_duration.read().clone()),
                 TrackId::YouTube(_) => Some(youtube.max_duration.read().clone()),
+                TrackId::Local(_) => Some(local.max_duration.read().clone()),
             };
 
             let min_currency = match track_id {
                 TrackId::Spotify(_) => Some(spotify.min_currency.read().clone() as i64),
                 TrackId::YouTube(_) => Some(youtube.min_currency.read().clone() as i64),
+                TrackId::Local(_) => Some(local.min_currency.read().clone() as i64),
+            };
+
+            let price = match track_id {
+                TrackId::Spotify(_) => *spotify.price.read() as i64,
+                TrackId::YouTube(_) => *youtube.price.read() as i64,
+                TrackId::Local(_) => *local.price.read() as i64,
+            };
+
+            let requester = Requester {
+                name: user.name.clone(),
+                is_moderator,
+                is_subscriber,
+                is_vip,
+                enforce_bans: false,
             };
 
             let result = player
                 .add_track(
                     currency.clone(),
                     user.target.clone(),
-                    user.name.clone(),
+                    requester,
                     track_id,
-                    is_moderator,
                     max_duration,
                     min_currency,
+                    Some(price),
                 )
                 .await;
 
             let (pos, item) = match result {
-                Ok((pos, item)) => (pos, item),
+                Ok((player::Added::Queue(pos), item)) => (pos, item),
+                Ok((player::Added::Pending(pos), item)) => {
+                    user.respond(format!(
+                        "Your request for {what} is waiting to be approved by a moderator \
+                         (#{pos} in line).",
+                        what = item.what(),
+                        pos = pos + 1,
+                    ));
+
+                    return Ok(());
+                }
                 Err(AddTrackError::PlayerClosed(reason)) => {
                     match reason {
                         Some(reason) => {
@@ -175,6 +222,14 @@ impl Handler {
 
                     return Ok(());
                 }
+                Err(AddTrackError::PendingContainsTrack(pos)) => {
+                    user.respond(format!(
+                        "That track is already waiting to be approved (#{pos} in line).",
+                        pos = pos + 1,
+                    ));
+
+                    return Ok(());
+                }
                 Err(AddTrackError::TooManyUserTracks(count)) => {
                     match count {
                         0 => {
@@ -241,7 +296,7 @@ impl Handler {
                     };
 
                     user.respond(format!(
-                        "You don't have enough {currency} to request songs. Need {required}, but you have {balance}, sorry :(",
+                        "You don't have enough {currency} to request that song. Need {required}, but you have {balance}, sorry :(",
                         currency = currency,
                         required = required,
                         balance = balance,
@@ -249,18 +304,88 @@ impl Handler {
 
                     return Ok(());
                 }
+                Err(AddTrackError::Banned(kind, reason)) => {
+                    let what = match kind {
+                        db::BanKind::Track => "That song is",
+                        db::BanKind::Artist => "The artist of that song is",
+                        db::BanKind::Channel => "The channel of that video is",
+                    };
+
+                    match reason {
+                        Some(reason) => {
+                            user.respond(format!("{} banned: {}", what, reason));
+                        }
+                        None => {
+                            user.respond(format!("{} banned from being requested, sorry :(", what));
+                        }
+                    }
+
+                    return Ok(());
+                }
+                Err(AddTrackError::Policy(violation)) => {
+                    user.respond(format!(
+                        "That song can't be requested since {}, sorry :(",
+                        violation
+                    ));
+                    return Ok(());
+                }
+                Err(AddTrackError::RequestQuotaExceeded {
+                    limit,
+                    window,
+                    wait,
+                }) => {
+                    user.respond(format!(
+                        "You can only request {limit} songs every {window}, \
+                         you can request again in {wait}.",
+                        limit = limit,
+                        window = utils::compact_duration(&window.as_std()),
+                        wait = utils::compact_duration(&wait),
+                    ));
+
+                    return Ok(());
+                }
+                Err(AddTrackError::DurationQuotaExceeded { limit, wait }) => {
+                    let limit = utils::compact_duration(&limit.as_std());
+
+                    match wait {
+                        Some(wait) => {
+                            user.respond(format!(
+                                "You can only have {limit} of songs in the queue, \
+                                 you can request that song again in {wait}.",
+                                limit = limit,
+                                wait = utils::compact_duration(&wait),
+                            ));
+                        }
+                        None => {
+                            user.respond(format!(
+                                "That song is longer than the {limit} of songs you can have in the queue, sorry :(",
+                                limit = limit,
+                            ));
+                        }
+                    }
+
+                    return Ok(());
+                }
                 Err(AddTrackError::Error(e)) => {
                     return Err(e);
                 }
             };
 
+            let charged = match currency.as_ref() {
+                Some(currency) if price > 0 && !is_moderator => {
+                    format!(" for {} {}", price, currency.name)
+                }
+                _ => String::new(),
+            };
+
             let currency = match currency.as_ref() {
                 Some(currency) if request_reward > 0 => currency,
                 _ => {
                     user.respond(format!(
-                        "Added {what} at position #{pos}!",
+                        "Added {what} at position #{pos}{charged}!",
                         what = item.what(),
-                        pos = pos + 1
+                        pos = pos + 1,
+                        charged = charged,
                     ));
 
                     return Ok(());
@@ -277,9 +402,10 @@ impl Handler {
             {
                 Ok(()) => {
                     user.respond(format!(
-                        "Added {what} at position #{pos}, here's your {amount} {currency}!",
+                        "Added {what} at position #{pos}{charged}, here's your {amount} {currency}!",
                         what = item.what(),
                         pos = pos + 1,
+                        charged = charged,
                         amount = request_reward,
                         currency = currency.name,
                     ));
@@ -306,6 +432,498 @@ impl Handler {
         Ok(())
     }
 
+    /// Handle a user paying to promote their own song.
+    fn handle_paid_promote(
+        &mut self,
+        ctx: &mut command::Context<'_, '_>,
+        player: Player,
+        index: usize,
+        price: i64,
+    ) {
+        let item = match player.get(index) {
+            Some(item) => item,
+            None => {
+                ctx.respond("No such song to promote");
+                return;
+            }
+        };
+
+        if !item
+            .user
+            .as_ref()
+            .map(|u| ctx.user.is(u))
+            .unwrap_or_default()
+        {
+            ctx.respond("You can only promote your own songs.");
+            return;
+        }
+
+        let currency = match self.currency.read().clone() {
+            Some(currency) => currency,
+            None => {
+                ctx.respond("No currency configured for stream, but it is required.");
+                return;
+            }
+        };
+
+        let user = ctx.user.as_owned_user();
+
+        let future = async move {
+            let result = currency
+                .balance_spend(user.target.clone(), user.name.clone(), price)
+                .await;
+
+            match result {
+                Ok(()) => (),
+                Err(BalanceTransferError::NoBalance) => {
+                    user.respond(format!(
+                        "You need {price} {currency} to promote a song, sorry :(",
+                        price = price,
+                        currency = currency.name,
+                    ));
+                    return Ok(());
+                }
+                Err(BalanceTransferError::Other(e)) => return Err(e),
+            }
+
+            // NB: make sure the song didn't move while we were charging.
+            let promoted = match player.get(index) {
+                Some(ref i) if i.track_id == item.track_id => {
+                    player.promote_song(&user.name, index)
+                }
+                _ => None,
+            };
+
+            match promoted {
+                Some(item) => {
+                    user.respond(format!(
+                        "Promoted song to head of queue for {} {}: {}",
+                        price,
+                        currency.name,
+                        item.what()
+                    ));
+                }
+                None => {
+                    currency
+                        .balance_add(user.target.clone(), user.name.clone(), price)
+                        .await?;
+                    user.respond("Could not promote your song, you have been refunded.");
+                }
+            }
+
+            Ok(())
+        };
+
+        ctx.spawn_result("!song promote", future);
+    }
+
+    /// Handle a moderator queueing up all the songs in a playlist or album.
+    fn handle_playlist(
+        &mut self,
+        ctx: &mut command::Context<'_, '_>,
+        player: Player,
+    ) -> Result<(), Error> {
+        ctx.check_moderator()?;
+
+        let url = ctx_try!(ctx.next_str("<url> [--shuffle] [--limit <n>]", "!song playlist"));
+
+        let playlist = match track_id::PlaylistId::parse_with_urls(url) {
+            Ok(playlist) => playlist,
+            Err(e) => {
+                ctx.respond(format!("{} :(", e));
+                return Ok(());
+            }
+        };
+
+        let mut shuffle = false;
+        let mut limit = None;
+
+        while let Some(arg) = ctx.next() {
+            match arg {
+                "--shuffle" => shuffle = true,
+                "--limit" => match ctx.next().map(str::parse::<usize>) {
+                    Some(Ok(n)) => limit = Some(n),
+                    _ => {
+                        ctx.respond("Expected number argument to `--limit`");
+                        return Ok(());
+                    }
+                },
+                other => {
+                    ctx.respond(format!("`{}` is not a valid parameter", other));
+                    return Ok(());
+                }
+            }
+        }
+
+        let (what, enabled, max_duration) = match playlist {
+            track_id::PlaylistId::SpotifyPlaylist(..) | track_id::PlaylistId::SpotifyAlbum(..) => (
+                "Spotify",
+                *self.spotify.enabled.read(),
+                self.spotify.max_duration.read().clone(),
+            ),
+            track_id::PlaylistId::YouTube(..) => (
+                "YouTube",
+                *self.youtube.enabled.read(),
+                self.youtube.max_duration.read().clone(),
+            ),
+        };
+
+        if !enabled {
+            ctx.respond(format!("{} song requests are currently not enabled.", what));
+            return Ok(());
+        }
+
+        let currency: Option<Currency> = self.currency.read().clone();
+        let user = ctx.user.as_owned_user();
+
+        let future = async move {
+            let mut tracks = match player.playlist_tracks(playlist).await {
+                Ok(tracks) => tracks,
+                Err(e) => {
+                    user.respond("Could not load that playlist, sorry :(");
+                    return Err(e);
+                }
+            };
+
+            if shuffle {
+                use rand::seq::SliceRandom as _;
+                tracks.shuffle(&mut rand::thread_rng());
+            }
+
+            // NB: moderators are allowed to exceed the queue length, but bulk adds are not.
+            let capacity = player.remaining_capacity();
+            let limit = limit.map(|l| usize::min(l, capacity)).unwrap_or(capacity);
+            let truncated = tracks.len() > limit;
+            tracks.truncate(limit);
+
+            let mut added = 0;
+            let mut skipped = 0;
+
+            for track_id in tracks {
+                let requester = Requester {
+                    name: user.name.clone(),
+                    is_moderator: true,
+                    is_subscriber: false,
+                    is_vip: false,
+                    enforce_bans: true,
+                };
+
+                let result = player
+                    .add_track(
+                        currency.clone(),
+                        user.target.clone(),
+                        requester,
+                        track_id.clone(),
+                        Some(max_duration.clone()),
+                        None,
+                        None,
+                    )
+                    .await;
+
+                match result {
+                    Ok(..) => added += 1,
+                    Err(AddTrackError::Error(e)) => {
+                        log_err!(e, "failed to add playlist track: {}", track_id);
+                        skipped += 1;
+                    }
+                    Err(..) => skipped += 1,
+                }
+            }
+
+            let mut extra = Vec::new();
+
+            if skipped > 0 {
+                extra.push(format!("{} skipped", skipped));
+            }
+
+            if truncated {
+                extra.push(String::from("limited by queue size"));
+            }
+
+            let extra = if extra.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", extra.join(", "))
+            };
+
+            user.respond(format!("Added {} songs to the queue{}.", added, extra));
+            Ok(())
+        };
+
+        ctx.spawn_result("!song playlist", future);
+        Ok(())
+    }
+
+    /// Handle a vote to skip the current song.
+    fn handle_voteskip(
+        &mut self,
+        ctx: &mut command::Context<'_, '_>,
+        player: Player,
+    ) -> Result<(), Error> {
+        if !*self.voteskip.enabled.read() {
+            ctx.respond("Vote skipping is not enabled.");
+            return Ok(());
+        }
+
+        let current = match player.current() {
+            Some(current) => current,
+            None => {
+                ctx.respond("No song is currently playing.");
+                return Ok(());
+            }
+        };
+
+        let is_requester = current
+            .item
+            .user
+            .as_ref()
+            .map(|u| ctx.user.is(u))
+            .unwrap_or_default();
+
+        if is_requester && *self.voteskip.requester_skip.read() {
+            player.skip()?;
+            ctx.respond("Skipping your song.");
+            return Ok(());
+        }
+
+        let track_id = current.item.track_id.clone();
+
+        let votes = {
+            let mut state = self.voteskip.state.write();
+
+            // NB: votes belong to a different song, start over.
+            if state.track_id.as_ref() != Some(&track_id) {
+                state.track_id = Some(track_id.clone());
+                state.voters.clear();
+            }
+
+            if !state.voters.insert(ctx.user.name.to_string()) {
+                ctx.respond("You have already voted to skip this song.");
+                return Ok(());
+            }
+
+            state.voters.len() as u32
+        };
+
+        let min_votes = *self.voteskip.votes.read();
+        let percentage = *self.voteskip.percentage.read();
+        let twitch = self.twitch.clone();
+        let streamer = ctx.streamer.to_string();
+        let user = ctx.user.as_owned_user();
+        let state = self.voteskip.state.clone();
+
+        let future = async move {
+            let mut required = u32::max(min_votes, 1);
+
+            if percentage > 0 {
+                match twitch.chatters(streamer).await {
+                    Ok(chatters) => {
+                        let count = chatters_count(&chatters) as u32;
+                        let by_percentage = (count * percentage + 99) / 100;
+                        required = u32::max(required, by_percentage);
+                    }
+                    Err(e) => {
+                        log_err!(e, "failed to get chatters for vote skip");
+                    }
+                }
+            }
+
+            if votes < required {
+                user.respond(format!(
+                    "Vote registered, {} out of {} votes needed to skip the current song.",
+                    votes, required
+                ));
+                return Ok(());
+            }
+
+            // NB: make sure we are still voting on the current song.
+            let is_current = match player.current() {
+                Some(current) => current.item.track_id == track_id,
+                None => false,
+            };
+
+            if !is_current {
+                return Ok(());
+            }
+
+            {
+                let mut state = state.write();
+                state.track_id = None;
+                state.voters.clear();
+            }
+
+            player.skip()?;
+            user.respond(format!("Vote passed with {} votes, skipping song.", votes));
+            Ok(())
+        };
+
+        ctx.spawn_result("!song voteskip", future);
+        return Ok(());
+
+        /// Count the number of chatters in the channel.
+        fn chatters_count(chatters: &api::twitch::Chatters) -> usize {
+            chatters.broadcaster.len()
+                + chatters.vips.len()
+                + chatters.moderators.len()
+                + chatters.staff.len()
+                + chatters.admins.len()
+                + chatters.global_mods.len()
+                + chatters.viewers.len()
+        }
+    }
+
+    /// Rate the current song.
+    fn handle_rate(
+        &mut self,
+        ctx: &mut command::Context<'_, '_>,
+        player: Player,
+        rating: db::Rating,
+    ) -> Result<(), Error> {
+        let current = match player.current() {
+            Some(current) => current,
+            None => {
+                ctx.respond("No song is currently playing.");
+                return Ok(());
+            }
+        };
+
+        let track_id = &current.item.track_id;
+        player.stats().rate(track_id, ctx.user.name, rating)?;
+        let ratings = player.stats().ratings(track_id, None)?;
+
+        if rating == db::Rating::Dislike && *self.dislike_skip.enabled.read() {
+            // NB: only ratings made while the song is currently playing count towards skipping.
+            let played_at = current.played_at().map(|t| t.naive_utc());
+            let scoped = player.stats().ratings(track_id, played_at)?;
+
+            let votes = scoped.likes + scoped.dislikes;
+            let min_votes = *self.dislike_skip.votes.read() as i64;
+            let percentage = *self.dislike_skip.percentage.read() as i64;
+
+            if votes >= i64::max(min_votes, 1) && scoped.dislikes * 100 >= votes * percentage {
+                player.skip()?;
+                ctx.respond(format!(
+                    "{} out of {} ratings are dislikes, skipping song.",
+                    scoped.dislikes, votes
+                ));
+                return Ok(());
+            }
+        }
+
+        ctx.respond(format!(
+            "Thanks for rating {}! ({} likes, {} dislikes)",
+            current.item.what(),
+            ratings.likes,
+            ratings.dislikes
+        ));
+
+        Ok(())
+    }
+
+    /// Show song statistics.
+    fn handle_top(
+        &mut self,
+        ctx: &mut command::Context<'_, '_>,
+        player: Player,
+    ) -> Result<(), Error> {
+        const LIMIT: i64 = 5;
+
+        let stats = player.stats();
+
+        let (what, lines) = match ctx.next().unwrap_or("requested") {
+            "requested" => ("Most requested", track_counts(stats.most_requested(LIMIT)?)),
+            "liked" => ("Most liked", track_counts(stats.most_liked(LIMIT)?)),
+            "played" => ("Most played", track_counts(stats.most_played(LIMIT)?)),
+            "requesters" => {
+                let lines = stats
+                    .top_requesters(LIMIT)?
+                    .into_iter()
+                    .map(|c| format!("{} ({})", c.user, c.count))
+                    .collect::<Vec<_>>();
+
+                ("Top requesters", lines)
+            }
+            other => {
+                ctx.respond(format!(
+                    "`{}` is not a valid statistic, expected one of: requested, liked, played, or requesters",
+                    other
+                ));
+                return Ok(());
+            }
+        };
+
+        if lines.is_empty() {
+            ctx.respond("No statistics available yet.");
+            return Ok(());
+        }
+
+        ctx.respond(format!("{}: {}.", what, lines.join("; ")));
+        return Ok(());
+
+        fn track_counts(counts: Vec<db::TrackCount>) -> Vec<String> {
+            counts
+                .into_iter()
+                .map(|c| match c.name {
+                    Some(name) => format!("\"{}\" ({})", name, c.count),
+                    None => format!("{} ({})", c.track_id.url(), c.count),
+                })
+                .collect()
+        }
+    }
+
+    /// Save the current song to a Spotify playlist.
+    fn handle_save(
+        &mut self,
+        ctx: &mut command::Context<'_, '_>,
+        player: Player,
+    ) -> Result<(), Error> {
+        ctx.check_scope(Scope::SongSave)?;
+
+        let playlist = match ctx.next() {
+            Some(playlist) => playlist.to_string(),
+            None => self.save.playlist.read().clone(),
+        };
+
+        if playlist.trim().is_empty() {
+            ctx.respond(
+                "No playlist to save to, use `!song save <playlist>` or configure `song/save/playlist`.",
+            );
+            return Ok(());
+        }
+
+        let playlist = match parse_spotify_playlist(&playlist) {
+            Ok(playlist) => playlist,
+            Err(e) => {
+                ctx.respond(format!("Bad playlist: {}", e));
+                return Ok(());
+            }
+        };
+
+        let item = match player.current() {
+            Some(current) => current.item,
+            None => {
+                ctx.respond("No song is currently playing.");
+                return Ok(());
+            }
+        };
+
+        if let TrackId::YouTube(..) | TrackId::Local(..) = item.track_id {
+            ctx.respond("Only Spotify songs can be saved to a playlist, sorry :(");
+            return Ok(());
+        }
+
+        let user = ctx.user.as_owned_user();
+
+        let future = async move {
+            player.save_track(playlist, &item.track_id).await?;
+            user.respond(format!("Saved {} to the playlist.", item.what()));
+            Ok(())
+        };
+
+        ctx.spawn_result("!song save", future);
+        Ok(())
+    }
+
     /// Provide a help message instructing the user how to perform song requests.
     fn request_help(&mut self, ctx: &mut command::Context<'_, '_>, reason: Option<&str>) {
         if !self.request_help_cooldown.is_open() {
@@ -371,19 +989,231 @@ impl command::Handler for Handler {
                 });
             }
             Some("promote") => {
-                ctx.check_moderator()?;
+                let promote_price = *self.promote_price.read();
+
+                // NB: with a price set, anyone can pay to promote their own song.
+                let paid = !ctx.is_moderator() && promote_price > 0;
+
+                if !paid {
+                    ctx.check_moderator()?;
+                }
 
                 let index = match ctx.next().and_then(|n| parse_queue_position(&ctx.user, n)) {
                     Some(index) => index,
                     None => return Ok(()),
                 };
 
+                if paid {
+                    self.handle_paid_promote(&mut ctx, player, index, promote_price as i64);
+                    return Ok(());
+                }
+
                 if let Some(item) = player.promote_song(ctx.user.name, index) {
                     ctx.respond(format!("Promoted song to head of queue: {}", item.what()));
                 } else {
                     ctx.respond("No such song to promote");
                 }
             }
+            Some("move") => {
+                ctx.check_moderator()?;
+
+                let (from, to) = match (ctx.next(), ctx.next()) {
+                    (Some(from), Some(to)) => (from, to),
+                    _ => {
+                        ctx.respond("Expected: !song move <from> <to>");
+                        return Ok(());
+                    }
+                };
+
+                let from = match parse_queue_position(&ctx.user, from) {
+                    Some(from) => from,
+                    None => return Ok(()),
+                };
+
+                let to = match parse_queue_position(&ctx.user, to) {
+                    Some(to) => to,
+                    None => return Ok(()),
+                };
+
+                match player.move_song(from, to) {
+                    Some(item) => {
+                        ctx.respond(format!("Moved {} to position #{}", item.what(), to + 1));
+                    }
+                    None => {
+                        ctx.respond("No such song to move");
+                    }
+                }
+            }
+            Some("swap") => {
+                ctx.check_moderator()?;
+
+                let (a, b) = match (ctx.next(), ctx.next()) {
+                    (Some(a), Some(b)) => (a, b),
+                    _ => {
+                        ctx.respond("Expected: !song swap <a> <b>");
+                        return Ok(());
+                    }
+                };
+
+                let a = match parse_queue_position(&ctx.user, a) {
+                    Some(a) => a,
+                    None => return Ok(()),
+                };
+
+                let b = match parse_queue_position(&ctx.user, b) {
+                    Some(b) => b,
+                    None => return Ok(()),
+                };
+
+                if player.swap_songs(a, b) {
+                    ctx.respond(format!("Swapped songs #{} and #{}", a + 1, b + 1));
+                } else {
+                    ctx.respond("No such songs to swap");
+                }
+            }
+            Some("shuffle") => {
+                ctx.check_moderator()?;
+                player.shuffle();
+                ctx.respond("Shuffled the song queue");
+            }
+            Some("playlist") => {
+                self.handle_playlist(&mut ctx, player)?;
+            }
+            Some("pending") => {
+                ctx.check_moderator()?;
+                let items = player.pending();
+
+                if items.is_empty() {
+                    ctx.respond("No songs are waiting to be approved.");
+                    return Ok(());
+                }
+
+                let lines = items
+                    .iter()
+                    .enumerate()
+                    .map(|(index, item)| match item.user.as_ref() {
+                        Some(user) => format!("#{}: {} ({})", index + 1, item.what(), user),
+                        None => format!("#{}: {}", index + 1, item.what()),
+                    })
+                    .collect::<Vec<_>>();
+
+                ctx.respond(format!("Waiting for approval: {}.", lines.join("; ")));
+            }
+            Some("approve") => {
+                ctx.check_moderator()?;
+
+                let selector = ctx_try!(ctx.next_str("<n|user>", "!song approve"));
+                let selector = parse_pending_selector(selector);
+                let user = ctx.user.as_owned_user();
+
+                let future = async move {
+                    match player.approve(selector).await {
+                        Ok(Some((pos, item))) => {
+                            user.respond(format!(
+                                "Approved {} at position #{}.",
+                                item.what(),
+                                pos + 1
+                            ));
+                        }
+                        Ok(None) => {
+                            user.respond("No such song is waiting to be approved.");
+                        }
+                        Err(AddTrackError::QueueFull) => {
+                            user.respond(
+                                "Player is full, the song is still waiting to be approved.",
+                            );
+                        }
+                        Err(AddTrackError::QueueContainsTrack(pos)) => {
+                            user.respond(format!(
+                                "Player already contains that track (position #{pos}).",
+                                pos = pos + 1,
+                            ));
+                        }
+                        Err(AddTrackError::TooManyUserTracks(..)) => {
+                            user.respond(
+                                "The requester already has too many tracks in the queue, \
+                                 the song is still waiting to be approved.",
+                            );
+                        }
+                        Err(AddTrackError::Duplicate(..)) => {
+                            user.respond(
+                                "That song was requested too recently, \
+                                 the song is still waiting to be approved.",
+                            );
+                        }
+                        Err(AddTrackError::Error(e)) => return Err(e),
+                        Err(_) => {
+                            user.respond("That song can't be approved right now.");
+                        }
+                    }
+
+                    Ok(())
+                };
+
+                ctx.spawn_result("!song approve", future);
+            }
+            Some("deny") => {
+                ctx.check_moderator()?;
+
+                let selector = ctx_try!(ctx.next_str("<n|user> [reason]", "!song deny"));
+                let selector = parse_pending_selector(selector);
+
+                let reason = match ctx.rest().trim() {
+                    "" => None,
+                    reason => Some(reason.to_string()),
+                };
+
+                match player.deny(selector, reason) {
+                    Some(item) => ctx.respond(format!("Denied {}.", item.what())),
+                    None => ctx.respond("No such song is waiting to be approved."),
+                }
+            }
+            Some("ban") => {
+                ctx.check_moderator()?;
+
+                let kind =
+                    ctx_try!(ctx.next_parse("<track|artist|channel> <id> [reason]", "!song ban"));
+                let id =
+                    ctx_try!(ctx.next_str("<track|artist|channel> <id> [reason]", "!song ban"));
+
+                let id = match kind.parse_id(id) {
+                    Ok(id) => id,
+                    Err(e) => {
+                        ctx.respond(format!("Bad {}: {}", kind, e));
+                        return Ok(());
+                    }
+                };
+
+                let reason = match ctx.rest().trim() {
+                    "" => None,
+                    reason => Some(reason),
+                };
+
+                player
+                    .bans()
+                    .insert(kind, &id, reason, Some(ctx.user.name))?;
+                ctx.respond(format!("Banned {} `{}` from being requested.", kind, id));
+            }
+            Some("unban") => {
+                ctx.check_moderator()?;
+
+                let kind = ctx_try!(ctx.next_parse("<track|artist|channel> <id>", "!song unban"));
+                let id = ctx_try!(ctx.next_str("<track|artist|channel> <id>", "!song unban"));
+
+                let id = match kind.parse_id(id) {
+                    Ok(id) => id,
+                    Err(e) => {
+                        ctx.respond(format!("Bad {}: {}", kind, e));
+                        return Ok(());
+                    }
+                };
+
+                if player.bans().delete(kind, &id)? {
+                    ctx.respond(format!("Unbanned {} `{}`.", kind, id));
+                } else {
+                    ctx.respond(format!("No ban for {} `{}`.", kind, id));
+                }
+            }
             Some("close") => {
                 ctx.check_moderator()?;
 
@@ -454,6 +1284,61 @@ impl command::Handler for Handler {
                     ctx.respond("No song :(");
                 }
             },
+            Some("history") => {
+                let limit = ctx_try!(ctx.next_parse_optional::<usize>()).unwrap_or(3);
+                let limit = usize::min(limit, 10);
+
+                let entries = player.history(limit)?;
+
+                if entries.is_empty() {
+                    ctx.respond("No songs have been played yet.");
+                    return Ok(());
+                }
+
+                // NB: offsets are only relevant for songs played during the current stream.
+                let stream_started_at = self
+                    .stream_info
+                    .data
+                    .read()
+                    .stream
+                    .as_ref()
+                    .map(|s| s.started_at.naive_utc());
+
+                let now = Utc::now().naive_utc();
+                let mut lines = Vec::new();
+
+                for e in entries {
+                    let mut line = match e.artists.as_ref() {
+                        Some(artists) => format!("\"{}\" by {}", e.name, artists),
+                        None => format!("\"{}\"", e.name),
+                    };
+
+                    if let Some(user) = e.user.as_ref() {
+                        line.push_str(&format!(" ({})", user));
+                    }
+
+                    let offset = stream_started_at
+                        .and_then(|s| e.started_at.signed_duration_since(s).to_std().ok());
+
+                    match offset {
+                        Some(offset) => {
+                            line.push_str(&format!(" at {}", utils::digital_duration(&offset)));
+                        }
+                        None => {
+                            let ago = now
+                                .signed_duration_since(e.started_at)
+                                .to_std()
+                                .unwrap_or_default();
+
+                            line.push_str(&format!(" {} ago", utils::compact_duration(&ago)));
+                        }
+                    }
+
+                    lines.push(line);
+                }
+
+                ctx.respond(format!("Recently played: {}.", lines.join("; ")));
+            }
             Some("purge") => {
                 ctx.check_moderator()?;
                 player.purge()?;
@@ -499,10 +1384,30 @@ impl command::Handler for Handler {
                         }
                     }
                     None => {
-                        if your {
-                            ctx.respond("You don't have any songs in queue :(");
-                        } else {
-                            ctx.respond(format!("{} doesn't have any songs in queue :(", user));
+                        let pending = player.find_pending(|item| item.user.as_ref() == Some(&user));
+
+                        match (your, pending) {
+                            (true, Some((pos, item))) => {
+                                ctx.respond(format!(
+                                    "Your song {} is waiting to be approved (#{} in line)",
+                                    item.what(),
+                                    pos + 1
+                                ));
+                            }
+                            (false, Some((pos, item))) => {
+                                ctx.respond(format!(
+                                    "{}'s song {} is waiting to be approved (#{} in line)",
+                                    user,
+                                    item.what(),
+                                    pos + 1
+                                ));
+                            }
+                            (true, None) => {
+                                ctx.respond("You don't have any songs in queue :(");
+                            }
+                            (false, None) => {
+                                ctx.respond(format!("{} doesn't have any songs in queue :(", user));
+                            }
                         }
                     }
                 }
@@ -592,6 +1497,21 @@ impl command::Handler for Handler {
                 ctx.check_moderator()?;
                 player.skip()?;
             }
+            Some("voteskip") => {
+                self.handle_voteskip(&mut ctx, player)?;
+            }
+            Some("like") => {
+                self.handle_rate(&mut ctx, player, db::Rating::Like)?;
+            }
+            Some("dislike") => {
+                self.handle_rate(&mut ctx, player, db::Rating::Dislike)?;
+            }
+            Some("top") => {
+                self.handle_top(&mut ctx, player)?;
+            }
+            Some("save") => {
+                self.handle_save(&mut ctx, player)?;
+            }
             Some("request") => {
                 self.handle_request(&mut ctx, player)?;
             }
@@ -654,6 +1574,7 @@ impl module::Module for Module {
             sender,
             settings,
             injector,
+            twitch,
             ..
         }: module::HookContext<'_, '_>,
     ) -> Result<(), Error> {
@@ -665,6 +1586,7 @@ impl module::Module for Module {
         let chat_feedback = vars.var("chat-feedback", true)?;
         let subscriber_only = vars.var("subscriber-only", false)?;
         let request_reward = vars.var("request-reward", 0)?;
+        let promote_price = vars.var("promote-price", 0)?;
         futures.push(vars.run().boxed());
 
         let vars = settings.scoped("spotify");
@@ -677,13 +1599,45 @@ impl module::Module for Module {
         let youtube = Constraint::build(&mut vars, false, true)?;
         futures.push(vars.run().boxed());
 
+        let vars = settings.scoped("local");
+        let mut vars = vars.vars();
+        let local = Constraint::build(&mut vars, false, false)?;
+        futures.push(vars.run().boxed());
+
+        let vars = settings.scoped("voteskip");
+        let mut vars = vars.vars();
+        let voteskip = VoteSkip::build(&mut vars)?;
+        futures.push(vars.run().boxed());
+
+        let vars = settings.scoped("dislike-skip");
+        let mut vars = vars.vars();
+        let dislike_skip = DislikeSkip::build(&mut vars)?;
+        futures.push(vars.run().boxed());
+
+        let vars = settings.scoped("save");
+        let mut vars = vars.vars();
+        let save = Save::build(&mut vars)?;
+        futures.push(vars.run().boxed());
+
         let (mut player_stream, player) = injector.stream();
 
-        let new_feedback_loop = move |player: Option<&Player>| match player {
-            Some(player) => {
-                Some(feedback(player.clone(), sender.clone(), chat_feedback.clone()).boxed())
+        let new_feedback_loop = {
+            let voteskip = voteskip.state.clone();
+            let save = save.clone();
+
+            move |player: Option<&Player>| match player {
+                Some(player) => Some(
+                    feedback(
+                        player.clone(),
+                        sender.clone(),
+                        chat_feedback.clone(),
+                        voteskip.clone(),
+                        save.clone(),
+                    )
+                    .boxed(),
+                ),
+                None => None,
             }
-            None => None,
         };
 
         let mut feedback_loop = new_feedback_loop(player.as_ref());
@@ -701,9 +1655,15 @@ impl module::Module for Module {
                 player: player.clone(),
                 subscriber_only,
                 request_reward,
+                promote_price,
                 currency,
                 spotify,
                 youtube,
+                local,
+                voteskip,
+                dislike_skip,
+                save,
+                twitch: twitch.clone(),
             },
         );
 
@@ -735,6 +1695,7 @@ struct Constraint {
     max_duration: Arc<RwLock<Duration>>,
     min_currency: Arc<RwLock<i64>>,
     subscriber_only: Arc<RwLock<bool>>,
+    price: Arc<RwLock<u32>>,
 }
 
 impl Constraint {
@@ -747,16 +1708,108 @@ impl Constraint {
         let max_duration = vars.var("max-duration", Duration::seconds(60 * 10))?;
         let min_currency = vars.var("min-currency", 60)?;
         let subscriber_only = vars.var("subscriber-only", subscriber_only)?;
+        let price = vars.var("price", 0)?;
 
         Ok(Constraint {
             enabled,
             max_duration,
             min_currency,
             subscriber_only,
+            price,
+        })
+    }
+}
+
+/// Settings and state for vote skipping.
+#[derive(Debug, Clone)]
+struct VoteSkip {
+    enabled: Arc<RwLock<bool>>,
+    votes: Arc<RwLock<u32>>,
+    percentage: Arc<RwLock<u32>>,
+    requester_skip: Arc<RwLock<bool>>,
+    state: Arc<RwLock<VoteSkipState>>,
+}
+
+impl VoteSkip {
+    fn build(vars: &mut settings::Vars) -> Result<Self, Error> {
+        let enabled = vars.var("enabled", false)?;
+        let votes = vars.var("votes", 3)?;
+        let percentage = vars.var("percentage", 0)?;
+        let requester_skip = vars.var("requester-skip", false)?;
+
+        Ok(VoteSkip {
+            enabled,
+            votes,
+            percentage,
+            requester_skip,
+            state: Default::default(),
+        })
+    }
+}
+
+/// Settings for skipping songs which are disliked.
+#[derive(Debug, Clone)]
+struct DislikeSkip {
+    enabled: Arc<RwLock<bool>>,
+    votes: Arc<RwLock<u32>>,
+    percentage: Arc<RwLock<u32>>,
+}
+
+impl DislikeSkip {
+    fn build(vars: &mut settings::Vars) -> Result<Self, Error> {
+        let enabled = vars.var("enabled", false)?;
+        let votes = vars.var("votes", 3)?;
+        let percentage = vars.var("percentage", 75)?;
+
+        Ok(DislikeSkip {
+            enabled,
+            votes,
+            percentage,
         })
     }
 }
 
+/// Settings for saving songs to a Spotify playlist.
+#[derive(Debug, Clone)]
+struct Save {
+    playlist: Arc<RwLock<String>>,
+    auto: Arc<RwLock<bool>>,
+}
+
+impl Save {
+    fn build(vars: &mut settings::Vars) -> Result<Self, Error> {
+        let playlist = vars.var("playlist", String::new())?;
+        let auto = vars.var("auto", false)?;
+
+        Ok(Save { playlist, auto })
+    }
+}
+
+/// Votes registered to skip the current song.
+#[derive(Debug, Default)]
+struct VoteSkipState {
+    /// The track that is being voted on.
+    track_id: Option<TrackId>,
+    /// Users that have voted.
+    voters: HashSet<String>,
+}
+
+/// Parse a reference to a pending request, either a position in the pending list or a user.
+fn parse_pending_selector(s: &str) -> player::PendingSelector {
+    match str::parse::<usize>(s) {
+        Ok(n) => player::PendingSelector::Index(n.saturating_sub(1)),
+        Err(_) => player::PendingSelector::User(s.trim_start_matches('@').to_lowercase()),
+    }
+}
+
+/// Parse the id of a Spotify playlist from a URI or an URL.
+fn parse_spotify_playlist(s: &str) -> Result<String, Error> {
+    match track_id::PlaylistId::parse_with_urls(s.trim())? {
+        track_id::PlaylistId::SpotifyPlaylist(id) => Ok(id),
+        _ => failure::bail!("expected a Spotify playlist"),
+    }
+}
+
 /// Parse a queue position.
 fn parse_queue_position(user: &irc::User<'_>, n: &str) -> Option<usize> {
     match str::parse::<usize>(n) {
@@ -809,8 +1862,11 @@ async fn feedback(
     player: Player,
     sender: irc::Sender,
     chat_feedback: Arc<RwLock<bool>>,
+    voteskip: Arc<RwLock<VoteSkipState>>,
+    save: Save,
 ) -> Result<(), Error> {
     let mut configured_cooldown = Cooldown::from_duration(Duration::seconds(10));
+    let mut device_health = None;
     let mut rx = player.add_rx().compat();
 
     while let Some(e) = rx.next().await {
@@ -820,9 +1876,42 @@ async fn feedback(
             Event::Detached => {
                 sender.privmsg("Player is detached!");
             }
+            Event::DeviceHealth(health) => {
+                match health {
+                    player::DeviceHealth::Unavailable => {
+                        sender.privmsg(
+                            "Spotify device is not reachable, pausing playback until it is back.",
+                        );
+                    }
+                    player::DeviceHealth::Fallback { ref device } => {
+                        sender.privmsg(format!(
+                            "Spotify device is not reachable, switched to fallback device {}.",
+                            device
+                        ));
+                    }
+                    // NB: don't announce the device as being back unless it was missing.
+                    player::DeviceHealth::Available { ref device } => {
+                        if device_health.is_some() {
+                            sender
+                                .privmsg(format!("Spotify device {} is reachable again.", device));
+                        }
+                    }
+                }
+
+                device_health = match health {
+                    player::DeviceHealth::Available { .. } => None,
+                    health => Some(health),
+                };
+            }
             Event::Playing(feedback, item) => {
+                {
+                    let mut voteskip = voteskip.write();
+                    voteskip.track_id = None;
+                    voteskip.voters.clear();
+                }
+
                 if !feedback || !*chat_feedback.read() {
-                    return Ok(());
+                    continue;
                 }
 
                 let message = match item.user.as_ref() {
@@ -832,9 +1921,92 @@ async fn feedback(
 
                 sender.privmsg(message);
             }
+            Event::Finished(item) => {
+                // NB: only songs requested by someone in chat are saved automatically.
+                if !*save.auto.read() || item.user.is_none() {
+                    continue;
+                }
+
+                if let TrackId::YouTube(..) | TrackId::Local(..) = item.track_id {
+                    continue;
+                }
+
+                let playlist = save.playlist.read().clone();
+
+                if playlist.trim().is_empty() {
+                    continue;
+                }
+
+                let playlist = match parse_spotify_playlist(&playlist) {
+                    Ok(playlist) => playlist,
+                    Err(e) => {
+                        log_err!(e, "bad playlist to save songs to: {}", playlist);
+                        continue;
+                    }
+                };
+
+                let player = player.clone();
+
+                // NB: saving talks to Spotify, which shouldn't hold up other player events.
+                let future = async move {
+                    if let Err(e) = player.save_track(playlist, &item.track_id).await {
+                        log_err!(e, "failed to save song to playlist: {}", item.track_id);
+                    }
+                };
+
+                tokio::spawn(Compat::new(Box::pin(future.unit_error())));
+            }
+            Event::Unplayable(item, reason) => {
+                sender.privmsg(format!("Skipping {}, since {}.", item.what(), reason));
+            }
+            Event::Expired(item, action, absent) => {
+                let user = match item.user.as_ref() {
+                    Some(user) => user,
+                    None => continue,
+                };
+
+                let what = match action {
+                    player::ExpiryAction::Skip => "Skipped",
+                    player::ExpiryAction::MoveDown => "Moved down",
+                };
+
+                sender.privmsg(format!(
+                    "{} {}, requested by {}, since they haven't been in chat for {}.",
+                    what,
+                    item.what(),
+                    user,
+                    utils::compact_duration(&absent),
+                ));
+            }
+            Event::Approved(item) => {
+                if let Some(user) = item.user.as_ref() {
+                    sender.privmsg(format!(
+                        "{}, your request for {} was approved and added to the queue!",
+                        user,
+                        item.what()
+                    ));
+                }
+            }
+            Event::Denied(item, reason) => {
+                if let Some(user) = item.user.as_ref() {
+                    match reason {
+                        Some(reason) => sender.privmsg(format!(
+                            "{}, your request for {} was denied: {}",
+                            user,
+                            item.what(),
+                            reason
+                        )),
+                        None => sender.privmsg(format!(
+                            "{}, your request for {} was denied, sorry :(",
+                            user,
+                            item.what()
+                        )),
+                    }
+                }
+            }
             Event::Pausing => {
                 if !*chat_feedback.read() {
-                    return Ok(());
+                    continue;
                 }
 
                 sender.privmsg("Pausing playback.");
@@ -408,7 +408,7 @@ pub struct Module;
 
 impl super::Module for Module {
     fn ty(&self) -> &'static str {
-        "8ball"
+        "speedrun"
     }
 
     /// Set up command handlers for this module.
@@ -1,20 +1,22 @@
 use crate::{
     api, bus, config,
-    currency::Currency,
+    currency::{BalanceTransferError, Currency},
     db,
+    last_seen::LastSeen,
     prelude::*,
     settings,
     song_file::{SongFile, SongFileBuilder},
     spotify_id::SpotifyId,
     template::Template,
     timer,
-    track_id::TrackId,
+    track_id::{PlaylistId, TrackId},
     utils::{self, PtDuration},
 };
 
 use chrono::{DateTime, Utc};
 use failure::{bail, format_err, Error};
-use parking_lot::RwLock;
+use hashbrown::{HashMap, HashSet};
+use parking_lot::{Mutex, RwLock};
 use std::{
     collections::VecDeque,
     sync::Arc,
@@ -23,9 +25,28 @@ use std::{
 use tokio_bus::{Bus, BusReader};
 use tokio_threadpool::ThreadPool;
 
+mod auto_dj;
 mod connect;
+mod library;
+mod local;
+mod policy;
+mod simulated;
 mod youtube;
 
+pub use self::connect::DeviceHealth;
+pub use self::library::LocalTrack;
+
+/// How far the elapsed time reported by the YouTube player may drift before we resynchronize.
+const YOUTUBE_SYNC_THRESHOLD: Duration = Duration::from_secs(2);
+
+/// The maximum number of tracks that are fetched from a single playlist.
+const MAX_PLAYLIST_TRACKS: usize = 1000;
+
+/// How long to wait before asking the auto-DJ again after it didn't pick anything.
+const AUTO_DJ_BACKOFF: Duration = Duration::from_secs(30);
+/// The longest to wait before asking the auto-DJ again.
+const AUTO_DJ_MAX_BACKOFF: Duration = Duration::from_secs(60 * 10);
+
 static DEFAULT_CURRENT_SONG_TEMPLATE: &'static str = "Song: {{name}}{{#if artists}} by {{artists}}{{/if}}{{#if paused}} (Paused){{/if}} ({{duration}})\n{{#if user~}}Request by: @{{user~}}{{/if}}";
 static DEFAULT_CURRENT_SONG_STOPPED_TEMPLATE: &'static str = "Not Playing";
 
@@ -34,6 +55,12 @@ static DEFAULT_CURRENT_SONG_STOPPED_TEMPLATE: &'static str = "Not Playing";
 pub enum IntegrationEvent {
     /// Indicate that the current device changed.
     DeviceChanged,
+    /// Indicate that the health of the device changed.
+    DeviceHealth(DeviceHealth),
+    /// Indicate if any overlay is connected to the YouTube player.
+    YouTubeConnected(bool),
+    /// A report from an overlay connected to the YouTube player.
+    YouTubeReport(bus::YouTubeReport),
 }
 
 /// The source of action.
@@ -70,6 +97,55 @@ pub struct Config {
     sync_player_interval: utils::Duration,
 }
 
+/// How songs are ordered when they are added to the queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum QueueMode {
+    /// Songs are played in the order they were requested.
+    #[serde(rename = "fifo")]
+    Fifo,
+    /// Songs are interleaved so that every requester gets a turn.
+    #[serde(rename = "fair")]
+    Fair,
+}
+
+impl Default for QueueMode {
+    fn default() -> Self {
+        QueueMode::Fifo
+    }
+}
+
+/// Where songs are taken from when the queue is empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum FallbackMode {
+    /// Songs are taken from `player/fallback-uri`, or the library of the user.
+    #[serde(rename = "playlist")]
+    Playlist,
+    /// Songs are picked based on what has been requested and liked in chat.
+    #[serde(rename = "auto-dj")]
+    AutoDj,
+}
+
+impl Default for FallbackMode {
+    fn default() -> Self {
+        FallbackMode::Playlist
+    }
+}
+
+/// The user requesting a track.
+#[derive(Debug, Clone)]
+pub struct Requester {
+    /// Name of the user.
+    pub name: String,
+    /// If the user is a moderator.
+    pub is_moderator: bool,
+    /// If the user is a subscriber.
+    pub is_subscriber: bool,
+    /// If the user is a VIP.
+    pub is_vip: bool,
+    /// If bans apply even though the user is a moderator, like when queueing a whole playlist.
+    pub enforce_bans: bool,
+}
+
 /// Information on a single track.
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 #[serde(tag = "type")]
@@ -78,6 +154,8 @@ pub enum Track {
     Spotify { track: api::spotify::FullTrack },
     #[serde(rename = "youtube")]
     YouTube { video: api::youtube::Video },
+    #[serde(rename = "local")]
+    Local { track: LocalTrack },
 }
 
 impl Track {
@@ -88,6 +166,7 @@ impl Track {
             Track::YouTube { ref video } => {
                 video.snippet.as_ref().and_then(|s| s.channel_title.clone())
             }
+            Track::Local { ref track } => track.artist.clone(),
         }
     }
 
@@ -101,6 +180,24 @@ impl Track {
                 .map(|s| s.title.as_str())
                 .unwrap_or("no name")
                 .to_string(),
+            Track::Local { ref track } => track.title.to_string(),
+        }
+    }
+
+    /// Get the URL to the album art of the track, or the thumbnail of the video.
+    pub fn album_art(&self) -> Option<String> {
+        match *self {
+            // NB: images are ordered widest first.
+            Track::Spotify { ref track } => track.album.images.first().map(|i| i.url.clone()),
+            Track::YouTube { ref video } => {
+                let thumbnails = &video.snippet.as_ref()?.thumbnails;
+
+                ["maxres", "standard", "high", "medium", "default"]
+                    .iter()
+                    .find_map(|k| thumbnails.get(*k))
+                    .map(|t| t.url.clone())
+            }
+            Track::Local { .. } => None,
         }
     }
 
@@ -110,6 +207,7 @@ impl Track {
         let json = match *self {
             Track::Spotify { ref track } => serde_json::to_value(&track)?,
             Track::YouTube { ref video } => serde_json::to_value(&video)?,
+            Track::Local { ref track } => serde_json::to_value(&track)?,
         };
 
         Ok(json)
@@ -144,6 +242,10 @@ impl Item {
                 },
                 None => String::from("*Some YouTube Video*"),
             },
+            Track::Local { ref track } => match track.artist.as_ref() {
+                Some(artist) => format!("\"{}\" by {}", track.title, artist),
+                None => format!("\"{}\"", track.title),
+            },
         }
     }
 }
@@ -209,25 +311,40 @@ pub fn run(
     config: Arc<config::Config>,
     global_bus: Arc<bus::Bus<bus::Global>>,
     youtube_bus: Arc<bus::Bus<bus::YouTube>>,
+    local_bus: Arc<bus::Bus<bus::Local>>,
     settings: settings::Settings,
     themes: db::Themes,
+    history: db::History,
+    bans: db::SongBans,
+    bad_words: db::Words,
+    stats: db::SongStats,
+    last_seen: LastSeen,
 ) -> Result<(Player, impl Future<Output = Result<(), Error>>), Error> {
-    let settings = settings.scoped("player");
-
     let mut futures = utils::Futures::default();
 
+    let mut vars = settings.scoped("song/policy").vars();
+    let policy = policy::Policy::build(&mut vars, bad_words)?;
+    futures.push(vars.run().boxed());
+
+    let mut vars = settings.scoped("song").vars();
+    let approval_required = vars.var("approval-required", false)?;
+    futures.push(vars.run().boxed());
+
+    let settings = settings.scoped("player");
+
     let (connect_stream, connect_player, device) =
         connect::setup(&mut futures, spotify.clone(), settings.scoped("spotify"))?;
-    let youtube_player = youtube::setup(
+    let (youtube_stream, youtube_player) = youtube::setup(
         &mut futures,
         youtube_bus.clone(),
         settings.scoped("youtube"),
     )?;
+    let local_player = local::setup(&mut futures, local_bus.clone(), settings.scoped("local"))?;
+    let library = library::setup(&mut futures, settings.scoped("local"))?;
 
     let bus = EventBus {
         bus: Arc::new(RwLock::new(Bus::new(1024))),
     };
-    let queue = Queue::new(db.clone());
 
     let song = Arc::new(RwLock::new(None));
     let closed = Arc::new(RwLock::new(None));
@@ -302,17 +419,44 @@ pub fn run(
         },
     )?;
 
+    let queue_mode = vars.var("queue-mode", QueueMode::default())?;
+    let subscriber_weight = vars.var("fair-queue/subscriber-weight", 1)?;
+    let vip_weight = vars.var("fair-queue/vip-weight", 1)?;
+    let quotas = Quotas::build(&mut vars)?;
+    let expiry = Expiry::build(&mut vars)?;
+    let fallback_mode = vars.var("fallback-mode", FallbackMode::default())?;
+
+    let auto_dj = auto_dj::AutoDj {
+        spotify: spotify.clone(),
+        connect_player: connect_player.clone(),
+        youtube: youtube.clone(),
+        library: library.clone(),
+        stats: stats.clone(),
+        history: history.clone(),
+        bans: bans.clone(),
+        duplicate_duration: duplicate_duration.clone(),
+        recommendations: vars.var("auto-dj/recommendations", 5)?,
+    };
+
     futures.push(vars.run().boxed());
 
+    let queue = Queue::new(db.clone(), queue_mode);
+    let charges = Charges::new(queue.thread_pool.clone());
+
     let parent_player = Player {
         inner: Arc::new(PlayerInner {
             device: device.clone(),
             queue: queue.clone(),
             connect_player: connect_player.clone(),
             youtube_player: youtube_player.clone(),
+            local_player: local_player.clone(),
+            library: library.clone(),
             max_queue_length,
             max_songs_per_user,
             duplicate_duration,
+            subscriber_weight,
+            vip_weight,
+            quotas,
             spotify: spotify.clone(),
             youtube: youtube.clone(),
             commands_tx,
@@ -320,6 +464,13 @@ pub fn run(
             song: song.clone(),
             themes: themes.clone(),
             closed: closed.clone(),
+            history: history.clone(),
+            charges: charges.clone(),
+            bans,
+            policy,
+            stats,
+            approval_required,
+            pending: Default::default(),
         }),
     };
 
@@ -331,18 +482,25 @@ pub fn run(
         log::trace!("Waiting for token to become ready");
 
         // NB: since we do some work when we initialize the player, wait until
-        // Spotify and YouTube are at least initialy authenticated.
-        future::try_join(
-            spotify.token.wait_until_ready(),
-            youtube.token.wait_until_ready(),
-        )
-        .await?;
+        // Spotify and YouTube are at least initialy authenticated. The simulated backend works
+        // without Spotify.
+        if connect_player.is_simulated() {
+            youtube.token.wait_until_ready().await?;
+        } else {
+            future::try_join(
+                spotify.token.wait_until_ready(),
+                youtube.token.wait_until_ready(),
+            )
+            .await?;
+        }
 
         // Add tracks from database.
         for song in db.list()? {
             let item = convert_item(
                 spotify.clone(),
                 youtube.clone(),
+                library.clone(),
+                connect_player.is_simulated(),
                 song.user.clone(),
                 song.track_id,
                 None,
@@ -357,14 +515,19 @@ pub fn run(
             sidelined: Default::default(),
             fallback_items: Default::default(),
             fallback_queue: Default::default(),
+            auto_dj_queue: Default::default(),
         };
 
+        let (auto_dj_tx, auto_dj_rx) = mpsc::unbounded();
+
         let future = PlaybackFuture {
             config: config.clone(),
             spotify: spotify.clone(),
             connect_stream,
             connect_player: connect_player.clone(),
             youtube_player,
+            youtube_stream,
+            local_player,
             commands,
             bus,
             mixer,
@@ -379,9 +542,29 @@ pub fn run(
             song_update_interval_stream,
             global_bus,
             timeout: None,
+            history,
+            charges,
+            device_paused: false,
+            overlay_paused: false,
+            expiry,
+            last_seen,
+            moved_down: Default::default(),
+            fallback_mode,
+            auto_dj,
+            auto_dj_tx,
+            auto_dj_rx,
+            auto_dj_picking: false,
+            auto_dj_backoff: AUTO_DJ_BACKOFF,
+            auto_dj_retry: None,
+        };
+
+        // NB: the simulated backend has no remote playback to synchronize with.
+        let p = match connect_player.is_simulated() {
+            true => None,
+            false => spotify.me_player().await?,
         };
 
-        let p = match spotify.me_player().await? {
+        let p = match p {
             Some(p) => p,
             None => return future.run(settings).await,
         };
@@ -421,6 +604,19 @@ pub enum Event {
     NotConfigured,
     /// Player is detached.
     Detached,
+    /// The given song was played until the end.
+    Finished(Arc<Item>),
+    /// The health of the Spotify device changed.
+    DeviceHealth(DeviceHealth),
+    /// The given song can't be played and was skipped, with the reason why.
+    Unplayable(Arc<Item>, &'static str),
+    /// A request was skipped or moved down since the requester has been absent from chat for
+    /// the given duration.
+    Expired(Arc<Item>, ExpiryAction, Duration),
+    /// A pending request was approved and added to the queue.
+    Approved(Arc<Item>),
+    /// A pending request was denied, with an optional reason.
+    Denied(Arc<Item>, Option<String>),
 }
 
 /// Information on current song.
@@ -431,6 +627,8 @@ pub struct Song {
     elapsed: Duration,
     /// When the current song started playing.
     started_at: Option<Instant>,
+    /// Wall-clock time at which the song was first played.
+    played_at: Option<DateTime<Utc>>,
 }
 
 impl Song {
@@ -440,9 +638,15 @@ impl Song {
             item,
             elapsed,
             started_at: None,
+            played_at: None,
         }
     }
 
+    /// Wall-clock time at which the song was first played, if it has been played.
+    pub fn played_at(&self) -> Option<DateTime<Utc>> {
+        self.played_at
+    }
+
     /// Test if the two songs reference roughly the same song.
     pub fn is_same(&self, song: &Song) -> bool {
         if self.item.track_id != song.item.track_id {
@@ -562,6 +766,7 @@ impl Song {
         match self.item.track_id {
             TrackId::Spotify(..) => PlayerKind::Spotify,
             TrackId::YouTube(..) => PlayerKind::YouTube,
+            TrackId::Local(..) => PlayerKind::Local,
         }
     }
 
@@ -571,6 +776,10 @@ impl Song {
         let duration = self.take_started_at();
         self.elapsed += duration;
         self.started_at = Some(Instant::now());
+
+        if self.played_at.is_none() {
+            self.played_at = Some(Utc::now());
+        }
     }
 
     /// Update the elapsed time based on when this song was started.
@@ -579,6 +788,15 @@ impl Song {
         self.elapsed += duration;
     }
 
+    /// Set the elapsed time of the song, as reported by the player.
+    pub fn sync_elapsed(&mut self, elapsed: Duration) {
+        self.elapsed = elapsed;
+
+        if self.started_at.is_some() {
+            self.started_at = Some(Instant::now());
+        }
+    }
+
     /// Take the current started_at as a duration and leave it as None.
     fn take_started_at(&mut self) -> Duration {
         let started_at = match self.started_at.take() {
@@ -613,9 +831,18 @@ pub struct PlayerInner {
     queue: Queue,
     connect_player: self::connect::ConnectPlayer,
     youtube_player: self::youtube::YouTubePlayer,
+    local_player: self::local::LocalPlayer,
+    /// Library of local audio files.
+    library: self::library::Library,
     max_queue_length: Arc<RwLock<u32>>,
     max_songs_per_user: Arc<RwLock<u32>>,
     duplicate_duration: Arc<RwLock<utils::Duration>>,
+    /// Scheduling weight of subscribers in the fair queue.
+    subscriber_weight: Arc<RwLock<u32>>,
+    /// Scheduling weight of VIPs in the fair queue.
+    vip_weight: Arc<RwLock<u32>>,
+    /// Request quotas per role.
+    quotas: Quotas,
     spotify: Arc<api::Spotify>,
     youtube: Arc<api::YouTube>,
     commands_tx: mpsc::UnboundedSender<Command>,
@@ -626,6 +853,20 @@ pub struct PlayerInner {
     themes: db::Themes,
     /// Player is closed for more requests.
     closed: Arc<RwLock<Option<Option<Arc<String>>>>>,
+    /// History of played songs.
+    history: db::History,
+    /// Charges for songs in the queue.
+    charges: Charges,
+    /// Tracks, artists, and channels that are banned from being requested.
+    bans: db::SongBans,
+    /// Content policy for requested tracks.
+    policy: policy::Policy,
+    /// Ratings and statistics of songs.
+    stats: db::SongStats,
+    /// Requests need to be approved by a moderator before they are queued.
+    approval_required: Arc<RwLock<bool>>,
+    /// Requests which are waiting to be approved.
+    pending: Arc<RwLock<Vec<Pending>>>,
 }
 
 /// All parts of a Player that can be shared between threads.
@@ -646,6 +887,26 @@ impl Player {
         self.send(Command::Sync { song })
     }
 
+    /// Indicate that an overlay connected to the YouTube player.
+    pub fn youtube_connected(&self) {
+        self.inner.youtube_player.connected();
+    }
+
+    /// Indicate that an overlay disconnected from the YouTube player.
+    pub fn youtube_disconnected(&self) {
+        self.inner.youtube_player.disconnected();
+    }
+
+    /// Pass on a report from an overlay connected to the YouTube player.
+    pub fn youtube_report(&self, report: bus::YouTubeReport) {
+        self.inner.youtube_player.report(report);
+    }
+
+    /// Get the path to the file of a track in the local library.
+    pub fn local_path(&self, id: &str) -> Option<std::path::PathBuf> {
+        self.inner.library.path(id)
+    }
+
     /// Get the current device.
     pub fn current_device(&self) -> Option<api::spotify::Device> {
         self.inner.device.current_device()
@@ -688,6 +949,121 @@ impl Player {
             .collect()
     }
 
+    /// Get the songs in the queue, not including the current song.
+    pub fn queue(&self) -> Vec<Arc<Item>> {
+        self.inner.queue.queue.read().iter().cloned().collect()
+    }
+
+    /// Get the most recently played songs, with the most recent first.
+    pub fn history(&self, limit: usize) -> Result<Vec<db::HistoryEntry>, Error> {
+        self.inner.history.list(0, limit as i64)
+    }
+
+    /// Access the tracks, artists, and channels that are banned from being requested.
+    pub fn bans(&self) -> &db::SongBans {
+        &self.inner.bans
+    }
+
+    /// Access ratings and statistics of songs.
+    pub fn stats(&self) -> &db::SongStats {
+        &self.inner.stats
+    }
+
+    /// Save the given track to the end of the given Spotify playlist.
+    pub async fn save_track(&self, playlist: String, track_id: &TrackId) -> Result<(), Error> {
+        let id = match *track_id {
+            TrackId::Spotify(ref id) => id,
+            TrackId::YouTube(..) | TrackId::Local(..) => {
+                bail!("only Spotify tracks can be saved to a playlist")
+            }
+        };
+
+        let uri = format!("spotify:track:{}", id.to_base62());
+        self.inner
+            .spotify
+            .playlist_add_tracks(playlist, vec![uri])
+            .await
+    }
+
+    /// Get the number of songs that can be added before the queue is full.
+    pub fn remaining_capacity(&self) -> usize {
+        let max_queue_length = *self.inner.max_queue_length.read() as usize;
+        max_queue_length.saturating_sub(self.inner.queue.queue.read().len())
+    }
+
+    /// Fetch the tracks in the given playlist or album, in playlist order.
+    pub async fn playlist_tracks(&self, playlist: PlaylistId) -> Result<Vec<TrackId>, Error> {
+        let mut out = Vec::new();
+
+        match playlist {
+            PlaylistId::SpotifyPlaylist(id) => {
+                let mut pages = self.inner.spotify.playlist_tracks_stream(id);
+
+                while let Some(page) = pages.try_next().await? {
+                    // NB: local files in the playlist don't have an id, and can't be played.
+                    for id in page.into_iter().flat_map(|t| t.id()) {
+                        out.push(TrackId::Spotify(
+                            SpotifyId::from_base62(&id)
+                                .map_err(|_| format_err!("bad spotify id: {}", id))?,
+                        ));
+                    }
+
+                    if out.len() >= MAX_PLAYLIST_TRACKS {
+                        break;
+                    }
+                }
+            }
+            PlaylistId::SpotifyAlbum(id) => {
+                let spotify = &self.inner.spotify;
+                let album = spotify.album(id).await?;
+                let mut pages = spotify.page_as_stream(album.tracks);
+
+                while let Some(page) = pages.try_next().await? {
+                    for t in page {
+                        out.push(TrackId::Spotify(
+                            SpotifyId::from_base62(&t.id)
+                                .map_err(|_| format_err!("bad spotify id: {}", t.id))?,
+                        ));
+                    }
+
+                    if out.len() >= MAX_PLAYLIST_TRACKS {
+                        break;
+                    }
+                }
+            }
+            PlaylistId::YouTube(id) => {
+                let mut page_token = None;
+
+                loop {
+                    let page = self
+                        .inner
+                        .youtube
+                        .playlist_items(id.clone(), page_token)
+                        .await?;
+
+                    for item in page.items {
+                        if let Some(details) = item.content_details {
+                            out.push(TrackId::YouTube(details.video_id));
+                        }
+                    }
+
+                    page_token = match page.next_page_token {
+                        Some(next) if out.len() < MAX_PLAYLIST_TRACKS => Some(next),
+                        _ => break,
+                    };
+                }
+            }
+        }
+
+        out.truncate(MAX_PLAYLIST_TRACKS);
+        Ok(out)
+    }
+
+    /// Get the item at the given position in the queue.
+    pub fn get(&self, n: usize) -> Option<Arc<Item>> {
+        self.inner.queue.queue.read().get(n).cloned()
+    }
+
     /// Promote the given song to the head of the queue.
     pub fn promote_song(&self, user: &str, n: usize) -> Option<Arc<Item>> {
         let promoted = self.inner.queue.promote_song(user, n);
@@ -699,6 +1075,41 @@ impl Player {
         promoted
     }
 
+    /// Move the song at position `from` in the queue to position `to`.
+    pub fn move_song(&self, from: usize, to: usize) -> Option<Arc<Item>> {
+        let moved = self.inner.queue.move_item(from, to);
+
+        if moved.is_some() {
+            self.modified();
+        }
+
+        moved
+    }
+
+    /// Swap the songs at the given positions in the queue.
+    pub fn swap_songs(&self, a: usize, b: usize) -> bool {
+        let swapped = self.inner.queue.swap(a, b);
+
+        if swapped {
+            self.modified();
+        }
+
+        swapped
+    }
+
+    /// Shuffle the queue.
+    pub fn shuffle(&self) {
+        self.inner.queue.shuffle();
+        self.modified();
+    }
+
+    /// Reorder the queue to match the given order of track ids.
+    pub fn reorder(&self, order: &[TrackId]) -> Result<(), Error> {
+        self.inner.queue.reorder(order)?;
+        self.modified();
+        Ok(())
+    }
+
     /// Toggle playback.
     pub fn toggle(&self) -> Result<(), Error> {
         self.send(Command::Toggle(Source::Manual))
@@ -743,6 +1154,7 @@ impl Player {
                 Ok(volume) => return Ok(Some(volume)),
             },
             TrackId::YouTube(..) => Ok(Some(self.inner.youtube_player.volume(modify)?)),
+            TrackId::Local(..) => Ok(Some(self.inner.local_player.volume(modify)?)),
         }
     }
 
@@ -758,6 +1170,7 @@ impl Player {
         match track_id {
             TrackId::Spotify(..) => Some(self.inner.connect_player.current_volume()),
             TrackId::YouTube(..) => Some(self.inner.youtube_player.current_volume()),
+            TrackId::Local(..) => Some(self.inner.local_player.current_volume()),
         }
     }
 
@@ -766,13 +1179,21 @@ impl Player {
         *self.inner.closed.write() = Some(reason.map(Arc::new));
     }
 
+    /// Test if the player is closed from further requests.
+    pub fn is_closed(&self) -> bool {
+        self.inner.closed.read().is_some()
+    }
+
     /// Open the player.
     pub fn open(&self) {
         *self.inner.closed.write() = None;
     }
 
     /// Search for a track.
-    pub async fn search_track(&self, q: String) -> Result<Option<TrackId>, Error> {
+    ///
+    /// `local` indicates if the local library should be searched before Spotify, which is only
+    /// the case if local requests are enabled and permitted for the user.
+    pub async fn search_track(&self, q: String, local: bool) -> Result<Option<TrackId>, Error> {
         if q.starts_with("youtube:") {
             let q = q.trim_start_matches("youtube:").to_string();
             let results = self.inner.youtube.search(q).await?;
@@ -786,6 +1207,18 @@ impl Player {
             return Ok(result.next().map(TrackId::YouTube));
         }
 
+        if q.starts_with("local:") {
+            let q = q.trim_start_matches("local:");
+            let track = self.inner.library.search(q);
+            return Ok(track.map(|t| TrackId::Local(t.id)));
+        }
+
+        if local {
+            if let Some(track) = self.inner.library.search(&q) {
+                return Ok(Some(TrackId::Local(track.id)));
+            }
+        }
+
         let q = if q.starts_with("spotify:") {
             q.trim_start_matches("spotify:").to_string()
         } else {
@@ -815,6 +1248,8 @@ impl Player {
         let item = convert_item(
             self.inner.spotify.clone(),
             self.inner.youtube.clone(),
+            self.inner.library.clone(),
+            self.inner.connect_player.is_simulated(),
             None,
             theme.track_id.clone(),
             duration,
@@ -840,62 +1275,27 @@ impl Player {
         &self,
         currency: Option<Currency>,
         channel: String,
-        user: String,
+        requester: Requester,
         track_id: TrackId,
-        is_moderator: bool,
         max_duration: Option<utils::Duration>,
         min_currency: Option<i64>,
-    ) -> Result<(usize, Arc<Item>), AddTrackError> {
-        let (user_count, len) = {
-            let queue_inner = self.inner.queue.queue.read();
-            let len = queue_inner.len();
-
-            if !is_moderator {
-                if let Some(reason) = self.inner.closed.read().as_ref() {
-                    return Err(AddTrackError::PlayerClosed(reason.clone()));
-                }
-
-                let max_queue_length = *self.inner.max_queue_length.read();
-
-                // NB: moderator is allowed to violate max queue length.
-                if len >= max_queue_length as usize {
-                    return Err(AddTrackError::QueueFull);
-                }
-
-                let duplicate_duration = self.inner.duplicate_duration.read().clone();
-
-                if !duplicate_duration.is_empty() {
-                    if let Some(last) = self
-                        .inner
-                        .queue
-                        .last_song_within(&track_id, duplicate_duration.clone())
-                        .map_err(AddTrackError::Error)?
-                    {
-                        let added_at = DateTime::from_utc(last.added_at, Utc);
-
-                        return Err(AddTrackError::Duplicate(
-                            added_at,
-                            last.user,
-                            duplicate_duration.as_std(),
-                        ));
-                    }
-                }
-            }
-
-            let mut user_count = 0;
-
-            for (index, i) in queue_inner.iter().enumerate() {
-                if i.track_id == track_id {
-                    return Err(AddTrackError::QueueContainsTrack(index));
-                }
+        price: Option<i64>,
+    ) -> Result<(Added, Arc<Item>), AddTrackError> {
+        let Requester {
+            name: user,
+            is_moderator,
+            is_subscriber,
+            is_vip,
+            enforce_bans,
+        } = requester;
 
-                if i.user.as_ref().map(|u| *u == user).unwrap_or_default() {
-                    user_count += 1;
-                }
+        if !is_moderator {
+            if let Some(reason) = self.inner.closed.read().as_ref() {
+                return Err(AddTrackError::PlayerClosed(reason.clone()));
             }
+        }
 
-            (user_count, len)
-        };
+        self.check_queue(&track_id, &user, is_moderator)?;
 
         if !is_moderator {
             if let Some(min_currency) = min_currency {
@@ -905,7 +1305,7 @@ impl Player {
                 };
 
                 let balance = currency
-                    .balance_of(channel, user.clone())
+                    .balance_of(channel.clone(), user.clone())
                     .await
                     .map_err(AddTrackError::Error)?
                     .unwrap_or_default();
@@ -919,17 +1319,51 @@ impl Player {
             }
         }
 
-        let max_songs_per_user = *self.inner.max_songs_per_user.read();
+        let (max_requests, max_queued) = self.inner.quotas.limits(is_subscriber, is_vip);
 
-        // NB: moderator is allowed to add more songs.
-        if !is_moderator && user_count >= max_songs_per_user {
-            return Err(AddTrackError::TooManyUserTracks(max_songs_per_user));
+        if !is_moderator && max_requests > 0 {
+            let window = self.inner.quotas.window.read().clone();
+
+            let requests = self
+                .inner
+                .queue
+                .user_requests_within(&user, window.clone())
+                .map_err(AddTrackError::Error)?;
+
+            if requests.len() >= max_requests as usize {
+                let oldest = requests[requests.len() - max_requests as usize];
+                let expires = DateTime::<Utc>::from_utc(oldest, Utc) + window.as_chrono();
+
+                let wait = expires
+                    .signed_duration_since(Utc::now())
+                    .to_std()
+                    .unwrap_or_default();
+
+                return Err(AddTrackError::RequestQuotaExceeded {
+                    limit: max_requests,
+                    window,
+                    wait,
+                });
+            }
+        }
+
+        if !is_moderator || enforce_bans {
+            let track_id = track_id.to_string();
+
+            if let Some(ban) = self.inner.bans.get(db::BanKind::Track, &track_id) {
+                return Err(AddTrackError::Banned(
+                    db::BanKind::Track,
+                    ban.reason.clone(),
+                ));
+            }
         }
 
         let mut item = convert_item(
             self.inner.spotify.clone(),
             self.inner.youtube.clone(),
-            Some(user),
+            self.inner.library.clone(),
+            self.inner.connect_player.is_simulated(),
+            Some(user.clone()),
             track_id,
             None,
         )
@@ -944,94 +1378,339 @@ impl Player {
             }
         }
 
-        let item = Arc::new(item);
-
-        self.inner
-            .queue
-            .push_back(item.clone())
-            .await
-            .map_err(|e| AddTrackError::Error(e.into()))?;
+        if !is_moderator || enforce_bans {
+            if let Some((kind, ban)) = find_ban(&self.inner.bans, &item.track) {
+                return Err(AddTrackError::Banned(kind, ban.reason.clone()));
+            }
+        }
 
-        self.inner
-            .commands_tx
-            .unbounded_send(Command::Modified(Source::Manual))
-            .map_err(|e| AddTrackError::Error(e.into()))?;
+        if let Some(violation) = self.inner.policy.check(&item.track) {
+            return Err(AddTrackError::Policy(violation));
+        }
 
-        Ok((len, item))
-    }
+        if !is_moderator && !max_queued.is_empty() {
+            let current = self.inner.song.read().as_ref().map(|s| s.remaining());
 
-    /// Remove the first track in the queue.
-    pub fn remove_first(&self) -> Result<Option<Arc<Item>>, Error> {
-        Ok(None)
-    }
+            let queue = self
+                .inner
+                .queue
+                .queue
+                .read()
+                .iter()
+                .map(|i| (i.user.as_ref() == Some(&user), i.duration))
+                .collect::<Vec<_>>();
 
-    pub fn purge(&self) -> Result<Vec<Arc<Item>>, Error> {
-        let purged = self.inner.queue.purge()?;
+            let limit = max_queued.as_std();
 
-        if !purged.is_empty() {
-            self.modified();
+            if let Some(wait) =
+                duration_quota_wait(current.unwrap_or_default(), queue, item.duration, limit)
+            {
+                return Err(AddTrackError::DurationQuotaExceeded {
+                    limit: max_queued,
+                    wait,
+                });
+            }
         }
 
-        Ok(purged)
-    }
+        let item = Arc::new(item);
 
-    /// Remove the item at the given position.
-    pub fn remove_at(&self, n: usize) -> Result<Option<Arc<Item>>, Error> {
-        let removed = self.inner.queue.remove_at(n)?;
+        // NB: moderators don't have to pay for requests.
+        if let Some(price) = price.filter(|p| *p > 0 && !is_moderator) {
+            let currency = match currency {
+                Some(currency) => currency,
+                None => return Err(AddTrackError::NoCurrency),
+            };
 
-        if removed.is_some() {
-            self.modified();
+            match currency
+                .balance_spend(channel.clone(), user.clone(), price)
+                .await
+            {
+                Ok(()) => (),
+                Err(BalanceTransferError::NoBalance) => {
+                    let balance = currency
+                        .balance_of(channel, user)
+                        .await
+                        .map_err(AddTrackError::Error)?
+                        .unwrap_or_default();
+
+                    return Err(AddTrackError::NotEnoughCurrency {
+                        balance,
+                        required: price,
+                    });
+                }
+                Err(BalanceTransferError::Other(e)) => return Err(AddTrackError::Error(e)),
+            }
+
+            self.inner.charges.insert(
+                &item,
+                Charge {
+                    currency,
+                    channel,
+                    user,
+                    amount: price,
+                },
+            );
         }
 
-        Ok(removed)
-    }
+        let mut weight = 1;
 
-    /// Remove the first track in the queue.
-    pub fn remove_last(&self) -> Result<Option<Arc<Item>>, Error> {
-        let removed = self.inner.queue.remove_last()?;
+        if is_subscriber {
+            weight = u32::max(weight, *self.inner.subscriber_weight.read());
+        }
 
-        if removed.is_some() {
-            self.modified();
+        if is_vip {
+            weight = u32::max(weight, *self.inner.vip_weight.read());
         }
 
-        Ok(removed)
-    }
+        // NB: moderators don't need their requests approved.
+        if !is_moderator && *self.inner.approval_required.read() {
+            let mut pending = self.inner.pending.write();
 
-    /// Remove the last track by the given user.
-    pub fn remove_last_by_user(&self, user: &str) -> Result<Option<Arc<Item>>, Error> {
-        let removed = self.inner.queue.remove_last_by_user(user)?;
+            pending.push(Pending {
+                item: item.clone(),
+                weight,
+            });
 
-        if removed.is_some() {
-            self.modified();
+            return Ok((Added::Pending(pending.len() - 1), item));
         }
 
-        Ok(removed)
+        let pos = match self.inner.queue.push_back(item.clone(), weight).await {
+            Ok(pos) => pos,
+            Err(e) => {
+                self.inner.charges.refund(&item);
+                return Err(AddTrackError::Error(e));
+            }
+        };
+
+        self.inner
+            .commands_tx
+            .unbounded_send(Command::Modified(Source::Manual))
+            .map_err(|e| AddTrackError::Error(e.into()))?;
+
+        Ok((Added::Queue(pos), item))
     }
 
-    /// Find the next item that matches the given predicate and how long until it plays.
-    pub fn find(&self, mut predicate: impl FnMut(&Item) -> bool) -> Option<(Duration, Arc<Item>)> {
-        let mut duration = Duration::default();
+    /// Check that the given track can be added to the queue on behalf of the given user.
+    ///
+    /// This checks the length of the queue, duplicate requests, and how many tracks the user
+    /// already has in the queue or waiting to be approved.
+    fn check_queue(
+        &self,
+        track_id: &TrackId,
+        user: &str,
+        is_moderator: bool,
+    ) -> Result<(), AddTrackError> {
+        if !is_moderator {
+            let duplicate_duration = self.inner.duplicate_duration.read().clone();
 
-        if let Some(c) = self.inner.song.read().as_ref() {
-            if predicate(&c.item) {
-                return Some((Default::default(), c.item.clone()));
-            }
+            if !duplicate_duration.is_empty() {
+                if let Some(last) = self
+                    .inner
+                    .queue
+                    .last_song_within(track_id, duplicate_duration.clone())
+                    .map_err(AddTrackError::Error)?
+                {
+                    let added_at = DateTime::from_utc(last.added_at, Utc);
 
-            duration += c.remaining();
+                    return Err(AddTrackError::Duplicate(
+                        added_at,
+                        last.user,
+                        duplicate_duration.as_std(),
+                    ));
+                }
+            }
         }
 
         let queue = self.inner.queue.queue.read();
+        let pending = self.inner.pending.read();
 
-        for item in &*queue {
-            if predicate(item) {
-                return Some((duration, item.clone()));
-            }
-
-            duration += item.duration;
-        }
+        let queue = queue
+            .iter()
+            .map(|i| (&i.track_id, i.user.as_ref().map(String::as_str)))
+            .collect::<Vec<_>>();
 
-        None
-    }
+        let pending = pending
+            .iter()
+            .map(|p| (&p.item.track_id, p.item.user.as_ref().map(String::as_str)))
+            .collect::<Vec<_>>();
+
+        // NB: moderators are allowed to violate the max queue length and add more songs.
+        let limits = if is_moderator {
+            None
+        } else {
+            Some((
+                *self.inner.max_queue_length.read(),
+                *self.inner.max_songs_per_user.read(),
+            ))
+        };
+
+        check_queue_limits(queue, pending, track_id, user, limits)
+    }
+
+    /// Get the requests which are waiting to be approved.
+    pub fn pending(&self) -> Vec<Arc<Item>> {
+        self.inner
+            .pending
+            .read()
+            .iter()
+            .map(|p| p.item.clone())
+            .collect()
+    }
+
+    /// Find the first pending request that matches the given predicate and its position.
+    pub fn find_pending(
+        &self,
+        mut predicate: impl FnMut(&Item) -> bool,
+    ) -> Option<(usize, Arc<Item>)> {
+        self.inner
+            .pending
+            .read()
+            .iter()
+            .enumerate()
+            .find(|(_, p)| predicate(&p.item))
+            .map(|(index, p)| (index, p.item.clone()))
+    }
+
+    /// Take the pending request matching the given selector, and the position it was at.
+    fn take_pending(&self, selector: &PendingSelector) -> Option<(usize, Pending)> {
+        let mut pending = self.inner.pending.write();
+
+        let index = match *selector {
+            PendingSelector::Index(index) => index,
+            PendingSelector::User(ref user) => pending
+                .iter()
+                .position(|p| p.item.user.as_ref() == Some(user))?,
+        };
+
+        if index >= pending.len() {
+            return None;
+        }
+
+        Some((index, pending.remove(index)))
+    }
+
+    /// Approve a pending request, adding it to the queue.
+    ///
+    /// The limits of the queue are checked again on behalf of the requester since the queue might
+    /// have changed while the request was waiting. If they are violated the request stays pending.
+    ///
+    /// Returns the position in the queue it was added to, or `None` if there is no matching
+    /// request.
+    pub async fn approve(
+        &self,
+        selector: PendingSelector,
+    ) -> Result<Option<(usize, Arc<Item>)>, AddTrackError> {
+        let (index, pending) = match self.take_pending(&selector) {
+            Some(pending) => pending,
+            None => return Ok(None),
+        };
+
+        if let Some(user) = pending.item.user.as_ref() {
+            if let Err(e) = self.check_queue(&pending.item.track_id, user, false) {
+                let mut queue = self.inner.pending.write();
+                let index = usize::min(index, queue.len());
+                queue.insert(index, pending);
+                return Err(e);
+            }
+        }
+
+        let Pending { item, weight } = pending;
+
+        let pos = match self.inner.queue.push_back(item.clone(), weight).await {
+            Ok(pos) => pos,
+            Err(e) => {
+                self.inner.charges.refund(&item);
+                return Err(AddTrackError::Error(e));
+            }
+        };
+
+        self.modified();
+        self.inner.bus.broadcast(Event::Approved(item.clone()));
+        Ok(Some((pos, item)))
+    }
+
+    /// Deny a pending request, refunding anything that was paid for it.
+    pub fn deny(&self, selector: PendingSelector, reason: Option<String>) -> Option<Arc<Item>> {
+        let (_, Pending { item, .. }) = self.take_pending(&selector)?;
+        self.inner.charges.refund(&item);
+        self.inner
+            .bus
+            .broadcast(Event::Denied(item.clone(), reason));
+        Some(item)
+    }
+
+    /// Remove the first track in the queue.
+    pub fn remove_first(&self) -> Result<Option<Arc<Item>>, Error> {
+        Ok(None)
+    }
+
+    pub fn purge(&self) -> Result<Vec<Arc<Item>>, Error> {
+        let purged = self.inner.queue.purge()?;
+
+        if !purged.is_empty() {
+            for item in &purged {
+                self.inner.charges.refund(&item);
+            }
+
+            self.modified();
+        }
+
+        Ok(purged)
+    }
+
+    /// Remove the item at the given position.
+    pub fn remove_at(&self, n: usize) -> Result<Option<Arc<Item>>, Error> {
+        let removed = self.inner.queue.remove_at(n)?;
+        self.removed(removed.as_ref());
+        Ok(removed)
+    }
+
+    /// Remove the first track in the queue.
+    pub fn remove_last(&self) -> Result<Option<Arc<Item>>, Error> {
+        let removed = self.inner.queue.remove_last()?;
+        self.removed(removed.as_ref());
+        Ok(removed)
+    }
+
+    /// Remove the last track by the given user.
+    pub fn remove_last_by_user(&self, user: &str) -> Result<Option<Arc<Item>>, Error> {
+        let removed = self.inner.queue.remove_last_by_user(user)?;
+        self.removed(removed.as_ref());
+        Ok(removed)
+    }
+
+    /// Handle an item that was removed from the queue without being played.
+    fn removed(&self, item: Option<&Arc<Item>>) {
+        if let Some(item) = item {
+            self.inner.charges.refund(&item);
+            self.modified();
+        }
+    }
+
+    /// Find the next item that matches the given predicate and how long until it plays.
+    pub fn find(&self, mut predicate: impl FnMut(&Item) -> bool) -> Option<(Duration, Arc<Item>)> {
+        let mut duration = Duration::default();
+
+        if let Some(c) = self.inner.song.read().as_ref() {
+            if predicate(&c.item) {
+                return Some((Default::default(), c.item.clone()));
+            }
+
+            duration += c.remaining();
+        }
+
+        let queue = self.inner.queue.queue.read();
+
+        for item in &*queue {
+            if predicate(item) {
+                return Some((duration, item.clone()));
+            }
+
+            duration += item.duration;
+        }
+
+        None
+    }
 
     /// Get the length in number of items and total number of seconds in queue.
     pub fn length(&self) -> (usize, Duration) {
@@ -1070,6 +1749,104 @@ impl Player {
     }
 }
 
+/// Where a requested track was added.
+#[derive(Debug, Clone, Copy)]
+pub enum Added {
+    /// The track was added to the queue at the given position.
+    Queue(usize),
+    /// The track is waiting to be approved, at the given position in the pending list.
+    Pending(usize),
+}
+
+/// Selects a request which is waiting to be approved.
+#[derive(Debug, Clone)]
+pub enum PendingSelector {
+    /// The pending request at the given position.
+    Index(usize),
+    /// The first pending request made by the given user.
+    User(String),
+}
+
+/// A request which is waiting to be approved by a moderator.
+struct Pending {
+    item: Arc<Item>,
+    /// Scheduling weight of the requester, used when the track is queued.
+    weight: u32,
+}
+
+/// A charge made for a song request.
+struct Charge {
+    currency: Currency,
+    channel: String,
+    user: String,
+    amount: i64,
+}
+
+/// Charges for songs in the queue, so that they can be refunded if they are never played.
+///
+/// Charges belong to a single entry in the queue, so the same track requested twice is charged
+/// and refunded separately. Charges are only tracked while the bot is running.
+#[derive(Clone)]
+struct Charges {
+    charges: Arc<RwLock<Vec<(Arc<Item>, Charge)>>>,
+    thread_pool: Arc<ThreadPool>,
+}
+
+impl Charges {
+    /// Construct a new collection of charges.
+    fn new(thread_pool: Arc<ThreadPool>) -> Self {
+        Self {
+            charges: Default::default(),
+            thread_pool,
+        }
+    }
+
+    /// Register a charge for the given queue entry.
+    fn insert(&self, item: &Arc<Item>, charge: Charge) {
+        self.charges.write().push((item.clone(), charge));
+    }
+
+    /// Take the charge for the given queue entry, if there is one.
+    fn take(&self, item: &Arc<Item>) -> Option<Charge> {
+        let mut charges = self.charges.write();
+        let index = charges.iter().position(|(i, _)| Arc::ptr_eq(i, item))?;
+        Some(charges.swap_remove(index).1)
+    }
+
+    /// The given entry has been played, so its charge can no longer be refunded.
+    fn settle(&self, item: &Arc<Item>) {
+        let _ = self.take(item);
+    }
+
+    /// Refund the charge for the given entry, if there is one.
+    fn refund(&self, item: &Arc<Item>) {
+        let charge = match self.take(item) {
+            Some(charge) => charge,
+            None => return,
+        };
+
+        let track_id = item.track_id.clone();
+
+        let future = async move {
+            let Charge {
+                currency,
+                channel,
+                user,
+                amount,
+            } = charge;
+
+            log::info!("{}: refunding {} to {}", track_id, amount, user);
+
+            if let Err(e) = currency.balance_add(channel, user, amount).await {
+                log_err!(e, "{}: failed to refund song request", track_id);
+            }
+        };
+
+        self.thread_pool
+            .spawn(Compat::new(Box::pin(future.unit_error())));
+    }
+}
+
 /// Error raised when failing to play a theme song.
 pub enum PlayThemeError {
     /// No such theme song.
@@ -1084,6 +1861,8 @@ pub enum AddTrackError {
     QueueFull,
     /// Queue already contains track.
     QueueContainsTrack(usize),
+    /// The track is already waiting to be approved at the given position.
+    PendingContainsTrack(usize),
     /// Too many user tracks.
     TooManyUserTracks(u32),
     /// Player has been closed from adding more tracks to the queue with an optional reason.
@@ -1094,6 +1873,24 @@ pub enum AddTrackError {
     NoCurrency,
     /// Not enough currency to request songs.
     NotEnoughCurrency { required: i64, balance: i64 },
+    /// The track, its artist, or its channel has been banned, with an optional reason.
+    Banned(db::BanKind, Option<String>),
+    /// The track was rejected by the content policy.
+    Policy(policy::Violation),
+    /// The user has made `limit` requests within `window`, and can request again in `wait`.
+    RequestQuotaExceeded {
+        limit: u32,
+        window: utils::Duration,
+        wait: Duration,
+    },
+    /// The user would have more than `limit` of songs in the queue.
+    ///
+    /// `wait` is how long until enough of their songs have played, or `None` if the requested song
+    /// is longer than the limit.
+    DurationQuotaExceeded {
+        limit: utils::Duration,
+        wait: Option<Duration>,
+    },
     /// Other generic error happened.
     Error(Error),
 }
@@ -1146,12 +1943,29 @@ pub trait Backend: Clone + Send + Sync {
     /// Promote the track with the given ID.
     fn promote_song(&self, user: &str, track_id: &TrackId) -> Result<bool, Error>;
 
+    /// Store the order of the queue, but only log on issues.
+    fn song_order_log(&self, order: &[TrackId]) {
+        if let Err(e) = self.song_order(order) {
+            log::warn!("failed to store queue order in database: {}", e);
+        }
+    }
+
+    /// Store the order of the queue.
+    fn song_order(&self, order: &[TrackId]) -> Result<(), Error>;
+
     /// Test if the song has been played within a given duration.
     fn last_song_within(
         &self,
         track_id: &TrackId,
         duration: utils::Duration,
     ) -> Result<Option<db::models::Song>, Error>;
+
+    /// When the given user requested songs within the given duration, oldest first.
+    fn user_requests_within(
+        &self,
+        user: &str,
+        duration: utils::Duration,
+    ) -> Result<Vec<chrono::NaiveDateTime>, Error>;
 }
 
 /// The playback queue.
@@ -1160,15 +1974,27 @@ struct Queue {
     db: db::Database,
     queue: Arc<RwLock<VecDeque<Arc<Item>>>>,
     thread_pool: Arc<ThreadPool>,
+    /// How items are ordered when added to the queue.
+    mode: Arc<RwLock<QueueMode>>,
+    /// Scheduling weights of users that have requested songs.
+    weights: Arc<RwLock<HashMap<String, u32>>>,
+    /// The latest order of the queue which has not yet been stored.
+    order: Arc<Mutex<Option<Vec<TrackId>>>>,
+    /// Held while storing the order, so that an older order never overwrites a newer one.
+    order_writer: Arc<Mutex<()>>,
 }
 
 impl Queue {
     /// Construct a new queue.
-    pub fn new(db: db::Database) -> Self {
+    pub fn new(db: db::Database, mode: Arc<RwLock<QueueMode>>) -> Self {
         Self {
             db,
             queue: Arc::new(RwLock::new(Default::default())),
             thread_pool: Arc::new(ThreadPool::new()),
+            mode,
+            weights: Default::default(),
+            order: Default::default(),
+            order_writer: Default::default(),
         }
     }
 
@@ -1181,6 +2007,15 @@ impl Queue {
         self.db.last_song_within(track_id, duration)
     }
 
+    /// When the given user requested songs within the given duration, oldest first.
+    pub fn user_requests_within(
+        &self,
+        user: &str,
+        duration: utils::Duration,
+    ) -> Result<Vec<chrono::NaiveDateTime>, Error> {
+        self.db.user_requests_within(user, duration)
+    }
+
     /// Get the front of the queue.
     pub fn front(&self) -> Option<Arc<Item>> {
         self.queue.read().front().cloned()
@@ -1191,11 +2026,14 @@ impl Queue {
         &self,
     ) -> impl Future<Output = Result<Option<Arc<Item>>, Error>> + Send + 'static {
         let db = self.db.clone();
-        let queue = self.queue.clone();
+        let queue = self.clone();
 
         let future = self.thread_pool.spawn_handle(future01::lazy(move || {
-            if let Some(item) = queue.write().pop_front() {
+            let mut q = queue.queue.write();
+
+            if let Some(item) = q.pop_front() {
                 db.remove_song_log(&item.track_id);
+                queue.forget_weight(&q, &item);
             }
 
             Ok(None)
@@ -1204,10 +2042,12 @@ impl Queue {
         future.compat()
     }
 
-    /// Push item to back of queue.
-    pub async fn push_back(&self, item: Arc<Item>) -> Result<(), Error> {
+    /// Push item to back of queue, or to its fair position if the queue is fair.
+    ///
+    /// Returns the position the item was inserted at.
+    pub async fn push_back(&self, item: Arc<Item>, weight: u32) -> Result<usize, Error> {
         let db = self.db.clone();
-        let queue = self.queue.clone();
+        let queue = self.clone();
 
         let future = self.thread_pool.spawn_handle(future01::lazy(move || {
             db.push_back(&db::models::AddSong {
@@ -1216,13 +2056,157 @@ impl Queue {
                 user: item.user.clone(),
             })?;
 
-            queue.write().push_back(item);
-            Ok(())
+            if let Some(user) = item.user.as_ref() {
+                queue.weights.write().insert(user.to_string(), weight);
+            }
+
+            Ok(queue.insert(item))
         }));
 
         future.compat().await
     }
 
+    /// Insert the item in the queue according to the current queue mode.
+    fn insert(&self, item: Arc<Item>) -> usize {
+        let mut q = self.queue.write();
+
+        let pos = match *self.mode.read() {
+            QueueMode::Fifo => q.len(),
+            QueueMode::Fair => {
+                let weights = self.weights.read();
+
+                fair_position(
+                    q.iter().map(|i| i.user.as_ref().map(String::as_str)),
+                    item.user.as_ref().map(String::as_str),
+                    |user| user.and_then(|u| weights.get(u).cloned()).unwrap_or(1),
+                )
+            }
+        };
+
+        q.insert(pos, item);
+        self.store_order(&q);
+        pos
+    }
+
+    /// Forget the weight of the user who requested the removed item, unless they have other items
+    /// in the queue.
+    fn forget_weight(&self, q: &VecDeque<Arc<Item>>, removed: &Item) {
+        let user = match removed.user.as_ref() {
+            Some(user) => user,
+            None => return,
+        };
+
+        if q.iter().any(|i| i.user.as_ref() == Some(user)) {
+            return;
+        }
+
+        self.weights.write().remove(user);
+    }
+
+    /// Store the current order of the queue in the database.
+    ///
+    /// The order is stored in the background, and only the latest of several orders stored in
+    /// quick succession is written.
+    fn store_order(&self, q: &VecDeque<Arc<Item>>) {
+        let order = q.iter().map(|i| i.track_id.clone()).collect::<Vec<_>>();
+
+        if self.order.lock().replace(order).is_some() {
+            // NB: a write is already scheduled, and will pick up the new order.
+            return;
+        }
+
+        let db = self.db.clone();
+        let order = self.order.clone();
+        let order_writer = self.order_writer.clone();
+
+        self.thread_pool.spawn(future01::lazy(move || {
+            let _writer = order_writer.lock();
+
+            if let Some(order) = order.lock().take() {
+                db.song_order_log(&order);
+            }
+
+            Ok(())
+        }));
+    }
+
+    /// Move the item at position `from` to position `to`.
+    pub fn move_item(&self, from: usize, to: usize) -> Option<Arc<Item>> {
+        let mut q = self.queue.write();
+
+        if from >= q.len() || to >= q.len() {
+            return None;
+        }
+
+        let item = q.remove(from)?;
+        q.insert(to, item.clone());
+        self.store_order(&q);
+        Some(item)
+    }
+
+    /// Swap the items at positions `a` and `b`.
+    pub fn swap(&self, a: usize, b: usize) -> bool {
+        let mut q = self.queue.write();
+
+        if a >= q.len() || b >= q.len() {
+            return false;
+        }
+
+        q.swap(a, b);
+        self.store_order(&q);
+        true
+    }
+
+    /// Shuffle the queue.
+    pub fn shuffle(&self) {
+        use rand::seq::SliceRandom as _;
+
+        let mut q = self.queue.write();
+
+        let mut items = q.drain(..).collect::<Vec<_>>();
+        items.shuffle(&mut rand::thread_rng());
+        q.extend(items);
+
+        self.store_order(&q);
+    }
+
+    /// Reorder the queue to match the given order of track ids.
+    ///
+    /// The order must contain exactly the tracks that are in the queue.
+    pub fn reorder(&self, order: &[TrackId]) -> Result<(), Error> {
+        let mut q = self.queue.write();
+
+        if order.len() != q.len() {
+            bail!(
+                "expected {} tracks in order, but got {}",
+                q.len(),
+                order.len()
+            );
+        }
+
+        let mut items = HashMap::new();
+
+        for item in q.iter() {
+            items.insert(item.track_id.clone(), item.clone());
+        }
+
+        let mut reordered = VecDeque::with_capacity(order.len());
+
+        for track_id in order {
+            match items.remove(track_id) {
+                Some(item) => reordered.push_back(item),
+                None => bail!(
+                    "track `{}` is not in the queue, or is listed twice",
+                    track_id
+                ),
+            }
+        }
+
+        *q = reordered;
+        self.store_order(&q);
+        Ok(())
+    }
+
     /// Purge the song queue.
     pub fn purge(&self) -> Result<Vec<Arc<Item>>, Error> {
         let mut q = self.queue.write();
@@ -1234,6 +2218,7 @@ impl Queue {
         let purged = std::mem::replace(&mut *q, VecDeque::new())
             .into_iter()
             .collect();
+        self.weights.write().clear();
         self.db.song_purge_log();
         Ok(purged)
     }
@@ -1248,6 +2233,7 @@ impl Queue {
 
         if let Some(item) = q.remove(n) {
             self.db.remove_song_log(&item.track_id);
+            self.forget_weight(&q, &item);
             return Ok(Some(item));
         }
 
@@ -1264,6 +2250,7 @@ impl Queue {
 
         if let Some(item) = q.pop_back() {
             self.db.remove_song_log(&item.track_id);
+            self.forget_weight(&q, &item);
             return Ok(Some(item));
         }
 
@@ -1284,6 +2271,7 @@ impl Queue {
         {
             if let Some(item) = q.remove(position) {
                 self.db.remove_song_log(&item.track_id);
+                self.forget_weight(&q, &item);
                 return Ok(Some(item));
             }
         }
@@ -1306,16 +2294,310 @@ impl Queue {
 
         if let Some(item) = q.get(0).cloned() {
             self.db.promote_song_log(user, &item.track_id);
+            self.store_order(&q);
             return Some(item);
         }
 
-        None
+        None
+    }
+
+    /// Push item to back of queue without going through the database.
+    ///
+    /// Items are loaded in the order they are stored in, so this doesn't take the queue mode into
+    /// account.
+    fn push_back_queue(&self, item: Arc<Item>) {
+        self.queue.write().push_back(item);
+    }
+}
+
+/// Calculate the position at which an item requested by `user` should be inserted to
+/// give every requester a fair turn.
+///
+/// Each item in the queue belongs to a round, which is the number of items the same user
+/// has ahead of it divided by the weight of that user. The new item is placed after the
+/// last item belonging to the same or an earlier round.
+fn fair_position<'a>(
+    users: impl IntoIterator<Item = Option<&'a str>>,
+    user: Option<&'a str>,
+    weight: impl Fn(Option<&str>) -> u32,
+) -> usize {
+    let mut counts = HashMap::<Option<&str>, u32>::new();
+    let mut rounds = Vec::new();
+
+    for u in users {
+        let count = counts.entry(u).or_default();
+        rounds.push(*count / u32::max(weight(u), 1));
+        *count += 1;
+    }
+
+    let count = counts.get(&user).cloned().unwrap_or_default();
+    let round = count / u32::max(weight(user), 1);
+
+    match rounds.iter().rposition(|r| *r <= round) {
+        Some(index) => index + 1,
+        None => 0,
+    }
+}
+
+/// Calculate how long a user has to wait until a song of duration `new` fits in their quota of
+/// queued songs.
+///
+/// `queue` contains every item in the queue, in play order, and whether it belongs to the user.
+///
+/// Returns `None` if the song fits right away, and `Some(None)` if it will never fit.
+fn duration_quota_wait(
+    current: Duration,
+    queue: impl IntoIterator<Item = (bool, Duration)>,
+    new: Duration,
+    limit: Duration,
+) -> Option<Option<Duration>> {
+    if new > limit {
+        return Some(None);
+    }
+
+    let queue = queue.into_iter().collect::<Vec<_>>();
+
+    let mut queued = queue
+        .iter()
+        .filter(|(is_user, _)| *is_user)
+        .map(|(_, d)| *d)
+        .sum::<Duration>();
+
+    if queued + new <= limit {
+        return None;
+    }
+
+    let mut wait = current;
+
+    for (is_user, duration) in queue {
+        wait += duration;
+
+        if !is_user {
+            continue;
+        }
+
+        queued -= duration;
+
+        if queued + new <= limit {
+            break;
+        }
+    }
+
+    Some(Some(wait))
+}
+
+/// Check the limits of the queue for a track requested by `user`.
+///
+/// `queue` and `pending` contain the track id and requester of every item in the queue and of
+/// every request waiting to be approved. `limits` is the maximum length of the queue and the
+/// maximum number of songs per user, or `None` if they don't apply.
+fn check_queue_limits(
+    queue: Vec<(&TrackId, Option<&str>)>,
+    pending: Vec<(&TrackId, Option<&str>)>,
+    track_id: &TrackId,
+    user: &str,
+    limits: Option<(u32, u32)>,
+) -> Result<(), AddTrackError> {
+    if let Some((max_queue_length, _)) = limits {
+        if queue.len() >= max_queue_length as usize {
+            return Err(AddTrackError::QueueFull);
+        }
+    }
+
+    let mut user_count = 0;
+
+    for (index, (id, u)) in queue.into_iter().enumerate() {
+        if id == track_id {
+            return Err(AddTrackError::QueueContainsTrack(index));
+        }
+
+        if u == Some(user) {
+            user_count += 1;
+        }
+    }
+
+    // NB: pending requests count towards the limits of the user as well.
+    for (index, (id, u)) in pending.into_iter().enumerate() {
+        if id == track_id {
+            return Err(AddTrackError::PendingContainsTrack(index));
+        }
+
+        if u == Some(user) {
+            user_count += 1;
+        }
+    }
+
+    if let Some((_, max_songs_per_user)) = limits {
+        if user_count >= max_songs_per_user {
+            return Err(AddTrackError::TooManyUserTracks(max_songs_per_user));
+        }
+    }
+
+    Ok(())
+}
+
+/// Decide what to do with the request at the front of the queue, made by a user who has been
+/// absent from chat for `absent`.
+///
+/// `moved_down` indicates if the request has been moved down before, and `behind` is the number
+/// of requests behind it in the queue. Returns `None` if the request should be left alone.
+fn expiry_action(
+    absent: Duration,
+    limit: Duration,
+    action: ExpiryAction,
+    moved_down: bool,
+    behind: usize,
+) -> Option<ExpiryAction> {
+    if absent < limit {
+        return None;
+    }
+
+    // NB: songs are only moved down once, after that they are skipped.
+    let action = match moved_down {
+        true => ExpiryAction::Skip,
+        false => action,
+    };
+
+    // NB: nothing to move the song behind.
+    if action == ExpiryAction::MoveDown && behind == 0 {
+        return None;
+    }
+
+    Some(action)
+}
+
+/// Request quotas for a single role.
+#[derive(Clone)]
+struct Quota {
+    /// Maximum number of requests within the quota window, or `0` for no limit.
+    requests: Arc<RwLock<u32>>,
+    /// Maximum total duration of songs in the queue, or empty for no limit.
+    duration: Arc<RwLock<utils::Duration>>,
+}
+
+impl Quota {
+    fn build(vars: &mut settings::Vars, role: &str) -> Result<Self, Error> {
+        Ok(Quota {
+            requests: vars.var(&format!("quota/{}/requests", role), 0)?,
+            duration: vars.var(
+                &format!("quota/{}/duration", role),
+                utils::Duration::default(),
+            )?,
+        })
+    }
+}
+
+/// What happens to requests by users who are no longer in chat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum ExpiryAction {
+    /// The request is removed from the queue.
+    #[serde(rename = "skip")]
+    Skip,
+    /// The request is moved to the end of the queue, and removed if it comes up again.
+    #[serde(rename = "move-down")]
+    MoveDown,
+}
+
+impl Default for ExpiryAction {
+    fn default() -> Self {
+        ExpiryAction::Skip
+    }
+}
+
+/// Expiry policy for requests by users who are no longer in chat.
+#[derive(Clone)]
+struct Expiry {
+    enabled: Arc<RwLock<bool>>,
+    /// How long the requester has to be absent from chat.
+    time: Arc<RwLock<utils::Duration>>,
+    action: Arc<RwLock<ExpiryAction>>,
+}
+
+impl Expiry {
+    fn build(vars: &mut settings::Vars) -> Result<Self, Error> {
+        Ok(Expiry {
+            enabled: vars.var("expiry/enabled", false)?,
+            time: vars.var("expiry/time", utils::Duration::seconds(30 * 60))?,
+            action: vars.var("expiry/action", ExpiryAction::default())?,
+        })
+    }
+}
+
+/// Request quotas, with separate limits for each role.
+#[derive(Clone)]
+struct Quotas {
+    /// The sliding window in which requests are counted.
+    window: Arc<RwLock<utils::Duration>>,
+    everyone: Quota,
+    subscriber: Quota,
+    vip: Quota,
+}
+
+impl Quotas {
+    fn build(vars: &mut settings::Vars) -> Result<Self, Error> {
+        Ok(Quotas {
+            window: vars.var("quota/window", utils::Duration::hours(1))?,
+            everyone: Quota::build(vars, "everyone")?,
+            subscriber: Quota::build(vars, "subscriber")?,
+            vip: Quota::build(vars, "vip")?,
+        })
+    }
+
+    /// Get the request and duration limits that apply to a user.
+    ///
+    /// Users with several roles get the most generous limit of those roles.
+    fn limits(&self, is_subscriber: bool, is_vip: bool) -> (u32, utils::Duration) {
+        let mut roles = Vec::new();
+
+        if is_subscriber {
+            roles.push(&self.subscriber);
+        }
+
+        if is_vip {
+            roles.push(&self.vip);
+        }
+
+        let requests = most_generous(
+            *self.everyone.requests.read(),
+            roles.iter().map(|q| *q.requests.read()).collect(),
+        );
+
+        let duration = most_generous(
+            self.everyone.duration.read().clone(),
+            roles.iter().map(|q| q.duration.read().clone()).collect(),
+        );
+
+        (requests, duration)
     }
+}
 
-    /// Push item to back of queue without going through the database.
-    fn push_back_queue(&self, item: Arc<Item>) {
-        self.queue.write().push_back(item);
+/// Pick the most generous limit of the given roles, where the default value means no limit.
+///
+/// Roles without a limit fall back to the limit of everyone, so there is only no limit if neither
+/// the role nor everyone has one.
+fn most_generous<T>(everyone: T, roles: Vec<T>) -> T
+where
+    T: Clone + Default + Ord,
+{
+    let unlimited = T::default();
+
+    let limits = roles
+        .into_iter()
+        .map(|limit| match limit == unlimited {
+            true => everyone.clone(),
+            false => limit,
+        })
+        .collect::<Vec<_>>();
+
+    if limits.is_empty() {
+        return everyone;
+    }
+
+    if limits.iter().any(|limit| *limit == unlimited) {
+        return unlimited;
     }
+
+    limits.into_iter().max().unwrap_or(unlimited)
 }
 
 /// Mixer decides what song to play next.
@@ -1328,6 +2610,8 @@ pub struct Mixer {
     fallback_items: Vec<Arc<Item>>,
     /// Items ordered in the reverse way they are meant to be played.
     fallback_queue: VecDeque<Arc<Item>>,
+    /// Songs picked by the auto-DJ, which are played before other fallback items.
+    auto_dj_queue: VecDeque<Arc<Item>>,
 }
 
 impl Mixer {
@@ -1339,6 +2623,10 @@ impl Mixer {
     fn next_fallback_item(&mut self) -> Option<Song> {
         use rand::seq::SliceRandom;
 
+        if let Some(item) = self.auto_dj_queue.pop_front() {
+            return Some(Song::new(item, Default::default()));
+        }
+
         if self.fallback_items.is_empty() {
             return None;
         }
@@ -1374,7 +2662,7 @@ impl Mixer {
             return Ok(Some(Song::new(item, Default::default())));
         }
 
-        if self.fallback_items.is_empty() {
+        if self.fallback_items.is_empty() && self.auto_dj_queue.is_empty() {
             log::warn!("there are no fallback songs available");
             return Ok(None);
         }
@@ -1404,10 +2692,30 @@ impl EventBus {
     }
 }
 
+/// The reason a song stopped playing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndReason {
+    /// The song played until the end.
+    Finished,
+    /// The song was skipped.
+    Skipped,
+}
+
+impl EndReason {
+    /// Get the reason as it is stored in the history.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            EndReason::Finished => "finished",
+            EndReason::Skipped => "skipped",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PlayerKind {
     Spotify,
     YouTube,
+    Local,
     None,
 }
 
@@ -1426,6 +2734,9 @@ pub struct PlaybackFuture {
     connect_stream: self::connect::ConnectStream,
     connect_player: self::connect::ConnectPlayer,
     youtube_player: self::youtube::YouTubePlayer,
+    /// Events from overlays connected to the YouTube player.
+    youtube_stream: mpsc::UnboundedReceiver<IntegrationEvent>,
+    local_player: self::local::LocalPlayer,
     commands: mpsc::UnboundedReceiver<Command>,
     bus: EventBus,
     mixer: Mixer,
@@ -1451,6 +2762,34 @@ pub struct PlaybackFuture {
     global_bus: Arc<bus::Bus<bus::Global>>,
     /// Timeout for end of song.
     timeout: Option<timer::Delay>,
+    /// History of played songs.
+    history: db::History,
+    /// Charges for songs in the queue.
+    charges: Charges,
+    /// Playback was paused because no device was reachable.
+    device_paused: bool,
+    /// Playback was paused because no overlay is connected to the YouTube player.
+    overlay_paused: bool,
+    /// Expiry policy for requests by users who are no longer in chat.
+    expiry: Expiry,
+    /// When users were last seen in chat.
+    last_seen: LastSeen,
+    /// Requests which have been moved down the queue since their requester was not in chat.
+    moved_down: HashSet<TrackId>,
+    /// Where songs are taken from when the queue is empty.
+    fallback_mode: Arc<RwLock<FallbackMode>>,
+    /// Picks songs to fall back to based on the request history.
+    auto_dj: auto_dj::AutoDj,
+    /// Sender for songs picked by the auto-DJ in the background.
+    auto_dj_tx: mpsc::UnboundedSender<Result<Vec<Arc<Item>>, Error>>,
+    /// Songs picked by the auto-DJ in the background.
+    auto_dj_rx: mpsc::UnboundedReceiver<Result<Vec<Arc<Item>>, Error>>,
+    /// The auto-DJ is picking songs in the background.
+    auto_dj_picking: bool,
+    /// How long to wait the next time the auto-DJ doesn't pick anything.
+    auto_dj_backoff: Duration,
+    /// The auto-DJ is not asked again before this time since it didn't pick anything.
+    auto_dj_retry: Option<Instant>,
 }
 
 impl PlaybackFuture {
@@ -1496,6 +2835,9 @@ impl PlaybackFuture {
             .or_with(self.config.current_song.update_interval.clone())?;
 
         let (mut enabled_stream, enabled) = song_file.stream("enabled").or_default()?;
+        let (mut outputs_stream, outputs) = song_file.stream("outputs").or_default()?;
+        let (mut json_path_stream, json_path) = song_file.stream("json-path").optional()?;
+        let (mut art_path_stream, art_path) = song_file.stream("art-path").optional()?;
 
         let (mut fallback_stream, fallback) = settings.stream("fallback-uri").optional()?;
         self.update_fallback_items(fallback.clone()).await;
@@ -1506,6 +2848,9 @@ impl PlaybackFuture {
         song_file.template = template;
         song_file.stopped_template = stopped_template;
         song_file.update_interval = update_interval;
+        song_file.outputs = outputs;
+        song_file.json_path = json_path;
+        song_file.art_path = art_path;
         song_file.init(&mut self.song_file);
 
         loop {
@@ -1536,6 +2881,18 @@ impl PlaybackFuture {
                     song_file.update_interval = update;
                     song_file.init(&mut self.song_file);
                 }
+                update = outputs_stream.select_next_some() => {
+                    song_file.outputs = update;
+                    song_file.init(&mut self.song_file);
+                }
+                update = json_path_stream.select_next_some() => {
+                    song_file.json_path = update;
+                    song_file.init(&mut self.song_file);
+                }
+                update = art_path_stream.select_next_some() => {
+                    song_file.art_path = update;
+                    song_file.init(&mut self.song_file);
+                }
                 update = song_file_update.select_next_some() => {
                     let _ =  update?;
                     let song = self.song.read();
@@ -1568,8 +2925,14 @@ impl PlaybackFuture {
                             .send(bus::Global::song_progress(song.as_ref()));
 
                         if let Some(song) = song.as_ref() {
-                            if let TrackId::YouTube(ref id) = song.item.track_id {
-                                self.youtube_player.tick(song.elapsed(), song.duration(), id.to_string());
+                            match song.item.track_id {
+                                TrackId::YouTube(ref id) => {
+                                    self.youtube_player.tick(song.elapsed(), song.duration(), id.to_string());
+                                }
+                                TrackId::Local(ref id) => {
+                                    self.local_player.tick(song.elapsed(), song.duration(), id.to_string());
+                                }
+                                _ => (),
                             }
                         }
                     }
@@ -1577,9 +2940,24 @@ impl PlaybackFuture {
                 event = self.connect_stream.select_next_some() => {
                     self.handle_player_event(event?).await?;
                 }
+                event = self.youtube_stream.select_next_some() => {
+                    self.handle_player_event(event).await?;
+                }
                 command = self.commands.select_next_some() => {
                     self.command(command).await?;
                 }
+                result = self.auto_dj_rx.select_next_some() => {
+                    self.auto_dj_picked(result);
+
+                    // NB: start playing if we ran out of songs while the auto-DJ was picking.
+                    if let State::Playing = self.state {
+                        if self.song.read().is_none() {
+                            if let Some(song) = self.next_song().await? {
+                                self.play_song(Source::Automatic, song).await?;
+                            }
+                        }
+                    }
+                }
             }
         }
     }
@@ -1720,23 +3098,51 @@ impl PlaybackFuture {
     ///
     /// MUST NOT be called when self.song is locked.
     fn update_song_file(&self, song: Option<&Song>) {
-        let current_song = match self.song_file.as_ref() {
-            Some(current_song) => current_song,
+        if let Some(song_file) = self.song_file.as_ref() {
+            song_file.update(song, self.state);
+        }
+    }
+
+    /// Handle the end of the current song.
+    ///
+    /// Records it in the play history, and refunds its charge if it never started playing.
+    ///
+    /// MUST NOT be called when self.song is locked.
+    fn song_ended(&self, reason: EndReason) {
+        self.record_history(reason);
+
+        if let Some(song) = self.song.read().as_ref() {
+            self.charges.refund(&song.item);
+        }
+    }
+
+    /// Record the current song in the play history, if it has been played.
+    ///
+    /// MUST NOT be called when self.song is locked.
+    fn record_history(&self, reason: EndReason) {
+        let song = self.song.read();
+
+        let song = match song.as_ref() {
+            Some(song) => song,
             None => return,
         };
 
-        let result = match song {
-            Some(song) => current_song.write(song, self.state),
-            None => current_song.blank(),
+        let played_at = match song.played_at {
+            Some(played_at) => played_at,
+            None => return,
         };
 
-        if let Err(e) = result {
-            log::warn!(
-                "failed to write current song: {}: {}",
-                current_song.path.display(),
-                e
-            );
-        }
+        self.history.push_log(&db::models::InsertHistoryEntry {
+            track_id: song.item.track_id.clone(),
+            name: song.item.track.name(),
+            artists: song.item.track.artists(),
+            user: song.item.user.clone(),
+            started_at: played_at.naive_utc(),
+            ended_at: Utc::now().naive_utc(),
+            elapsed: song.elapsed().as_millis() as i32,
+            duration: song.duration().as_millis() as i32,
+            reason: reason.as_str().to_string(),
+        });
     }
 
     /// Switch the current player and send the appropriate play commands.
@@ -1746,18 +3152,36 @@ impl PlaybackFuture {
         match (self.player, player) {
             (Spotify, Spotify) => (),
             (YouTube, YouTube) => (),
-            (Spotify, _) | (None, YouTube) => {
+            (Local, Local) => (),
+            (None, None) => (),
+            // NB: we don't know what was playing before, so stop all other players.
+            (None, player) => {
+                for other in [Spotify, YouTube, Local].iter().cloned() {
+                    if other != player {
+                        self.stop_player(other).await;
+                    }
+                }
+            }
+            (current, _) => self.stop_player(current).await,
+        }
+
+        self.player = player;
+    }
+
+    /// Send a stop command to the given player.
+    async fn stop_player(&mut self, player: PlayerKind) {
+        match player {
+            PlayerKind::Spotify => {
                 let result = self.connect_player.stop().await;
 
                 if let Err(self::connect::CommandError::NoDevice) = result {
                     self.bus.broadcast(Event::NotConfigured);
                 }
             }
-            (YouTube, _) | (None, Spotify) => self.youtube_player.stop(),
-            (None, None) => (),
+            PlayerKind::YouTube => self.youtube_player.stop(),
+            PlayerKind::Local => self.local_player.stop(),
+            PlayerKind::None => (),
         }
-
-        self.player = player;
     }
 
     /// Send a pause command to the appropriate player.
@@ -1776,6 +3200,10 @@ impl PlaybackFuture {
                 log::trace!("pausing youtube player");
                 self.youtube_player.pause();
             }
+            PlayerKind::Local => {
+                log::trace!("pausing local player");
+                self.local_player.pause();
+            }
             _ => (),
         }
     }
@@ -1784,15 +3212,33 @@ impl PlaybackFuture {
     async fn send_play_command(&mut self, song: Song) {
         match song.item.track_id.clone() {
             TrackId::Spotify(id) => {
-                let result = self.connect_player.play(song.elapsed(), id).await;
+                let result = self
+                    .connect_player
+                    .play(song.elapsed(), song.duration(), id)
+                    .await;
 
-                if let Err(self::connect::CommandError::NoDevice) = result {
-                    self.bus.broadcast(Event::NotConfigured);
+                match result {
+                    Ok(()) => self.charges.settle(&song.item),
+                    // NB: the charge is settled once a device picks the song up, or refunded
+                    // when the song ends without having been played.
+                    Err(self::connect::CommandError::NoDevice) => {
+                        self.bus.broadcast(Event::NotConfigured);
+                    }
+                    Err(e) => {
+                        log_err!(e, "{}: failed to play song", song.item.track_id);
+                        self.charges.refund(&song.item);
+                    }
                 }
             }
-            TrackId::YouTube(id) => self
-                .youtube_player
-                .play(song.elapsed(), song.duration(), id),
+            TrackId::YouTube(id) => {
+                self.youtube_player
+                    .play(song.elapsed(), song.duration(), id);
+                self.charges.settle(&song.item);
+            }
+            TrackId::Local(id) => {
+                self.local_player.play(song.elapsed(), song.duration(), id);
+                self.charges.settle(&song.item);
+            }
         }
     }
 
@@ -1828,6 +3274,7 @@ impl PlaybackFuture {
         self.write_song(Some(song))?;
 
         self.state = State::Playing;
+        self.check_overlay()?;
         Ok(())
     }
 
@@ -1847,6 +3294,7 @@ impl PlaybackFuture {
         self.notify_song_change(Some(&song))?;
 
         self.state = State::Playing;
+        self.check_overlay()?;
         Ok(())
     }
 
@@ -1901,7 +3349,8 @@ impl PlaybackFuture {
             (Skip(source), _) => {
                 log::trace!("skipping song");
 
-                let song = self.mixer.next_song().await?;
+                self.song_ended(EndReason::Skipped);
+                let song = self.next_song().await?;
 
                 match (song, self.state) {
                     (Some(song), State::Playing) => self.play_song(source, song).await?,
@@ -1956,7 +3405,7 @@ impl PlaybackFuture {
                 }
 
                 // play the next song in queue.
-                if let Some(song) = self.mixer.next_song().await? {
+                if let Some(song) = self.next_song().await? {
                     self.play_song(source, song).await?;
                 } else {
                     if let Source::Manual = source {
@@ -1988,7 +3437,7 @@ impl PlaybackFuture {
             // queue was modified in some way
             (Modified(source), State::Playing) => {
                 if self.song.read().is_none() {
-                    if let Some(song) = self.mixer.next_song().await? {
+                    if let Some(song) = self.next_song().await? {
                         self.play_song(source, song).await?;
                     }
                 }
@@ -2012,6 +3461,165 @@ impl PlaybackFuture {
         Ok(())
     }
 
+    /// Get the next song to play, after expiring requests by users who are no longer in chat.
+    async fn next_song(&mut self) -> Result<Option<Song>, Error> {
+        self.expire_requests().await?;
+        self.fill_auto_dj();
+
+        let song = self.mixer.next_song().await?;
+
+        if let Some(song) = song.as_ref() {
+            self.moved_down.remove(&song.item.track_id);
+        }
+
+        Ok(song)
+    }
+
+    /// Let the auto-DJ pick more songs in the background if the queue is running low and it has
+    /// run out.
+    fn fill_auto_dj(&mut self) {
+        if *self.fallback_mode.read() != FallbackMode::AutoDj {
+            self.mixer.auto_dj_queue.clear();
+            return;
+        }
+
+        if self.auto_dj_picking || !self.mixer.auto_dj_queue.is_empty() {
+            return;
+        }
+
+        if let Some(retry) = self.auto_dj_retry {
+            if Instant::now() < retry {
+                return;
+            }
+
+            self.auto_dj_retry = None;
+        }
+
+        // NB: the song about to be played is the last one before we fall back.
+        let running_low =
+            self.mixer.sidelined.is_empty() && self.mixer.queue.queue.read().len() <= 1;
+
+        if !running_low {
+            return;
+        }
+
+        let auto_dj = self.auto_dj.clone();
+        let auto_dj_tx = self.auto_dj_tx.clone();
+
+        let future = async move {
+            if let Err(_) = auto_dj_tx.unbounded_send(auto_dj.items().await) {
+                log::warn!("failed to send songs picked by the auto-DJ");
+            }
+        };
+
+        tokio::spawn(Compat::new(Box::pin(future.unit_error())));
+        self.auto_dj_picking = true;
+    }
+
+    /// Handle the songs picked by the auto-DJ, backing off if it didn't pick anything.
+    fn auto_dj_picked(&mut self, result: Result<Vec<Arc<Item>>, Error>) {
+        self.auto_dj_picking = false;
+
+        // NB: the fallback mode might have changed while picking.
+        if *self.fallback_mode.read() != FallbackMode::AutoDj {
+            return;
+        }
+
+        match result {
+            Ok(items) => {
+                if !items.is_empty() {
+                    log::info!("Auto-DJ picked {} songs.", items.len());
+                    self.mixer.auto_dj_queue.extend(items);
+                    self.auto_dj_backoff = AUTO_DJ_BACKOFF;
+                    return;
+                }
+
+                log::info!(
+                    "Auto-DJ didn't pick any songs, trying again in {:?}.",
+                    self.auto_dj_backoff
+                );
+            }
+            Err(e) => {
+                log_err!(
+                    e,
+                    "Failed to pick songs for the auto-DJ, trying again in {:?}",
+                    self.auto_dj_backoff
+                );
+            }
+        }
+
+        self.auto_dj_retry = Some(Instant::now() + self.auto_dj_backoff);
+        self.auto_dj_backoff = Duration::min(self.auto_dj_backoff * 2, AUTO_DJ_MAX_BACKOFF);
+    }
+
+    /// Skip or move down requests at the front of the queue by users who haven't been seen in
+    /// chat recently.
+    async fn expire_requests(&mut self) -> Result<(), Error> {
+        // NB: sidelined songs are played before the queue.
+        if !*self.expiry.enabled.read() || !self.mixer.sidelined.is_empty() {
+            return Ok(());
+        }
+
+        let limit = self.expiry.time.read().as_std();
+        let queue = self.mixer.queue.clone();
+
+        // NB: bounded since songs which are moved down might come back to the front.
+        let len = queue.queue.read().len();
+
+        for _ in 0..len {
+            let item = match queue.front() {
+                Some(item) => item,
+                None => break,
+            };
+
+            let absent = match item
+                .user
+                .as_ref()
+                .and_then(|u| self.last_seen.absent_for(u))
+            {
+                Some(absent) => absent,
+                None => break,
+            };
+
+            let last = queue.queue.read().len().saturating_sub(1);
+
+            let action = match expiry_action(
+                absent,
+                limit,
+                *self.expiry.action.read(),
+                self.moved_down.contains(&item.track_id),
+                last,
+            ) {
+                Some(action) => action,
+                None => break,
+            };
+
+            log::info!(
+                "{}: {:?} request by {:?}, not seen in chat for {:?}",
+                item.track_id,
+                action,
+                item.user,
+                absent
+            );
+
+            match action {
+                ExpiryAction::Skip => {
+                    queue.pop_front().await?;
+                    self.charges.refund(&item);
+                    self.moved_down.remove(&item.track_id);
+                }
+                ExpiryAction::MoveDown => {
+                    queue.move_item(0, last);
+                    self.moved_down.insert(item.track_id.clone());
+                }
+            }
+
+            self.bus.broadcast(Event::Expired(item, action, absent));
+        }
+
+        Ok(())
+    }
+
     /// We've reached the end of a track.
     async fn end_of_track(&mut self) -> Result<(), Error> {
         if self.detached {
@@ -2021,7 +3629,13 @@ impl PlaybackFuture {
 
         log::trace!("Song ended, loading next song...");
 
-        if let Some(song) = self.mixer.next_song().await? {
+        self.song_ended(EndReason::Finished);
+
+        if let Some(song) = self.song.read().as_ref() {
+            self.bus.broadcast(Event::Finished(song.item.clone()));
+        }
+
+        if let Some(song) = self.next_song().await? {
             self.play_song(Source::Manual, song).await?;
         } else {
             self.bus.broadcast(Event::Empty);
@@ -2074,10 +3688,18 @@ impl PlaybackFuture {
 
                 match track_id {
                     TrackId::Spotify(id) => {
-                        let result = self.connect_player.play(elapsed, id).await;
+                        let result = self.connect_player.play(elapsed, duration, id).await;
 
-                        if let Err(self::connect::CommandError::NoDevice) = result {
-                            self.bus.broadcast(Event::NotConfigured);
+                        match result {
+                            Ok(()) => {
+                                if let Some(song) = self.song.read().as_ref() {
+                                    self.charges.settle(&song.item);
+                                }
+                            }
+                            Err(self::connect::CommandError::NoDevice) => {
+                                self.bus.broadcast(Event::NotConfigured);
+                            }
+                            Err(_) => (),
                         }
 
                         self.switch_current_player(PlayerKind::Spotify).await;
@@ -2088,23 +3710,214 @@ impl PlaybackFuture {
                         self.switch_current_player(PlayerKind::YouTube).await;
                         self.state = State::Playing;
                     }
+                    TrackId::Local(id) => {
+                        self.local_player.play(elapsed, duration, id);
+                        self.switch_current_player(PlayerKind::Local).await;
+                        self.state = State::Playing;
+                    }
+                }
+            }
+            IntegrationEvent::DeviceHealth(health) => {
+                self.bus.broadcast(Event::DeviceHealth(health.clone()));
+                self.global_bus.send(bus::Global::DeviceHealth {
+                    health: health.clone(),
+                });
+
+                match health {
+                    self::connect::DeviceHealth::Unavailable => {
+                        // NB: only Spotify playback depends on the device.
+                        if self.state != State::Playing || self.player != PlayerKind::Spotify {
+                            return Ok(());
+                        }
+
+                        log::info!("no spotify device reachable, pausing playback");
+                        self.device_paused = true;
+                        self.suspend()?;
+                    }
+                    _ => {
+                        if !self.device_paused {
+                            return Ok(());
+                        }
+
+                        self.device_paused = false;
+
+                        if self.state == State::Paused {
+                            log::info!("spotify device reachable again, resuming playback");
+                            self.command(Command::Play(Source::Automatic)).await?;
+                        }
+                    }
+                }
+            }
+            YouTubeConnected(true) => {
+                if !self.overlay_paused {
+                    return Ok(());
+                }
+
+                self.overlay_paused = false;
+
+                if self.state == State::Paused {
+                    log::info!("youtube player connected, resuming playback");
+                    self.command(Command::Play(Source::Automatic)).await?;
+                }
+            }
+            YouTubeConnected(false) => {
+                if self.state == State::Playing {
+                    self.check_overlay()?;
+                }
+            }
+            YouTubeReport(report) => {
+                self.handle_youtube_report(report).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle a report from an overlay connected to the YouTube player.
+    async fn handle_youtube_report(&mut self, report: bus::YouTubeReport) -> Result<(), Error> {
+        let item = match self.song.read().as_ref() {
+            Some(song) => song.item.clone(),
+            None => return Ok(()),
+        };
+
+        // NB: reports about anything but the current video are stale.
+        match item.track_id {
+            TrackId::YouTube(ref id) if id == report.video_id() => (),
+            _ => return Ok(()),
+        }
+
+        match report {
+            bus::YouTubeReport::Error { code, .. } => {
+                let reason = match code {
+                    2 => "the video id is invalid",
+                    5 => "the video can't be played in the browser",
+                    100 => "the video has been removed or is private",
+                    101 | 150 => "the video doesn't allow embedding",
+                    _ => "the video failed to play",
+                };
+
+                log::warn!("{}: {} (code: {})", item.track_id, reason, code);
+                self.bus.broadcast(Event::Unplayable(item, reason));
+                self.command(Command::Skip(Source::Manual)).await?;
+            }
+            bus::YouTubeReport::State { state, elapsed, .. } => {
+                let elapsed = Duration::from_millis((elapsed.max(0f64) * 1000f64) as u64);
+
+                match (state, self.state) {
+                    (bus::YouTubeState::Ended, State::Playing) => {
+                        self.end_of_track().await?;
+                    }
+                    (bus::YouTubeState::Playing, State::Playing)
+                    | (bus::YouTubeState::Buffering, State::Playing) => {
+                        self.sync_elapsed(elapsed);
+                    }
+                    // NB: the player is playing even though we are paused.
+                    (bus::YouTubeState::Playing, _) => {
+                        self.youtube_player.pause();
+                    }
+                    _ => (),
                 }
             }
         }
 
         Ok(())
     }
+
+    /// Synchronize the elapsed time of the current song with what the player reports.
+    fn sync_elapsed(&mut self, elapsed: Duration) {
+        let mut song = self.song.write();
+
+        let song = match song.as_mut() {
+            Some(song) => song,
+            None => return,
+        };
+
+        let current = song.elapsed();
+
+        let diff = match current > elapsed {
+            true => current - elapsed,
+            false => elapsed - current,
+        };
+
+        if diff < YOUTUBE_SYNC_THRESHOLD {
+            return;
+        }
+
+        log::trace!(
+            "{}: resynchronizing elapsed time from {:?} to {:?}",
+            song.item.track_id,
+            current,
+            elapsed
+        );
+
+        song.sync_elapsed(elapsed);
+        self.timeout = Some(timer::Delay::new(song.deadline()));
+        self.global_bus
+            .send(bus::Global::song_progress(Some(&*song)));
+    }
+
+    /// Suspend playback of a YouTube song if no overlay is connected to play it.
+    fn check_overlay(&mut self) -> Result<(), Error> {
+        if self.player != PlayerKind::YouTube || self.youtube_player.is_connected() {
+            return Ok(());
+        }
+
+        log::info!("no youtube player connected, pausing playback");
+        self.overlay_paused = true;
+        self.suspend()
+    }
+
+    /// Pause playback since the player it depends on is not available.
+    ///
+    /// MUST NOT be called when self.song is locked.
+    fn suspend(&mut self) -> Result<(), Error> {
+        self.timeout = None;
+        self.state = State::Paused;
+
+        let mut song = self.song.write();
+
+        if let Some(song) = song.as_mut() {
+            song.pause();
+        }
+
+        self.notify_song_change(song.as_ref())?;
+        Ok(())
+    }
+}
+
+/// Find a ban which applies to the artist or channel of the given track.
+fn find_ban(bans: &db::SongBans, track: &Track) -> Option<(db::BanKind, Arc<db::SongBan>)> {
+    match *track {
+        Track::Spotify { ref track } => track.artists.iter().find_map(|a| {
+            bans.get(db::BanKind::Artist, &a.id)
+                .map(|ban| (db::BanKind::Artist, ban))
+        }),
+        Track::YouTube { ref video } => {
+            let snippet = video.snippet.as_ref()?;
+            let ban = bans.get(db::BanKind::Channel, &snippet.channel_id)?;
+            Some((db::BanKind::Channel, ban))
+        }
+        Track::Local { .. } => None,
+    }
 }
 
 /// Converts a track into an Item.
+///
+/// Spotify tracks are not looked up if playback is simulated, so that it works offline.
 async fn convert_item(
     spotify: Arc<api::Spotify>,
     youtube: Arc<api::YouTube>,
+    library: self::library::Library,
+    simulated: bool,
     user: Option<String>,
     track_id: TrackId,
     duration_override: Option<Duration>,
 ) -> Result<Item, Error> {
     let (track, duration) = match track_id {
+        TrackId::Spotify(ref id) if simulated => {
+            let (track, duration) = self::simulated::track(id)?;
+            (Track::Spotify { track }, duration)
+        }
         TrackId::Spotify(ref id) => {
             let track_id_string = id.to_base62();
             let track = spotify.track(track_id_string).await?;
@@ -2117,7 +3930,7 @@ async fn convert_item(
             let video_info = youtube.get_video_info(id.clone());
             let video = youtube
                 .clone()
-                .videos_by_id(id.clone(), String::from("contentDetails,snippet"));
+                .videos_by_id(id.clone(), String::from("contentDetails,snippet,status"));
             let (video_info, video) = future::try_join(video_info, video).await?;
 
             log::trace!("info = {:?}", video_info);
@@ -2135,6 +3948,15 @@ async fn convert_item(
             let duration = str::parse::<PtDuration>(&content_details.duration)?;
             (Track::YouTube { video }, duration.into_std())
         }
+        TrackId::Local(ref id) => {
+            let track = match library.get(id) {
+                Some(track) => track,
+                None => bail!("no local track found for id `{}`", id),
+            };
+
+            let duration = track.duration();
+            (Track::Local { track }, duration)
+        }
     };
 
     let duration = match duration_override {
@@ -2149,3 +3971,120 @@ async fn convert_item(
         duration,
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_queue_limits, duration_quota_wait, expiry_action, fair_position, most_generous,
+        AddTrackError, ExpiryAction,
+    };
+    use crate::track_id::TrackId;
+    use std::time::Duration;
+
+    #[test]
+    fn test_expiry_action() {
+        let m = |m: u64| Duration::from_secs(m * 60);
+        let (skip, move_down) = (ExpiryAction::Skip, ExpiryAction::MoveDown);
+
+        assert_eq!(None, expiry_action(m(5), m(10), skip, false, 3));
+        assert_eq!(Some(skip), expiry_action(m(10), m(10), skip, false, 3));
+        assert_eq!(
+            Some(move_down),
+            expiry_action(m(15), m(10), move_down, false, 3)
+        );
+        // NB: requests are only moved down once.
+        assert_eq!(Some(skip), expiry_action(m(15), m(10), move_down, true, 3));
+        // NB: nothing to move the request behind.
+        assert_eq!(None, expiry_action(m(15), m(10), move_down, false, 0));
+        assert_eq!(Some(skip), expiry_action(m(15), m(10), skip, false, 0));
+    }
+
+    #[test]
+    fn test_check_queue_limits() {
+        let a = "youtube:video:a".parse::<TrackId>().expect("track id");
+        let b = "youtube:video:b".parse::<TrackId>().expect("track id");
+        let c = "youtube:video:c".parse::<TrackId>().expect("track id");
+
+        let queue = vec![(&a, Some("alice")), (&b, Some("bob"))];
+        let pending = vec![(&c, Some("alice"))];
+
+        match check_queue_limits(queue.clone(), pending.clone(), &b, "alice", None) {
+            Err(AddTrackError::QueueContainsTrack(1)) => (),
+            _ => panic!("expected track to be in queue"),
+        }
+
+        match check_queue_limits(queue.clone(), pending.clone(), &c, "bob", None) {
+            Err(AddTrackError::PendingContainsTrack(0)) => (),
+            _ => panic!("expected track to be pending"),
+        }
+
+        // NB: the approved request is no longer pending, but the track is queued.
+        match check_queue_limits(queue.clone(), vec![], &a, "alice", None) {
+            Err(AddTrackError::QueueContainsTrack(0)) => (),
+            _ => panic!("expected track to be in queue"),
+        }
+
+        match check_queue_limits(queue.clone(), vec![], &c, "alice", Some((2, 5))) {
+            Err(AddTrackError::QueueFull) => (),
+            _ => panic!("expected queue to be full"),
+        }
+
+        // NB: pending requests count towards the limit of the user.
+        match check_queue_limits(vec![], pending.clone(), &a, "alice", Some((5, 1))) {
+            Err(AddTrackError::TooManyUserTracks(1)) => (),
+            _ => panic!("expected too many user tracks"),
+        }
+
+        assert!(check_queue_limits(queue, vec![], &c, "alice", Some((3, 2))).is_ok());
+    }
+
+    #[test]
+    fn test_most_generous() {
+        assert_eq!(3, most_generous(3, vec![]));
+        // NB: roles without a limit fall back to the limit of everyone.
+        assert_eq!(3, most_generous(3, vec![0]));
+        assert_eq!(5, most_generous(3, vec![0, 5]));
+        assert_eq!(2, most_generous(3, vec![2]));
+        assert_eq!(5, most_generous(0, vec![5]));
+        assert_eq!(0, most_generous(0, vec![0, 5]));
+        assert_eq!(0, most_generous(0, vec![]));
+    }
+
+    #[test]
+    fn test_fair_position() {
+        let weight = |_: Option<&str>| 1;
+
+        assert_eq!(0, fair_position(vec![], Some("a"), weight));
+
+        let q = vec![Some("a"), Some("a"), Some("a")];
+        assert_eq!(1, fair_position(q.clone(), Some("b"), weight));
+        assert_eq!(3, fair_position(q, Some("a"), weight));
+
+        let q = vec![Some("a"), Some("b"), Some("a"), Some("a")];
+        assert_eq!(2, fair_position(q.clone(), Some("c"), weight));
+        assert_eq!(3, fair_position(q, Some("b"), weight));
+
+        let weight = |user: Option<&str>| if user == Some("b") { 2 } else { 1 };
+        let q = vec![Some("a"), Some("b"), Some("a")];
+        assert_eq!(2, fair_position(q, Some("b"), weight));
+    }
+
+    #[test]
+    fn test_duration_quota_wait() {
+        let m = |m: u64| Duration::from_secs(m * 60);
+
+        assert_eq!(None, duration_quota_wait(m(1), vec![], m(5), m(15)));
+        assert_eq!(Some(None), duration_quota_wait(m(1), vec![], m(20), m(15)));
+
+        let q = vec![(true, m(5)), (false, m(3)), (true, m(5))];
+        assert_eq!(None, duration_quota_wait(m(1), q.clone(), m(5), m(15)));
+        assert_eq!(
+            Some(Some(m(6))),
+            duration_quota_wait(m(1), q.clone(), m(6), m(15))
+        );
+        assert_eq!(
+            Some(Some(m(14))),
+            duration_quota_wait(m(1), q, m(11), m(15))
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//! Fallback which picks songs based on what has been requested and liked in chat.
+
+use super::{connect::ConnectPlayer, convert_item, find_ban, library::Library, Item};
+use crate::{api, db, spotify_id::SpotifyId, track_id::TrackId, utils};
+use failure::Error;
+use hashbrown::HashSet;
+use parking_lot::RwLock;
+use rand::seq::SliceRandom;
+use std::sync::Arc;
+
+/// Number of songs picked at a time.
+const BATCH_SIZE: usize = 10;
+/// Number of the most requested and most liked tracks to pick from.
+const POPULAR_LIMIT: i64 = 50;
+/// Number of recent requests to seed recommendations with. Spotify permits at most five.
+const SEED_LIMIT: i64 = 5;
+
+/// Picks songs from the request history of the channel, mixed with Spotify recommendations
+/// seeded by recent requests.
+#[derive(Clone)]
+pub struct AutoDj {
+    pub spotify: Arc<api::Spotify>,
+    pub connect_player: ConnectPlayer,
+    pub youtube: Arc<api::YouTube>,
+    pub library: Library,
+    pub stats: db::SongStats,
+    pub history: db::History,
+    pub bans: db::SongBans,
+    /// Tracks which have been played within this duration are not picked.
+    pub duplicate_duration: Arc<RwLock<utils::Duration>>,
+    /// Number of recommendations to mix in.
+    pub recommendations: Arc<RwLock<u32>>,
+}
+
+impl AutoDj {
+    /// Pick the next batch of songs to play.
+    pub async fn items(&self) -> Result<Vec<Arc<Item>>, Error> {
+        let mut candidates = Vec::new();
+
+        for c in self.stats.most_requested(POPULAR_LIMIT)? {
+            candidates.push(c.track_id);
+        }
+
+        for c in self.stats.most_liked(POPULAR_LIMIT)? {
+            // NB: count is likes minus dislikes.
+            if c.count > 0 {
+                candidates.push(c.track_id);
+            }
+        }
+
+        candidates.shuffle(&mut rand::thread_rng());
+        let recommended = self.recommended().await;
+        let candidates = mix(candidates, recommended);
+
+        let duplicate_duration = self.duplicate_duration.read().clone();
+        let mut items = Vec::new();
+
+        for track_id in candidates {
+            if self
+                .bans
+                .get(db::BanKind::Track, &track_id.to_string())
+                .is_some()
+            {
+                continue;
+            }
+
+            if !duplicate_duration.is_empty()
+                && self
+                    .history
+                    .played_within(&track_id, duplicate_duration.clone())?
+            {
+                continue;
+            }
+
+            let item = convert_item(
+                self.spotify.clone(),
+                self.youtube.clone(),
+                self.library.clone(),
+                self.connect_player.is_simulated(),
+                None,
+                track_id.clone(),
+                None,
+            )
+            .await;
+
+            let item = match item {
+                Ok(item) => item,
+                Err(e) => {
+                    log::warn!("{}: failed to load track for auto-dj: {}", track_id, e);
+                    continue;
+                }
+            };
+
+            if find_ban(&self.bans, &item.track).is_some() {
+                continue;
+            }
+
+            items.push(Arc::new(item));
+        }
+
+        items.shuffle(&mut rand::thread_rng());
+        Ok(items)
+    }
+
+    /// Get Spotify recommendations seeded by recently requested tracks.
+    async fn recommended(&self) -> Vec<TrackId> {
+        let limit = *self.recommendations.read();
+
+        if limit == 0 {
+            return Vec::new();
+        }
+
+        let seeds = match self.stats.recent_requests(SEED_LIMIT) {
+            Ok(seeds) => seeds,
+            Err(e) => {
+                log_err!(e, "failed to get recent requests");
+                return Vec::new();
+            }
+        };
+
+        let seeds = seeds
+            .into_iter()
+            .filter_map(|track_id| match track_id {
+                TrackId::Spotify(id) => Some(id.to_base62()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if seeds.is_empty() {
+            return Vec::new();
+        }
+
+        let ids = match self.spotify.recommendations(seeds, limit).await {
+            Ok(ids) => ids,
+            Err(e) => {
+                log_err!(e, "failed to get recommendations");
+                return Vec::new();
+            }
+        };
+
+        ids.into_iter()
+            .filter_map(|id| SpotifyId::from_base62(&id).ok().map(TrackId::Spotify))
+            .collect()
+    }
+}
+
+/// Mix popular tracks with recommendations into a batch of candidates to pick from.
+///
+/// Duplicates are removed, and popular tracks make room for recommendations but always fill at
+/// least half of the batch.
+fn mix(mut popular: Vec<TrackId>, mut recommended: Vec<TrackId>) -> Vec<TrackId> {
+    let mut seen = HashSet::new();
+    popular.retain(|track_id| seen.insert(track_id.clone()));
+    recommended.retain(|track_id| seen.insert(track_id.clone()));
+
+    let room = BATCH_SIZE.saturating_sub(recommended.len());
+    popular.truncate(room.max(BATCH_SIZE / 2));
+    popular.extend(recommended);
+    popular
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mix, BATCH_SIZE};
+    use crate::track_id::TrackId;
+
+    fn tracks(prefix: &str, n: usize) -> Vec<TrackId> {
+        (0..n)
+            .map(|i| TrackId::YouTube(format!("{}{}", prefix, i)))
+            .collect()
+    }
+
+    #[test]
+    fn test_mix_popular() {
+        let popular = tracks("p", 20);
+        let mut candidates = popular.clone();
+        candidates.insert(1, popular[0].clone());
+
+        let mixed = mix(candidates, vec![]);
+        assert_eq!(&popular[..BATCH_SIZE], &mixed[..]);
+    }
+
+    #[test]
+    fn test_mix_recommended() {
+        let popular = tracks("p", 20);
+        let mut recommended = tracks("r", 3);
+        // NB: recommendations which are already popular are dropped.
+        recommended.push(popular[0].clone());
+
+        let mixed = mix(popular.clone(), recommended.clone());
+        assert_eq!(BATCH_SIZE, mixed.len());
+        assert_eq!(&popular[..BATCH_SIZE - 3], &mixed[..BATCH_SIZE - 3]);
+        assert_eq!(&recommended[..3], &mixed[BATCH_SIZE - 3..]);
+    }
+
+    #[test]
+    fn test_mix_many_recommended() {
+        let popular = tracks("p", 20);
+        let recommended = tracks("r", 20);
+
+        // NB: popular tracks always fill at least half of the batch.
+        let mixed = mix(popular.clone(), recommended.clone());
+        assert_eq!(BATCH_SIZE / 2 + 20, mixed.len());
+        assert_eq!(&popular[..BATCH_SIZE / 2], &mixed[..BATCH_SIZE / 2]);
+        assert_eq!(&recommended[..], &mixed[BATCH_SIZE / 2..]);
+    }
+}
@@ -1,4 +1,15 @@
-use crate::{api, player, prelude::*, settings::Settings, track_id::SpotifyId, utils::Futures};
+use crate::{
+    api,
+    player::{
+        self,
+        simulated::{self, Backend, SimulatedPlayer},
+    },
+    prelude::*,
+    settings::Settings,
+    timer,
+    track_id::SpotifyId,
+    utils::Futures,
+};
 use failure::{bail, Error};
 use parking_lot::RwLock;
 use std::sync::Arc;
@@ -8,6 +19,24 @@ use std::{
     time::Duration,
 };
 
+/// How often the availability of the device is checked.
+const DEVICE_HEALTH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Health of the device used for playback.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "status")]
+pub enum DeviceHealth {
+    /// The configured device is reachable.
+    #[serde(rename = "available")]
+    Available { device: String },
+    /// The configured device is not reachable, so a fallback device is used instead.
+    #[serde(rename = "fallback")]
+    Fallback { device: String },
+    /// Neither the configured device nor any fallback device is reachable.
+    #[serde(rename = "unavailable")]
+    Unavailable,
+}
+
 /// Setup a player.
 pub fn setup(
     futures: &mut Futures,
@@ -21,7 +50,13 @@ pub fn setup(
         .stream::<api::spotify::Device>("device")
         .optional()?;
 
+    let (mut backend_stream, backend) = settings.stream("backend").or_default()?;
+    let (mut fallbacks_stream, mut fallbacks) = settings
+        .stream::<Vec<String>>("device-fallbacks")
+        .or_default()?;
+
     let device = Arc::new(RwLock::new(device));
+    let backend = Arc::new(RwLock::new(backend));
 
     let mut scaled_volume = (volume * volume_scale) / 100u32;
     let volume = Arc::new(RwLock::new(volume));
@@ -35,6 +70,9 @@ pub fn setup(
         device: device.clone(),
         settings: settings.clone(),
         volume: volume.clone(),
+        backend: backend.clone(),
+        simulated: SimulatedPlayer::default(),
+        fallback: Default::default(),
     };
 
     // Configuration interface.
@@ -49,8 +87,96 @@ pub fn setup(
     let future = async move {
         player.volume_update(scaled_volume).await?;
 
+        let mut health_interval = timer::Interval::new_interval(DEVICE_HEALTH_INTERVAL);
+        let mut health = None;
+
+        // results of health checks, together with the device that was used before the check.
+        let (health_tx, mut health_rx) = mpsc::unbounded();
+        let mut checking = false;
+
         loop {
             futures::select! {
+                _ = health_interval.select_next_some() => {
+                    // NB: only one health check is in flight at a time.
+                    if checking {
+                        continue;
+                    }
+
+                    let before = player.device_id();
+
+                    // NB: the simulated device is always reachable.
+                    if player.is_simulated() {
+                        let update = Some(DeviceHealth::Available {
+                            device: String::from(simulated::DEVICE),
+                        });
+
+                        if let Err(_) = health_tx.unbounded_send((before, Ok(update))) {
+                            bail!("failed to send health check result");
+                        }
+
+                        continue;
+                    }
+
+                    checking = true;
+
+                    let player = player.clone();
+                    let fallbacks = fallbacks.clone();
+                    let health_tx = health_tx.clone();
+
+                    // NB: listing devices can take a while, so it shouldn't hold up changes to
+                    // the configuration.
+                    let future = async move {
+                        let result = player.check_health(&fallbacks).await;
+
+                        if let Err(_) = health_tx.unbounded_send((before, result)) {
+                            log::warn!("failed to send health check result");
+                        }
+                    };
+
+                    tokio::spawn(Compat::new(Box::pin(future.unit_error())));
+                }
+                (before, result) = health_rx.select_next_some() => {
+                    checking = false;
+
+                    let update = match result {
+                        Ok(update) => update,
+                        Err(e) => {
+                            log_err!(e, "failed to check health of spotify device");
+                            continue;
+                        }
+                    };
+
+                    if health == update {
+                        continue;
+                    }
+
+                    log::info!("spotify device health changed: {:?}", update);
+
+                    // NB: playback only needs to move over if it switched between two reachable
+                    // devices, the player resumes by itself when a device becomes reachable.
+                    let device_changed = before != player.device_id()
+                        && health != Some(DeviceHealth::Unavailable)
+                        && update != Some(DeviceHealth::Unavailable);
+
+                    health = update.clone();
+
+                    if let Some(update) = update {
+                        let event = ConfigurationEvent::DeviceHealth(update);
+
+                        if let Err(_) = config_tx.unbounded_send(event) {
+                            bail!("failed to send configuration event");
+                        }
+                    }
+
+                    if device_changed {
+                        if let Err(_) = config_tx.unbounded_send(ConfigurationEvent::DeviceChanged) {
+                            bail!("failed to send configuration event");
+                        }
+                    }
+                }
+                update = fallbacks_stream.select_next_some() => {
+                    fallbacks = update;
+                }
                 update = device_stream.select_next_some() => {
                     *device.write() = update;
 
@@ -58,6 +184,21 @@ pub fn setup(
                         bail!("failed to send configuration event");
                     }
                 }
+                update = backend_stream.select_next_some() => {
+                    log::info!("switching spotify backend to {:?}", update);
+
+                    // NB: stop the simulated player so that it doesn't linger in the background.
+                    if update != Backend::Simulated {
+                        player.simulated.stop();
+                    }
+
+                    *backend.write() = update;
+
+                    // NB: the new backend needs to pick up where the old one left off.
+                    if let Err(_) = config_tx.unbounded_send(ConfigurationEvent::DeviceChanged) {
+                        bail!("failed to send configuration event");
+                    }
+                }
                 update = volume_scale_stream.select_next_some() => {
                     volume_scale = update;
                     scaled_volume = (*volume.read() * volume_scale) / 100u32;
@@ -108,13 +249,84 @@ pub struct ConnectPlayer {
     settings: Settings,
     /// Current volume for this player.
     volume: Arc<RwLock<u32>>,
+    /// Backend used for playback.
+    backend: Arc<RwLock<Backend>>,
+    /// Stand-in player used with the simulated backend.
+    simulated: SimulatedPlayer,
+    /// Fallback device used while the configured device is not reachable.
+    fallback: Arc<RwLock<Option<api::spotify::Device>>>,
 }
 
 impl ConnectPlayer {
+    /// Test if playback is simulated instead of going through a device.
+    pub fn is_simulated(&self) -> bool {
+        *self.backend.read() == Backend::Simulated
+    }
+
+    /// Get the id of the device that playback should go to.
+    fn device_id(&self) -> Option<String> {
+        if let Some(fallback) = self.fallback.read().as_ref() {
+            return Some(fallback.id.to_string());
+        }
+
+        self.device.read().as_ref().map(|d| d.id.to_string())
+    }
+
+    /// Check if the configured device is reachable, and pick a fallback device if it isn't.
+    ///
+    /// Returns `None` if there is nothing to check since no devices are configured.
+    async fn check_health(&self, fallbacks: &[String]) -> Result<Option<DeviceHealth>, Error> {
+        let device = self.device.read().clone();
+
+        if device.is_none() && fallbacks.is_empty() {
+            *self.fallback.write() = None;
+            return Ok(None);
+        }
+
+        let devices = self.spotify.my_player_devices().await?;
+
+        if let Some(device) = device {
+            if devices.iter().any(|d| d.id == device.id) {
+                *self.fallback.write() = None;
+                return Ok(Some(DeviceHealth::Available {
+                    device: device.name,
+                }));
+            }
+        }
+
+        // NB: fallbacks are matched by name or id, in the order they are configured.
+        for fallback in fallbacks {
+            let fallback = fallback.trim();
+
+            if let Some(d) = devices
+                .iter()
+                .find(|d| d.id == fallback || d.name == fallback)
+            {
+                *self.fallback.write() = Some(d.clone());
+                return Ok(Some(DeviceHealth::Fallback {
+                    device: d.name.clone(),
+                }));
+            }
+        }
+
+        *self.fallback.write() = None;
+        Ok(Some(DeviceHealth::Unavailable))
+    }
+
     /// Play the specified song.
-    pub async fn play(&self, elapsed: Duration, id: SpotifyId) -> Result<(), CommandError> {
+    pub async fn play(
+        &self,
+        elapsed: Duration,
+        duration: Duration,
+        id: SpotifyId,
+    ) -> Result<(), CommandError> {
+        if self.is_simulated() {
+            self.simulated.play(elapsed, duration, id);
+            return Ok(());
+        }
+
         let track_uri = format!("spotify:track:{}", id.to_base62());
-        let device_id = self.device.read().as_ref().map(|d| d.id.to_string());
+        let device_id = self.device_id();
 
         let result = self
             .spotify
@@ -125,12 +337,22 @@ impl ConnectPlayer {
     }
 
     pub async fn pause(&self) -> Result<(), CommandError> {
-        let device_id = self.device.read().as_ref().map(|d| d.id.to_string());
+        if self.is_simulated() {
+            self.simulated.pause();
+            return Ok(());
+        }
+
+        let device_id = self.device_id();
         CommandError::handle(self.spotify.me_player_pause(device_id).await, "pause")
     }
 
     pub async fn stop(&self) -> Result<(), CommandError> {
-        let device_id = self.device.read().as_ref().map(|d| d.id.to_string());
+        if self.is_simulated() {
+            self.simulated.stop();
+            return Ok(());
+        }
+
+        let device_id = self.device_id();
         CommandError::handle(self.spotify.me_player_pause(device_id).await, "stop")
     }
 
@@ -149,8 +371,13 @@ impl ConnectPlayer {
     }
 
     async fn volume_update(&self, volume: u32) -> Result<(), CommandError> {
+        // NB: the volume is kept by this player, so there's nothing to update.
+        if self.is_simulated() {
+            return Ok(());
+        }
+
         let volume = (volume as f32) / 100f32;
-        let device_id = self.device.read().as_ref().map(|d| d.id.to_string());
+        let device_id = self.device_id();
         CommandError::handle(
             self.spotify.me_player_volume(device_id, volume).await,
             "volume",
@@ -175,6 +402,11 @@ impl Stream for ConnectStream {
                     ConfigurationEvent::DeviceChanged => {
                         return Poll::Ready(Some(Ok(DeviceChanged)));
                     }
+                    ConfigurationEvent::DeviceHealth(health) => {
+                        return Poll::Ready(Some(Ok(player::IntegrationEvent::DeviceHealth(
+                            health,
+                        ))));
+                    }
                 }
             }
 
@@ -192,6 +424,8 @@ impl stream::FusedStream for ConnectStream {
 pub enum ConfigurationEvent {
     /// Indicate that the current device has been changed.
     DeviceChanged,
+    /// Indicate that the health of the device has changed.
+    DeviceHealth(DeviceHealth),
 }
 
 #[derive(Clone)]
@@ -0,0 +1,361 @@
+//! Index of local audio files which can be requested and played through the overlay.
+
+use crate::{prelude::*, settings::Settings, utils::Futures};
+use failure::{format_err, Error};
+use hashbrown::HashMap;
+use parking_lot::RwLock;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tokio_threadpool::ThreadPool;
+
+/// Extensions of files which are indexed.
+const EXTENSIONS: &[&str] = &["mp3", "flac"];
+
+/// Setup the library of local audio files.
+pub fn setup(futures: &mut Futures, settings: Settings) -> Result<Library, Error> {
+    let (mut directory_stream, directory) = settings.stream::<PathBuf>("directory").optional()?;
+
+    let library = Library::default();
+    let thread_pool = Arc::new(ThreadPool::new());
+
+    // NB: index synchronously on startup, since queued songs might refer to the library.
+    if let Err(e) = library.index(directory) {
+        log_err!(e, "failed to index local library");
+    }
+
+    let returned_library = library.clone();
+
+    let future = async move {
+        while let Some(directory) = directory_stream.next().await {
+            let library = library.clone();
+
+            let future = thread_pool.spawn_handle(future01::lazy(move || library.index(directory)));
+
+            if let Err(e) = future.compat().await {
+                log_err!(e, "failed to index local library");
+            }
+        }
+
+        Ok(())
+    };
+
+    futures.push(future.boxed());
+    Ok(returned_library)
+}
+
+/// A single track in the local library.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct LocalTrack {
+    /// The id of the track, which is its path relative to the library directory.
+    pub id: String,
+    /// Title of the track, or the name of the file if it isn't tagged.
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// Duration of the track in milliseconds.
+    pub duration_ms: u64,
+}
+
+impl LocalTrack {
+    /// Get the duration of the track.
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.duration_ms)
+    }
+
+    /// Test how well the track matches the given, lowercased search terms.
+    ///
+    /// Returns `None` unless all terms match.
+    fn score(&self, terms: &[String]) -> Option<usize> {
+        let title = self.title.to_lowercase();
+        let artist = self
+            .artist
+            .as_ref()
+            .map(|s| s.to_lowercase())
+            .unwrap_or_default();
+        let album = self
+            .album
+            .as_ref()
+            .map(|s| s.to_lowercase())
+            .unwrap_or_default();
+        let id = self.id.to_lowercase();
+
+        let mut score = 0;
+
+        for term in terms {
+            if title.contains(term.as_str()) {
+                score += 3;
+            } else if artist.contains(term.as_str()) {
+                score += 2;
+            } else if album.contains(term.as_str()) || id.contains(term.as_str()) {
+                score += 1;
+            } else {
+                return None;
+            }
+        }
+
+        Some(score)
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    /// The directory being indexed.
+    root: Option<PathBuf>,
+    /// Indexed tracks by id.
+    tracks: HashMap<String, LocalTrack>,
+}
+
+/// The library of local audio files.
+#[derive(Clone, Default)]
+pub struct Library {
+    inner: Arc<RwLock<Inner>>,
+}
+
+impl Library {
+    /// Index all audio files in the given directory, replacing the current index.
+    pub fn index(&self, root: Option<PathBuf>) -> Result<(), Error> {
+        let root = match root {
+            Some(root) => root,
+            None => {
+                *self.inner.write() = Inner::default();
+                return Ok(());
+            }
+        };
+
+        let mut tracks = HashMap::new();
+        index_dir(&root, &root, &mut tracks)?;
+
+        log::info!(
+            "Indexed {} local tracks in {}",
+            tracks.len(),
+            root.display()
+        );
+
+        let mut inner = self.inner.write();
+        inner.root = Some(root);
+        inner.tracks = tracks;
+        Ok(())
+    }
+
+    /// Get the track with the given id.
+    pub fn get(&self, id: &str) -> Option<LocalTrack> {
+        self.inner.read().tracks.get(id).cloned()
+    }
+
+    /// Get the path to the file of the track with the given id.
+    ///
+    /// Only files which are part of the index are available.
+    pub fn path(&self, id: &str) -> Option<PathBuf> {
+        let inner = self.inner.read();
+        let root = inner.root.as_ref()?;
+        let track = inner.tracks.get(id)?;
+        Some(root.join(&track.id))
+    }
+
+    /// Search the library for the track best matching the given query.
+    pub fn search(&self, q: &str) -> Option<LocalTrack> {
+        let terms = q
+            .split_whitespace()
+            .map(|t| t.to_lowercase())
+            .collect::<Vec<_>>();
+
+        if terms.is_empty() {
+            return None;
+        }
+
+        let inner = self.inner.read();
+
+        let mut best = None::<(usize, &LocalTrack)>;
+
+        for track in inner.tracks.values() {
+            let score = match track.score(&terms) {
+                Some(score) => score,
+                None => continue,
+            };
+
+            // NB: break ties by id so that searches are stable.
+            best = match best {
+                Some((s, t)) if s > score || (s == score && t.id < track.id) => Some((s, t)),
+                _ => Some((score, track)),
+            };
+        }
+
+        best.map(|(_, track)| track.clone())
+    }
+}
+
+/// Recursively index all audio files in the given directory.
+fn index_dir(
+    root: &Path,
+    dir: &Path,
+    tracks: &mut HashMap<String, LocalTrack>,
+) -> Result<(), Error> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            index_dir(root, &path, tracks)?;
+            continue;
+        }
+
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+
+        let extension = match extension {
+            Some(ref extension) if EXTENSIONS.contains(&extension.as_str()) => extension,
+            _ => continue,
+        };
+
+        match read_track(root, &path, extension) {
+            Ok(track) => {
+                tracks.insert(track.id.clone(), track);
+            }
+            Err(e) => {
+                log::warn!("failed to read local track: {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Read information about a single track from its tags.
+fn read_track(root: &Path, path: &Path, extension: &str) -> Result<LocalTrack, Error> {
+    let id = path
+        .strip_prefix(root)?
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| id.clone());
+
+    let (title, artist, album, duration) = match extension {
+        "mp3" => {
+            let duration = mp3_duration::from_path(path)
+                .map_err(|e| format_err!("failed to read duration: {}", e))?;
+
+            // NB: untagged files are fine, they're identified by file name.
+            match id3::Tag::read_from_path(path) {
+                Ok(tag) => (
+                    tag.title().map(String::from),
+                    tag.artist().map(String::from),
+                    tag.album().map(String::from),
+                    duration,
+                ),
+                Err(_) => (None, None, None, duration),
+            }
+        }
+        "flac" => {
+            let tag = metaflac::Tag::read_from_path(path)?;
+
+            let info = tag
+                .get_streaminfo()
+                .ok_or_else(|| format_err!("missing stream info"))?;
+
+            let duration = match info.sample_rate {
+                0 => Duration::default(),
+                rate => Duration::from_millis(info.total_samples * 1000 / u64::from(rate)),
+            };
+
+            let comments = tag.vorbis_comments();
+            let first = |v: Option<&Vec<String>>| v.and_then(|v| v.first().cloned());
+
+            (
+                comments.and_then(|c| first(c.title())),
+                comments.and_then(|c| first(c.artist())),
+                comments.and_then(|c| first(c.album())),
+                duration,
+            )
+        }
+        extension => return Err(format_err!("unsupported extension: {}", extension)),
+    };
+
+    Ok(LocalTrack {
+        id,
+        title: title.unwrap_or(stem),
+        artist,
+        album,
+        duration_ms: duration.as_millis() as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Library, LocalTrack};
+
+    fn track(id: &str, title: &str, artist: Option<&str>, album: Option<&str>) -> LocalTrack {
+        LocalTrack {
+            id: id.to_string(),
+            title: title.to_string(),
+            artist: artist.map(String::from),
+            album: album.map(String::from),
+            duration_ms: 0,
+        }
+    }
+
+    fn terms(q: &str) -> Vec<String> {
+        q.split_whitespace().map(|t| t.to_lowercase()).collect()
+    }
+
+    fn library(tracks: Vec<LocalTrack>) -> Library {
+        let library = Library::default();
+
+        {
+            let mut inner = library.inner.write();
+
+            for track in tracks {
+                inner.tracks.insert(track.id.clone(), track);
+            }
+        }
+
+        library
+    }
+
+    #[test]
+    fn test_score() {
+        let t = track(
+            "queen/rock.mp3",
+            "We Will Rock You",
+            Some("Queen"),
+            Some("News of the World"),
+        );
+
+        assert_eq!(Some(3), t.score(&terms("rock")));
+        assert_eq!(Some(5), t.score(&terms("Rock queen")));
+        assert_eq!(Some(1), t.score(&terms("world")));
+        // NB: the id is matched as well, which is the path of the file.
+        assert_eq!(Some(1), t.score(&terms("mp3")));
+        assert_eq!(None, t.score(&terms("rock abba")));
+    }
+
+    #[test]
+    fn test_search() {
+        let library = library(vec![
+            track("b.mp3", "Rock Song", None, None),
+            track("a.mp3", "Rock Song", None, None),
+            track("c.mp3", "Other", Some("Rock Band"), None),
+        ]);
+
+        assert_eq!(None, library.search("   ").map(|t| t.id));
+        assert_eq!(None, library.search("jazz").map(|t| t.id));
+        // NB: ties are broken by id.
+        assert_eq!(
+            Some("a.mp3"),
+            library.search("rock").as_ref().map(|t| t.id.as_str())
+        );
+        assert_eq!(
+            Some("c.mp3"),
+            library.search("rock other").as_ref().map(|t| t.id.as_str())
+        );
+    }
+}
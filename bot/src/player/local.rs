@@ -0,0 +1,97 @@
+use crate::{bus, player, prelude::*, settings::Settings, utils::Futures};
+use failure::Error;
+use parking_lot::RwLock;
+use std::{sync::Arc, time::Duration};
+
+/// Setup a player for local files.
+pub fn setup(
+    futures: &mut Futures,
+    bus: Arc<bus::Bus<bus::Local>>,
+    settings: Settings,
+) -> Result<LocalPlayer, failure::Error> {
+    let (mut volume_scale_stream, mut volume_scale) =
+        settings.stream("volume-scale").or_with(100)?;
+    let (mut volume_stream, volume) = settings.stream("volume").or_with(50)?;
+    let mut scaled_volume = (volume * volume_scale) / 100u32;
+    let volume = Arc::new(RwLock::new(volume));
+
+    let player = LocalPlayer {
+        bus,
+        settings,
+        volume: volume.clone(),
+    };
+
+    let returned_player = player.clone();
+
+    let future = async move {
+        player.volume_update(scaled_volume);
+
+        loop {
+            futures::select! {
+                update = volume_scale_stream.select_next_some() => {
+                    volume_scale = update;
+                    scaled_volume = (*volume.read() * volume_scale) / 100u32;
+                    player.volume_update(scaled_volume);
+                }
+                update = volume_stream.select_next_some() => {
+                    *volume.write() = update;
+                    scaled_volume = (*volume.read() * volume_scale) / 100u32;
+                    player.volume_update(scaled_volume);
+                }
+            }
+        }
+    };
+
+    futures.push(future.boxed());
+    Ok(returned_player)
+}
+
+#[derive(Clone)]
+pub struct LocalPlayer {
+    bus: Arc<bus::Bus<bus::Local>>,
+    settings: Settings,
+    volume: Arc<RwLock<u32>>,
+}
+
+impl LocalPlayer {
+    /// Update playback information.
+    pub fn tick(&self, elapsed: Duration, duration: Duration, id: String) {
+        self.play(elapsed, duration, id);
+    }
+
+    pub fn play(&self, elapsed: Duration, duration: Duration, id: String) {
+        let event = bus::LocalEvent::Play {
+            id,
+            elapsed: elapsed.as_secs(),
+            duration: duration.as_secs(),
+        };
+
+        self.bus.send(bus::Local::LocalCurrent { event });
+    }
+
+    pub fn pause(&self) {
+        let event = bus::LocalEvent::Pause;
+        self.bus.send(bus::Local::LocalCurrent { event });
+    }
+
+    pub fn stop(&self) {
+        let event = bus::LocalEvent::Stop;
+        self.bus.send(bus::Local::LocalCurrent { event });
+    }
+
+    pub fn volume(&self, modify: player::ModifyVolume) -> Result<u32, Error> {
+        let mut volume = self.volume.write();
+        let update = modify.apply(*volume);
+        *volume = update;
+        self.settings.set("volume", update)?;
+        Ok(update)
+    }
+
+    pub fn current_volume(&self) -> u32 {
+        *self.volume.read()
+    }
+
+    fn volume_update(&self, volume: u32) {
+        self.bus.send(bus::Local::LocalVolume { volume });
+    }
+}
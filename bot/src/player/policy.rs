@@ -0,0 +1,234 @@
+use crate::{db, player::Track, settings, utils};
+use failure::Error;
+use parking_lot::RwLock;
+use std::{fmt, sync::Arc};
+
+/// Reason why a track was rejected by the content policy.
+#[derive(Debug, Clone)]
+pub enum Violation {
+    /// Spotify track flagged as explicit.
+    Explicit,
+    /// YouTube video which is age restricted.
+    AgeRestricted,
+    /// YouTube video which can't be embedded in the overlay.
+    NotEmbeddable,
+    /// YouTube video which is not available in the given region.
+    RegionBlocked(String),
+    /// The title of the track contains a bad word.
+    BadWord(String),
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Violation::Explicit => "it is explicit".fmt(fmt),
+            Violation::AgeRestricted => "it is age restricted".fmt(fmt),
+            Violation::NotEmbeddable => "it can't be played on stream".fmt(fmt),
+            Violation::RegionBlocked(ref region) => {
+                write!(fmt, "it is not available in this region ({})", region)
+            }
+            Violation::BadWord(..) => "its title contains a bad word".fmt(fmt),
+        }
+    }
+}
+
+/// Content policy for requested tracks.
+#[derive(Clone)]
+pub struct Policy {
+    explicit: Arc<RwLock<bool>>,
+    age_restricted: Arc<RwLock<bool>>,
+    not_embeddable: Arc<RwLock<bool>>,
+    region: Arc<RwLock<String>>,
+    bad_words_enabled: Arc<RwLock<bool>>,
+    bad_words: db::Words,
+}
+
+impl Policy {
+    /// Build a new policy from the given settings.
+    pub fn build(vars: &mut settings::Vars, bad_words: db::Words) -> Result<Policy, Error> {
+        Ok(Policy {
+            explicit: vars.var("explicit", false)?,
+            age_restricted: vars.var("age-restricted", false)?,
+            not_embeddable: vars.var("not-embeddable", true)?,
+            region: vars.var("region", String::new())?,
+            bad_words_enabled: vars.var("bad-words", false)?,
+            bad_words,
+        })
+    }
+
+    /// Check the given track against the policy.
+    pub fn check(&self, track: &Track) -> Option<Violation> {
+        match *track {
+            Track::Spotify { ref track } => {
+                if *self.explicit.read() && track.explicit {
+                    return Some(Violation::Explicit);
+                }
+            }
+            Track::YouTube { ref video } => {
+                let content_details = video.content_details.as_ref();
+
+                if *self.age_restricted.read() {
+                    let age_restricted = content_details
+                        .and_then(|c| c.content_rating.as_ref())
+                        .map(|r| r.is_age_restricted())
+                        .unwrap_or_default();
+
+                    if age_restricted {
+                        return Some(Violation::AgeRestricted);
+                    }
+                }
+
+                if *self.not_embeddable.read() {
+                    let embeddable = video.status.as_ref().map(|s| s.embeddable).unwrap_or(true);
+
+                    if !embeddable {
+                        return Some(Violation::NotEmbeddable);
+                    }
+                }
+
+                let region = self.region.read();
+                let region = region.trim();
+
+                if !region.is_empty() {
+                    let available = content_details
+                        .and_then(|c| c.region_restriction.as_ref())
+                        .map(|r| r.is_available_in(region))
+                        .unwrap_or(true);
+
+                    if !available {
+                        return Some(Violation::RegionBlocked(region.to_uppercase()));
+                    }
+                }
+            }
+            // NB: the local library is curated by the streamer.
+            Track::Local { .. } => (),
+        }
+
+        if *self.bad_words_enabled.read() {
+            let tester = self.bad_words.tester();
+            let name = track.name();
+
+            for word in utils::TrimmedWords::new(&name) {
+                if let Some(word) = tester.test(word) {
+                    return Some(Violation::BadWord(word.word.to_string()));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Policy, Violation};
+    use crate::{db, player::Track};
+    use parking_lot::RwLock;
+    use std::sync::Arc;
+
+    fn policy() -> Policy {
+        let db = db::Database::memory().expect("database");
+        let bad_words = db::Words::load(db).expect("bad words");
+        bad_words.edit("heck", None).expect("bad word");
+
+        Policy {
+            explicit: Arc::new(RwLock::new(false)),
+            age_restricted: Arc::new(RwLock::new(false)),
+            not_embeddable: Arc::new(RwLock::new(false)),
+            region: Arc::new(RwLock::new(String::new())),
+            bad_words_enabled: Arc::new(RwLock::new(false)),
+            bad_words,
+        }
+    }
+
+    fn video(title: &str, details: serde_json::Value) -> Track {
+        let mut video = serde_json::json!({
+            "kind": "youtube#video",
+            "etag": "etag",
+            "id": "id",
+            "snippet": {"channelId": "channel", "title": title},
+            "status": {"embeddable": true},
+        });
+
+        for (key, value) in details.as_object().expect("object") {
+            video[key] = value.clone();
+        }
+
+        serde_json::from_value(serde_json::json!({"type": "youtube", "video": video}))
+            .expect("track")
+    }
+
+    #[test]
+    fn test_age_restricted() {
+        let policy = policy();
+        let track = video(
+            "Song",
+            serde_json::json!({
+                "contentDetails": {
+                    "duration": "PT3M",
+                    "contentRating": {"ytRating": "ytAgeRestricted"},
+                },
+            }),
+        );
+
+        assert!(policy.check(&track).is_none());
+        *policy.age_restricted.write() = true;
+
+        match policy.check(&track) {
+            Some(Violation::AgeRestricted) => (),
+            other => panic!("expected age restriction, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_not_embeddable() {
+        let policy = policy();
+        let track = video("Song", serde_json::json!({"status": {"embeddable": false}}));
+
+        assert!(policy.check(&track).is_none());
+        *policy.not_embeddable.write() = true;
+
+        match policy.check(&track) {
+            Some(Violation::NotEmbeddable) => (),
+            other => panic!("expected not embeddable, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_region() {
+        let policy = policy();
+        let track = video(
+            "Song",
+            serde_json::json!({
+                "contentDetails": {"duration": "PT3M", "regionRestriction": {"blocked": ["de"]}},
+            }),
+        );
+
+        *policy.region.write() = String::from("se");
+        assert!(policy.check(&track).is_none());
+        *policy.region.write() = String::from("de");
+
+        match policy.check(&track) {
+            Some(Violation::RegionBlocked(ref region)) if region == "DE" => (),
+            other => panic!("expected region block, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_bad_words() {
+        let policy = policy();
+        let track = video("What the Heck", serde_json::json!({}));
+
+        assert!(policy.check(&track).is_none());
+        *policy.bad_words_enabled.write() = true;
+
+        match policy.check(&track) {
+            Some(Violation::BadWord(ref word)) if word == "heck" => (),
+            other => panic!("expected bad word, got: {:?}", other),
+        }
+
+        assert!(policy
+            .check(&video("Nice Song", serde_json::json!({})))
+            .is_none());
+    }
+}
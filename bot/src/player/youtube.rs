@@ -8,7 +8,15 @@ pub fn setup(
     futures: &mut Futures,
     bus: Arc<bus::Bus<bus::YouTube>>,
     settings: Settings,
-) -> Result<YouTubePlayer, failure::Error> {
+) -> Result<
+    (
+        mpsc::UnboundedReceiver<player::IntegrationEvent>,
+        YouTubePlayer,
+    ),
+    failure::Error,
+> {
+    let (events_tx, events_rx) = mpsc::unbounded();
+
     let (mut volume_scale_stream, mut volume_scale) =
         settings.stream("volume-scale").or_with(100)?;
     let (mut volume_stream, volume) = settings.stream("volume").or_with(50)?;
@@ -19,6 +27,8 @@ pub fn setup(
         bus,
         settings,
         volume: volume.clone(),
+        connected: Default::default(),
+        events_tx,
     };
 
     let returned_player = player.clone();
@@ -43,7 +53,7 @@ pub fn setup(
     };
 
     futures.push(future.boxed());
-    Ok(returned_player)
+    Ok((events_rx, returned_player))
 }
 
 #[derive(Clone)]
@@ -51,6 +61,10 @@ pub struct YouTubePlayer {
     bus: Arc<bus::Bus<bus::YouTube>>,
     settings: Settings,
     volume: Arc<RwLock<u32>>,
+    /// Number of overlays connected to the player.
+    connected: Arc<RwLock<usize>>,
+    /// Events reported by connected overlays.
+    events_tx: mpsc::UnboundedSender<player::IntegrationEvent>,
 }
 
 impl YouTubePlayer {
@@ -97,6 +111,42 @@ impl YouTubePlayer {
         *self.volume.read()
     }
 
+    /// Test if any overlay is connected to the player.
+    pub fn is_connected(&self) -> bool {
+        *self.connected.read() > 0
+    }
+
+    /// Indicate that an overlay connected.
+    pub fn connected(&self) {
+        let mut connected = self.connected.write();
+        *connected += 1;
+
+        if *connected == 1 {
+            self.send(player::IntegrationEvent::YouTubeConnected(true));
+        }
+    }
+
+    /// Indicate that an overlay disconnected.
+    pub fn disconnected(&self) {
+        let mut connected = self.connected.write();
+        *connected = connected.saturating_sub(1);
+
+        if *connected == 0 {
+            self.send(player::IntegrationEvent::YouTubeConnected(false));
+        }
+    }
+
+    /// Pass on a report from a connected overlay.
+    pub fn report(&self, report: bus::YouTubeReport) {
+        self.send(player::IntegrationEvent::YouTubeReport(report));
+    }
+
+    fn send(&self, event: player::IntegrationEvent) {
+        if let Err(_) = self.events_tx.unbounded_send(event) {
+            log::error!("failed to send youtube event: player is closed");
+        }
+    }
+
     fn volume_update(&self, volume: u32) {
         self.bus.send(bus::YouTube::YouTubeVolume { volume });
     }
@@ -9,6 +9,8 @@ use parking_lot::RwLock;
 use std::{borrow::Cow, fmt, marker, pin::Pin, sync::Arc};
 
 const SEPARATOR: char = '/';
+/// Prefix of settings which only apply to a single channel, like `channel/<name>/song/enabled`.
+const CHANNEL_PREFIX: &'static str = "channel";
 
 type EventSender = mpsc::UnboundedSender<Event<serde_json::Value>>;
 type Subscriptions = Arc<RwLock<HashMap<String, Vec<EventSender>>>>;
@@ -90,12 +92,28 @@ impl Schema {
 
     /// Lookup the given type by key.
     pub fn lookup(&self, key: &str) -> Option<SchemaType> {
-        self.types.get(key).cloned()
+        self.get(key).cloned()
     }
 
     /// Test if schema contains the given key.
     pub fn contains(&self, key: &str) -> bool {
-        self.types.contains_key(key)
+        self.get(key).is_some()
+    }
+
+    /// Get the type of the given key.
+    ///
+    /// Keys scoped to a channel have the same type as the key they are scoped from.
+    fn get(&self, key: &str) -> Option<&SchemaType> {
+        if let Some(schema) = self.types.get(key) {
+            return Some(schema);
+        }
+
+        let mut it = key.splitn(3, SEPARATOR);
+
+        match (it.next(), it.next(), it.next()) {
+            (Some(CHANNEL_PREFIX), Some(_), Some(key)) => self.types.get(key),
+            _ => None,
+        }
     }
 }
 
@@ -111,6 +129,8 @@ pub struct Inner {
 #[derive(Clone)]
 pub struct Settings {
     scope: String,
+    /// Scope to read settings from when they are not set in this scope, like for channels.
+    fallback: Option<String>,
     inner: Arc<Inner>,
 }
 
@@ -118,6 +138,7 @@ impl Settings {
     pub fn new(db: db::Database, schema: Schema) -> Self {
         Self {
             scope: String::from(""),
+            fallback: None,
             inner: Arc::new(Inner {
                 db,
                 subscriptions: Default::default(),
@@ -170,7 +191,7 @@ impl Settings {
     /// Lookup the given schema.
     pub fn lookup(&self, key: &str) -> Option<&SchemaType> {
         let key = self.key(key);
-        self.inner.schema.types.get(key.as_ref())
+        self.inner.schema.get(key.as_ref())
     }
 
     /// Get a setting by prefix.
@@ -208,6 +229,7 @@ impl Settings {
             });
         }
 
+        self.channel_settings(&values, prefix.as_ref(), &mut settings)?;
         Ok(settings)
     }
 
@@ -218,14 +240,14 @@ impl Settings {
     where
         T: serde::Serialize + serde::de::DeserializeOwned,
     {
+        let value = self.get(key)?;
         let key = self.key(key);
 
-        let schema = match self.inner.schema.types.get(key.as_ref()) {
+        let schema = match self.inner.schema.get(key.as_ref()) {
             Some(schema) => schema,
             None => return Ok(None),
         };
 
-        let value = self.inner_get(&key)?;
         Ok(Some(SettingRef { schema, key, value }))
     }
 
@@ -236,12 +258,20 @@ impl Settings {
     }
 
     /// Get the value of the given key from the database.
+    ///
+    /// Falls back to the unscoped key for settings of a channel which haven't been set.
     pub fn get<T>(&self, key: &str) -> Result<Option<T>, Error>
     where
         T: serde::Serialize + serde::de::DeserializeOwned,
     {
-        let key = self.key(key);
-        self.inner_get(&key)
+        if let Some(value) = self.inner_get(&self.key(key))? {
+            return Ok(Some(value));
+        }
+
+        match self.fallback_key(key) {
+            Some(key) => self.inner_get(&key),
+            None => Ok(None),
+        }
     }
 
     /// Insert the given setting without sending an update notification to other components.
@@ -337,9 +367,38 @@ impl Settings {
             });
         }
 
+        self.channel_settings(&values, "", &mut settings)?;
         Ok(settings)
     }
 
+    /// Add settings which have been stored for a single channel and match the given prefix.
+    fn channel_settings(
+        &self,
+        values: &HashMap<String, String>,
+        prefix: &str,
+        settings: &mut Vec<Setting>,
+    ) -> Result<(), Error> {
+        for (key, value) in values {
+            if !key.starts_with(prefix) || self.inner.schema.types.contains_key(key) {
+                continue;
+            }
+
+            // NB: only matches keys scoped to a channel, since others are in the schema.
+            let schema = match self.inner.schema.get(key) {
+                Some(schema) => schema,
+                None => continue,
+            };
+
+            settings.push(Setting {
+                schema: schema.clone(),
+                key: key.to_string(),
+                value: serde_json::from_str(value)?,
+            });
+        }
+
+        Ok(())
+    }
+
     /// Clear the given setting. Returning `true` if it was removed.
     pub fn clear(&self, key: &str) -> Result<bool, Error> {
         use self::db::schema::settings::dsl;
@@ -356,33 +415,37 @@ impl Settings {
     /// Create a scoped setting.
     pub fn scoped(&self, s: &str) -> Settings {
         let mut scope = self.scope.clone();
+        push_scope(&mut scope, s);
 
-        for s in s.trim_matches(SEPARATOR).split('/') {
-            if s.is_empty() {
-                continue;
-            }
-
-            if !scope.is_empty() {
-                scope.push(SEPARATOR);
-            }
-
-            scope.push_str(s);
-        }
+        let fallback = self.fallback.clone().map(|mut fallback| {
+            push_scope(&mut fallback, s);
+            fallback
+        });
 
         Settings {
             scope,
+            fallback,
             inner: self.inner.clone(),
         }
     }
 
+    /// Create settings for a single channel.
+    ///
+    /// Settings which haven't been set for the channel fall back to the current settings, and
+    /// any defaults are stored in the current settings so that they apply to every channel.
+    pub fn channel(&self, name: &str) -> Settings {
+        let mut settings = self.scoped(CHANNEL_PREFIX).scoped(name);
+        settings.fallback = Some(self.scope.clone());
+        settings
+    }
+
     /// Initialize the value from the database.
     pub fn stream<'a, T>(&'a self, key: &str) -> StreamBuilder<'_, T> {
-        let key = self.key(key);
-
         StreamBuilder {
             settings: self,
             default_value: None,
-            key,
+            fallback: self.fallback_key(key),
+            key: self.key(key),
         }
     }
 
@@ -433,27 +496,55 @@ impl Settings {
     }
 
     /// Subscribe for events on the given key.
-    fn make_stream<T>(&self, key: &str, default: T) -> Stream<T>
+    fn make_stream<T>(
+        &self,
+        key: &str,
+        fallback: Option<&str>,
+        default: T,
+    ) -> Result<Stream<T>, Error>
     where
         T: Clone + serde::Serialize + serde::de::DeserializeOwned,
     {
-        Stream {
+        Ok(Stream {
             default,
-            option_stream: self.make_option_stream(key),
-        }
+            option_stream: self.make_option_stream(key, fallback)?,
+        })
     }
 
-    /// Subscribe for any events on the given key.
-    fn make_option_stream<T>(&self, key: &str) -> OptionStream<T>
+    /// Subscribe for any events on the given key, and on the key it falls back to.
+    fn make_option_stream<T>(
+        &self,
+        key: &str,
+        fallback: Option<&str>,
+    ) -> Result<OptionStream<T>, Error>
     where
         T: serde::Serialize + serde::de::DeserializeOwned,
     {
-        let (tx, rx) = mpsc::unbounded();
-
         if !self.inner.schema.contains(key) {
             panic!("no schema registered for key `{}`", key);
         }
 
+        let fallback = match fallback {
+            Some(fallback) => Some(Fallback {
+                rx: self.subscribe(fallback),
+                value: self.inner_get(fallback)?,
+                overridden: self.inner_get::<serde_json::Value>(key)?.is_some(),
+            }),
+            None => None,
+        };
+
+        Ok(OptionStream {
+            key: key.to_string(),
+            rx: self.subscribe(key),
+            fallback,
+            marker: marker::PhantomData,
+        })
+    }
+
+    /// Subscribe to events on the given key.
+    fn subscribe(&self, key: &str) -> mpsc::UnboundedReceiver<Event<serde_json::Value>> {
+        let (tx, rx) = mpsc::unbounded();
+
         {
             let mut subscriptions = self.inner.subscriptions.write();
             let values = subscriptions.entry(key.to_string()).or_default();
@@ -470,11 +561,7 @@ impl Settings {
             *values = update;
         }
 
-        OptionStream {
-            key: key.to_string(),
-            rx,
-            marker: marker::PhantomData,
-        }
+        rx
     }
 
     /// Try to send the specified event.
@@ -494,28 +581,56 @@ impl Settings {
 
     /// Construct a new key.
     fn key<'a>(&'a self, key: &str) -> Cow<'a, str> {
-        let key = key.trim_matches(SEPARATOR);
+        scoped_key(&self.scope, key)
+    }
+
+    /// Construct the key to fall back to, if any.
+    fn fallback_key<'a>(&'a self, key: &str) -> Option<Cow<'a, str>> {
+        self.fallback
+            .as_ref()
+            .map(|fallback| scoped_key(fallback, key))
+    }
+}
 
-        if key.is_empty() {
-            return Cow::Borrowed(&self.scope);
+/// Append the given path to a scope.
+fn push_scope(scope: &mut String, s: &str) {
+    for s in s.trim_matches(SEPARATOR).split('/') {
+        if s.is_empty() {
+            continue;
         }
 
-        if self.scope.is_empty() {
-            return Cow::Owned(key.to_string());
+        if !scope.is_empty() {
+            scope.push(SEPARATOR);
         }
 
-        let mut scope = self.scope.clone();
-        scope.push(SEPARATOR);
-        scope.push_str(key.trim_matches(SEPARATOR));
-        Cow::Owned(scope)
+        scope.push_str(s);
     }
 }
 
+/// Construct the key for the given scope.
+fn scoped_key<'a>(scope: &'a str, key: &str) -> Cow<'a, str> {
+    let key = key.trim_matches(SEPARATOR);
+
+    if key.is_empty() {
+        return Cow::Borrowed(scope);
+    }
+
+    if scope.is_empty() {
+        return Cow::Owned(key.to_string());
+    }
+
+    let mut scope = scope.to_string();
+    scope.push(SEPARATOR);
+    scope.push_str(key);
+    Cow::Owned(scope)
+}
+
 #[must_use = "Must consume to drive decide how to handle stream"]
 pub struct StreamBuilder<'a, T> {
     settings: &'a Settings,
     default_value: Option<T>,
     key: Cow<'a, str>,
+    fallback: Option<Cow<'a, str>>,
 }
 
 impl<'a, T> StreamBuilder<'a, T>
@@ -544,38 +659,57 @@ where
         T: Clone,
         F: FnOnce() -> T,
     {
-        let value = match self.settings.inner_get::<T>(&self.key)? {
+        let value = match self.get()? {
             Some(value) => value,
             None => {
                 let value = value();
-                self.settings.inner_set(&self.key, &value, true)?;
+                self.settings.inner_set(self.root_key(), &value, true)?;
                 value
             }
         };
 
-        let stream = self.settings.make_stream(&self.key, value.clone());
+        let fallback = self.fallback.as_ref().map(|k| k.as_ref());
+        let stream = self
+            .settings
+            .make_stream(&self.key, fallback, value.clone())?;
         Ok((stream, value))
     }
 
     /// Make the setting optional.
     pub fn optional(self) -> Result<(OptionStream<T>, Option<T>), Error> {
-        let value = self.settings.inner_get::<T>(&self.key)?;
-
-        let value = match value {
+        let value = match self.get()? {
             Some(value) => Some(value),
-            None => match self.default_value {
-                Some(value) => {
-                    self.settings.inner_set(&self.key, &value, true)?;
-                    Some(value)
+            None => {
+                if let Some(value) = self.default_value.as_ref() {
+                    self.settings.inner_set(self.root_key(), value, true)?;
                 }
-                None => None,
-            },
+
+                self.default_value
+            }
         };
 
-        let stream = self.settings.make_option_stream(&self.key);
+        let fallback = self.fallback.as_ref().map(|k| k.as_ref());
+        let stream = self.settings.make_option_stream(&self.key, fallback)?;
         Ok((stream, value))
     }
 
+    /// Get the current value of the setting, or the value it falls back to.
+    fn get(&self) -> Result<Option<T>, Error> {
+        if let Some(value) = self.settings.inner_get(&self.key)? {
+            return Ok(Some(value));
+        }
+
+        match self.fallback.as_ref() {
+            Some(fallback) => self.settings.inner_get(fallback),
+            None => Ok(None),
+        }
+    }
+
+    /// The key that defaults are stored in, which is the key that is fallen back to if present.
+    fn root_key(&self) -> &str {
+        self.fallback.as_ref().unwrap_or(&self.key)
+    }
+
     /// Add a potential fallback value when the type is optional.
     pub fn or(self, other: Option<T>) -> StreamBuilder<'a, T> {
         self.or_else(move || other)
@@ -630,6 +764,27 @@ impl Vars {
         Ok(value)
     }
 
+    /// Get a synchronized optional variable for the given configuration key.
+    pub fn optional<T>(&mut self, key: &str) -> Result<Arc<RwLock<Option<T>>>, Error>
+    where
+        T: 'static + Send + Sync + serde::Serialize + serde::de::DeserializeOwned + Unpin,
+    {
+        let (mut stream, value) = self.settings.stream(key).optional()?;
+        let value = Arc::new(RwLock::new(value));
+        let future_value = value.clone();
+
+        let future = async move {
+            while let Some(update) = stream.next().await {
+                *future_value.write() = update;
+            }
+
+            Ok(())
+        };
+
+        self.futures.push(future.boxed());
+        Ok(value)
+    }
+
     /// Drive the local variable set.
     pub fn run(self) -> impl Future<Output = Result<(), Error>> {
         let Vars { futures, .. } = self;
@@ -674,9 +829,35 @@ where
 pub struct OptionStream<T> {
     key: String,
     rx: mpsc::UnboundedReceiver<Event<serde_json::Value>>,
+    fallback: Option<Fallback>,
     marker: marker::PhantomData<T>,
 }
 
+impl<T> OptionStream<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    /// Parse an updated value.
+    fn parse(&self, value: Option<serde_json::Value>) -> Option<T> {
+        match serde_json::from_value(value?) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                log::warn!("bad value for key: {}: {}", self.key, e);
+                None
+            }
+        }
+    }
+}
+
+/// The key that a setting falls back to when it is not set.
+struct Fallback {
+    rx: mpsc::UnboundedReceiver<Event<serde_json::Value>>,
+    /// Current value of the key that is fallen back to.
+    value: Option<serde_json::Value>,
+    /// If the setting is set, which hides the value that is fallen back to.
+    overridden: bool,
+}
+
 impl<T> stream::FusedStream for OptionStream<T> {
     fn is_terminated(&self) -> bool {
         false
@@ -689,24 +870,55 @@ where
 {
     type Item = Option<T>;
 
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
-        let update = match ready!(Pin::new(&mut self.rx).poll_next(cx)) {
-            Some(update) => update,
-            None => return Poll::Ready(None),
-        };
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            if let Poll::Ready(update) = Pin::new(&mut this.rx).poll_next(cx) {
+                let update = match update {
+                    Some(update) => update,
+                    None => return Poll::Ready(None),
+                };
+
+                let value = match (update, this.fallback.as_mut()) {
+                    // NB: clearing the setting reveals the value that is fallen back to.
+                    (Event::Clear, Some(fallback)) => {
+                        fallback.overridden = false;
+                        fallback.value.clone()
+                    }
+                    (Event::Clear, None) => None,
+                    (Event::Set(value), fallback) => {
+                        if let Some(fallback) = fallback {
+                            fallback.overridden = true;
+                        }
 
-        let value = Some(match update {
-            Event::Clear => None,
-            Event::Set(value) => match serde_json::from_value(value) {
-                Ok(value) => Some(value),
-                Err(e) => {
-                    log::warn!("bad value for key: {}: {}", self.as_ref().key, e);
-                    None
-                }
-            },
-        });
+                        Some(value)
+                    }
+                };
 
-        Poll::Ready(value)
+                return Poll::Ready(Some(this.parse(value)));
+            }
+
+            let fallback = match this.fallback.as_mut() {
+                Some(fallback) => fallback,
+                None => return Poll::Pending,
+            };
+
+            let update = match ready!(Pin::new(&mut fallback.rx).poll_next(cx)) {
+                Some(update) => update,
+                None => return Poll::Ready(None),
+            };
+
+            fallback.value = match update {
+                Event::Clear => None,
+                Event::Set(value) => Some(value),
+            };
+
+            if !fallback.overridden {
+                let value = fallback.value.clone();
+                return Poll::Ready(Some(this.parse(value)));
+            }
+        }
     }
 }
 
@@ -893,3 +1105,108 @@ impl fmt::Display for Type {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Schema, Settings};
+    use crate::db;
+    use futures::{executor, StreamExt as _};
+
+    fn settings() -> Settings {
+        let db = db::Database::memory().expect("database");
+        Settings::new(db, Schema::load_static().expect("schema"))
+    }
+
+    #[test]
+    fn test_schema_get() {
+        let schema = Schema::load_static().expect("schema");
+
+        assert!(schema.get("irc/disabled-modules").is_some());
+        assert!(schema.get("channel/setbac/irc/disabled-modules").is_some());
+        assert!(schema.get("channel/setbac/irc/missing").is_none());
+        assert!(schema.get("channel/setbac").is_none());
+        assert!(schema.get("other/setbac/irc/disabled-modules").is_none());
+    }
+
+    #[test]
+    fn test_channel_get() {
+        let settings = settings();
+        let channel = settings.channel("setbac");
+
+        assert_eq!(
+            None,
+            channel.get::<Vec<String>>("irc/disabled-modules").unwrap()
+        );
+
+        settings.set("irc/disabled-modules", vec!["song"]).unwrap();
+        let modules = channel.get::<Vec<String>>("irc/disabled-modules").unwrap();
+        assert_eq!(Some(vec![String::from("song")]), modules);
+
+        // NB: scoping within a channel keeps falling back.
+        let modules = channel
+            .scoped("irc")
+            .get::<Vec<String>>("disabled-modules")
+            .unwrap();
+        assert_eq!(Some(vec![String::from("song")]), modules);
+
+        channel
+            .set("irc/disabled-modules", Vec::<String>::new())
+            .unwrap();
+        let modules = channel.get::<Vec<String>>("irc/disabled-modules").unwrap();
+        assert_eq!(Some(vec![]), modules);
+        let modules = settings.get::<Vec<String>>("irc/disabled-modules").unwrap();
+        assert_eq!(Some(vec![String::from("song")]), modules);
+    }
+
+    #[test]
+    fn test_channel_default() {
+        let settings = settings();
+        let channel = settings.channel("setbac").scoped("irc");
+
+        let (_, value) = channel
+            .stream("url-whitelist/enabled")
+            .or_with(false)
+            .unwrap();
+        assert_eq!(false, value);
+
+        // NB: the default is stored globally so that it applies to every channel.
+        assert_eq!(
+            Some(false),
+            settings.get::<bool>("irc/url-whitelist/enabled").unwrap()
+        );
+        assert!(!settings
+            .has("channel/setbac/irc/url-whitelist/enabled")
+            .unwrap());
+    }
+
+    #[test]
+    fn test_channel_stream() {
+        let settings = settings();
+        let channel = settings.channel("setbac");
+
+        let (mut stream, value) = channel
+            .stream::<String>("irc/startup-message")
+            .optional()
+            .unwrap();
+        assert_eq!(None, value);
+
+        let mut next = move || executor::block_on(stream.next()).expect("update");
+
+        settings.set("irc/startup-message", "a").unwrap();
+        assert_eq!(Some(String::from("a")), next());
+
+        channel.set("irc/startup-message", "b").unwrap();
+        assert_eq!(Some(String::from("b")), next());
+
+        // NB: global updates are hidden while the channel overrides the setting.
+        settings.set("irc/startup-message", "c").unwrap();
+        channel.clear("irc/startup-message").unwrap();
+        assert_eq!(Some(String::from("c")), next());
+
+        settings.set("irc/startup-message", "d").unwrap();
+        assert_eq!(Some(String::from("d")), next());
+
+        settings.clear("irc/startup-message").unwrap();
+        assert_eq!(None, next());
+    }
+}
@@ -1,5 +1,13 @@
-use crate::{player, template::Template, timer, utils};
-use std::{fs::File, path::PathBuf};
+use crate::{player, prelude::*, template::Template, timer, track_id::TrackId, utils};
+use failure::format_err;
+use parking_lot::Mutex;
+use reqwest::r#async::{Client, Decoder};
+use std::{
+    fs::{self, File},
+    io, mem,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 /// Configuration which has been deprecated.
 #[derive(Debug, Clone, Default, serde::Deserialize)]
@@ -14,6 +22,21 @@ pub struct Config {
     pub update_interval: utils::Duration,
 }
 
+/// A named text output, in addition to the one configured through `path` and `template`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Output {
+    pub name: String,
+    pub path: PathBuf,
+    pub template: Template,
+    /// Template to use when no song is playing, instead of the shared stopped template.
+    #[serde(
+        default,
+        rename = "stopped-template",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub stopped_template: Option<Template>,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct SongFileBuilder {
     pub enabled: bool,
@@ -21,6 +44,9 @@ pub struct SongFileBuilder {
     pub template: Option<Template>,
     pub stopped_template: Option<Template>,
     pub update_interval: utils::Duration,
+    pub outputs: Vec<Output>,
+    pub json_path: Option<PathBuf>,
+    pub art_path: Option<PathBuf>,
 }
 
 impl SongFileBuilder {
@@ -30,19 +56,47 @@ impl SongFileBuilder {
             return None;
         }
 
-        let path = self.path.as_ref()?;
-        let template = self.template.as_ref()?;
         let update_interval = match self.update_interval.is_empty() {
             false => &self.update_interval,
             true => return None,
         };
 
+        let mut outputs = Vec::new();
+
+        if let (Some(path), Some(template)) = (self.path.as_ref(), self.template.as_ref()) {
+            outputs.push(Output {
+                name: String::from("default"),
+                path: path.clone(),
+                template: template.clone(),
+                stopped_template: self.stopped_template.clone(),
+            });
+        }
+
+        for output in &self.outputs {
+            let mut output = output.clone();
+
+            if output.stopped_template.is_none() {
+                output.stopped_template = self.stopped_template.clone();
+            }
+
+            outputs.push(output);
+        }
+
+        let art = self.art_path.as_ref().map(|path| AlbumArt {
+            path: path.clone(),
+            current: Default::default(),
+        });
+
+        if outputs.is_empty() && self.json_path.is_none() && art.is_none() {
+            return None;
+        }
+
         let update_interval = timer::Interval::new_interval(update_interval.as_std());
 
         Some(SongFile {
-            path: path.clone(),
-            template: template.clone(),
-            stopped_template: self.stopped_template.clone(),
+            outputs,
+            json_path: self.json_path.clone(),
+            art,
             update_interval,
         })
     }
@@ -57,26 +111,57 @@ impl SongFileBuilder {
 }
 
 pub struct SongFile {
-    /// Path to render current song at.
-    pub path: PathBuf,
-    /// Message to render when a song is playing.
-    template: Template,
-    /// Message to show when no song is playing.
-    stopped_template: Option<Template>,
+    /// Text outputs to render the current song to.
+    outputs: Vec<Output>,
+    /// Path to write the current song to as JSON.
+    json_path: Option<PathBuf>,
+    /// Album art of the current song.
+    art: Option<AlbumArt>,
     /// Update frequency.
     pub update_interval: timer::Interval,
 }
 
 impl SongFile {
-    /// Either creates or truncates the current song file.
-    fn create_or_truncate(&self) -> Result<File, failure::Error> {
-        File::create(&self.path).map_err(Into::into)
+    /// Update all outputs with the given song, or blank them if no song is loaded.
+    pub fn update(&self, song: Option<&player::Song>, state: player::State) {
+        for output in &self.outputs {
+            let result = match song {
+                Some(song) => output.write(song, state),
+                None => output.blank(),
+            };
+
+            if let Err(e) = result {
+                log::warn!(
+                    "failed to write current song to `{}` output: {}: {}",
+                    output.name,
+                    output.path.display(),
+                    e
+                );
+            }
+        }
+
+        if let Some(path) = self.json_path.as_ref() {
+            if let Err(e) = write_json(path, song, state) {
+                log::warn!("failed to write current song: {}: {}", path.display(), e);
+            }
+        }
+
+        if let Some(art) = self.art.as_ref() {
+            art.update(song);
+        }
     }
 
-    /// Blank the current file.
-    pub fn blank(&self) -> Result<(), failure::Error> {
+    /// Clear the old log.
+    pub fn blank_log(&self) {
+        self.update(None, player::State::None);
+    }
+}
+
+impl Output {
+    /// Blank the output.
+    fn blank(&self) -> Result<(), failure::Error> {
         use std::io::Write as _;
-        let mut f = self.create_or_truncate()?;
+        let mut f = File::create(&self.path)?;
 
         if let Some(stopped_template) = self.stopped_template.as_ref() {
             write!(f, "{}", stopped_template)?;
@@ -87,18 +172,135 @@ impl SongFile {
         Ok(())
     }
 
-    /// Write the current song to a path.
-    pub fn write(&self, song: &player::Song, state: player::State) -> Result<(), failure::Error> {
-        let mut f = self.create_or_truncate()?;
+    /// Write the current song to the output.
+    fn write(&self, song: &player::Song, state: player::State) -> Result<(), failure::Error> {
+        let mut f = File::create(&self.path)?;
         let data = song.data(state)?;
         self.template.render(&mut f, &data)?;
         Ok(())
     }
+}
 
-    /// Clear the old log.
-    pub fn blank_log(&self) {
-        if let Err(e) = self.blank() {
-            log::error!("Failed to blank file: {}: {}", self.path.display(), e);
+/// Machine-readable information on the current song.
+#[derive(Debug, serde::Serialize)]
+struct Current<'a> {
+    state: &'static str,
+    track_id: Option<&'a TrackId>,
+    name: Option<String>,
+    artists: Option<String>,
+    user: Option<&'a str>,
+    /// Elapsed time in seconds.
+    elapsed: u64,
+    /// Duration in seconds.
+    duration: u64,
+}
+
+/// Write the current song as JSON to the given path.
+fn write_json(
+    path: &Path,
+    song: Option<&player::Song>,
+    state: player::State,
+) -> Result<(), failure::Error> {
+    let current = match song {
+        Some(song) => Current {
+            state: match state {
+                player::State::Playing => "playing",
+                _ => "paused",
+            },
+            track_id: Some(&song.item.track_id),
+            name: Some(song.item.track.name()),
+            artists: song.item.track.artists(),
+            user: song.item.user.as_ref().map(|s| s.as_str()),
+            elapsed: song.elapsed().as_secs(),
+            duration: song.duration().as_secs(),
+        },
+        None => Current {
+            state: "stopped",
+            track_id: None,
+            name: None,
+            artists: None,
+            user: None,
+            elapsed: 0,
+            duration: 0,
+        },
+    };
+
+    let f = File::create(path)?;
+    serde_json::to_writer_pretty(f, &current)?;
+    Ok(())
+}
+
+/// Album art of the current song, downloaded to a file.
+struct AlbumArt {
+    path: PathBuf,
+    /// URL of the album art which should currently be shown.
+    current: Arc<Mutex<Option<String>>>,
+}
+
+impl AlbumArt {
+    /// Download the album art of the given song if it has changed, or remove it if there is none.
+    fn update(&self, song: Option<&player::Song>) {
+        let url = song.and_then(|s| s.item.track.album_art());
+
+        {
+            let mut current = self.current.lock();
+
+            if *current == url {
+                return;
+            }
+
+            *current = url.clone();
         }
+
+        let path = self.path.clone();
+        let current = self.current.clone();
+
+        let future = async move {
+            let result = match url {
+                Some(url) => download(&path, url, &current).await,
+                None => remove(&path),
+            };
+
+            if let Err(e) = result {
+                log::warn!("failed to update album art: {}: {}", path.display(), e);
+            }
+        };
+
+        tokio::spawn(Compat::new(Box::pin(future.unit_error())));
+    }
+}
+
+/// Download the image at the given URL to the given path.
+async fn download(
+    path: &Path,
+    url: String,
+    current: &Mutex<Option<String>>,
+) -> Result<(), failure::Error> {
+    let mut res = Client::new().get(&url).send().compat().await?;
+
+    if !res.status().is_success() {
+        return Err(format_err!("bad response: {}", res.status()));
+    }
+
+    let body = mem::replace(res.body_mut(), Decoder::empty());
+    let body = body.compat().try_concat().await?;
+
+    // NB: the song might have changed while we were downloading.
+    if current.lock().as_ref() != Some(&url) {
+        return Ok(());
+    }
+
+    // NB: write to a temporary file so that the image is never seen partially written.
+    let temp = path.with_extension("download");
+    fs::write(&temp, body.as_ref())?;
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
+/// Remove the file at the given path, if it exists.
+fn remove(path: &Path) -> Result<(), failure::Error> {
+    match fs::remove_file(path) {
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => Ok(result?),
     }
 }
@@ -13,6 +13,15 @@ pub struct Data {
     pub subs_set: HashSet<String>,
 }
 
+/// The live state of the stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamState {
+    /// The stream went live.
+    Started,
+    /// The stream went offline.
+    Stopped,
+}
+
 #[derive(Debug, Clone)]
 pub struct StreamInfo {
     pub data: Arc<RwLock<Data>>,
@@ -25,7 +34,17 @@ impl StreamInfo {
     }
 
     /// Refresh the stream info.
-    pub async fn refresh<'a>(&'a self, twitch: &'a api::Twitch, streamer: &'a str) {
+    ///
+    /// Subscriptions are only fetched if `subscriptions` is set, since they are only visible to
+    /// the streamer themselves.
+    ///
+    /// Returns `false` if the stream info could not be refreshed.
+    pub async fn refresh<'a>(
+        &'a self,
+        twitch: &'a api::Twitch,
+        streamer: &'a str,
+        subscriptions: bool,
+    ) -> bool {
         let stream = twitch.stream_by_login(streamer);
 
         let streamer = async {
@@ -38,12 +57,21 @@ impl StreamInfo {
 
             let channel = twitch.channel_by_login(&streamer.id);
 
-            let subs = twitch
-                .stream_subscriptions(&streamer.id, vec![])
-                .try_concat();
+            let subs = async {
+                if !subscriptions {
+                    return Ok(None);
+                }
+
+                let subs = twitch
+                    .stream_subscriptions(&streamer.id, vec![])
+                    .try_concat()
+                    .await?;
+
+                Ok::<_, failure::Error>(Some(subs))
+            };
 
             let (subs, channel) = match future::try_join(subs, channel).await {
-                Ok((subs, channel)) => (Some(subs), Some(channel)),
+                Ok((subs, channel)) => (subs, Some(channel)),
                 Err(e) => {
                     log_err!(e, "failed to fetch subscriptions or channel");
                     (None, None)
@@ -59,7 +87,7 @@ impl StreamInfo {
             Ok(result) => result,
             Err(e) => {
                 log_err!(e, "failed to refresh stream info");
-                return;
+                return false;
             }
         };
 
@@ -80,16 +108,23 @@ impl StreamInfo {
                 .map(|s| s.user_name.to_lowercase())
                 .collect();
         }
+
+        true
     }
 }
 
 /// Set up a reward loop.
+///
+/// The returned receiver is notified about the initial live state of the stream, and every time it
+/// changes.
 pub fn setup<'a>(
     streamer: &'a str,
     interval: time::Duration,
     twitch: api::Twitch,
+    subscriptions: bool,
 ) -> (
     StreamInfo,
+    mpsc::UnboundedReceiver<StreamState>,
     impl Future<Output = Result<(), failure::Error>> + 'a,
 ) {
     let stream_info = StreamInfo {
@@ -99,16 +134,39 @@ pub fn setup<'a>(
     let mut interval = timer::Interval::new(time::Instant::now(), interval);
 
     let future_info = stream_info.clone();
+    let (state_tx, state_rx) = mpsc::unbounded();
 
     let future = async move {
         twitch.token.wait_until_ready().await?;
 
+        let mut is_live = None;
+
         while let Some(_) = interval.next().await.transpose()? {
-            future_info.refresh(&twitch, streamer).await;
+            if !future_info.refresh(&twitch, streamer, subscriptions).await {
+                continue;
+            }
+
+            let live = future_info.data.read().stream.is_some();
+
+            if is_live == Some(live) {
+                continue;
+            }
+
+            is_live = Some(live);
+
+            let state = match live {
+                true => StreamState::Started,
+                false => StreamState::Stopped,
+            };
+
+            log::info!("stream state changed: {:?}", state);
+
+            // NB: nothing might be listening.
+            let _ = state_tx.unbounded_send(state);
         }
 
         Ok(())
     };
 
-    (stream_info, future)
+    (stream_info, state_rx, future)
 }
@@ -13,6 +13,8 @@ pub enum TrackId {
     Spotify(SpotifyId),
     /// A YouTube track.
     YouTube(String),
+    /// A track in the local library, identified by its path in the library.
+    Local(String),
 }
 
 #[derive(Debug, err_derive::Error)]
@@ -31,7 +33,10 @@ pub enum ParseTrackIdError {
     /// Failed to parse an ID.
     #[error(display = "bad spotify track id (expected base62): {}", _0)]
     BadBase62(String),
-    #[error(display = "missing uri prefix, expected youtube:video:<id>, or spotify:track:<id>")]
+    #[error(
+        display = "missing uri prefix, expected youtube:video:<id>, spotify:track:<id>, \
+                       or local:track:<id>"
+    )]
     MissingUriPrefix,
 }
 
@@ -52,6 +57,11 @@ impl std::str::FromStr for TrackId {
             return Ok(TrackId::Spotify(id));
         }
 
+        if s.starts_with("local:track:") {
+            let id = s.trim_start_matches("local:track:");
+            return Ok(TrackId::Local(id.to_string()));
+        }
+
         Err(ParseTrackIdError::MissingUriPrefix)
     }
 }
@@ -61,6 +71,7 @@ impl fmt::Display for TrackId {
         match *self {
             TrackId::Spotify(ref id) => write!(fmt, "spotify:track:{}", id.to_base62()),
             TrackId::YouTube(ref id) => write!(fmt, "youtube:video:{}", id),
+            TrackId::Local(ref id) => write!(fmt, "local:track:{}", id),
         }
     }
 }
@@ -75,10 +86,13 @@ impl TrackId {
     }
 
     /// Get the URL for this track.
+    ///
+    /// Local tracks don't have a URL, so their URI is used instead.
     pub fn url(&self) -> String {
         match *self {
             TrackId::Spotify(ref id) => format!("{}/{}", SPOTIFY_URL, id.to_base62()),
             TrackId::YouTube(ref id) => format!("{}/{}", YOUTUBE_URL, id),
+            TrackId::Local(..) => self.to_string(),
         }
     }
 
@@ -168,6 +182,80 @@ impl TrackId {
     }
 }
 
+/// A collection of tracks that can be queued in bulk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlaylistId {
+    /// A Spotify playlist.
+    SpotifyPlaylist(String),
+    /// A Spotify album.
+    SpotifyAlbum(String),
+    /// A YouTube playlist.
+    YouTube(String),
+}
+
+#[derive(Debug, err_derive::Error)]
+pub enum ParsePlaylistIdError {
+    /// Requested a playlist from a host which is not supported.
+    #[error(display = "bad host, expected: open.spotify.com or youtube.com")]
+    BadHost(String),
+    #[error(display = "bad URL, expected: \
+                       https://open.spotify.com/playlist/<id>, \
+                       https://open.spotify.com/album/<id>, or \
+                       https://youtube.com/playlist?list=<id>")]
+    BadUrl(String),
+    #[error(display = "bad URI, expected: \
+                       spotify:playlist:<id>, spotify:album:<id>, or youtube:playlist:<id>")]
+    BadUri(String),
+}
+
+impl PlaylistId {
+    /// Parse a playlist from either a URI or a URL.
+    pub fn parse_with_urls(s: &str) -> Result<Self, ParsePlaylistIdError> {
+        if let Ok(url) = str::parse::<url::Url>(s) {
+            match url.host() {
+                Some(url::Host::Domain("open.spotify.com")) => {
+                    let parts = url.path().split("/").collect::<Vec<_>>();
+
+                    return match parts.as_slice() {
+                        &["", "playlist", id] | &["", "user", _, "playlist", id] => {
+                            Ok(PlaylistId::SpotifyPlaylist(id.to_string()))
+                        }
+                        &["", "album", id] => Ok(PlaylistId::SpotifyAlbum(id.to_string())),
+                        _ => Err(ParsePlaylistIdError::BadUrl(url.to_string())),
+                    };
+                }
+                Some(url::Host::Domain("youtube.com"))
+                | Some(url::Host::Domain("www.youtube.com")) => {
+                    // NB: both playlist pages and videos played as part of a playlist have the
+                    // `list` parameter.
+                    for (n, value) in url.query_pairs() {
+                        if n == "list" {
+                            return Ok(PlaylistId::YouTube(value.to_string()));
+                        }
+                    }
+
+                    return Err(ParsePlaylistIdError::BadUrl(url.to_string()));
+                }
+                Some(..) => {
+                    return Err(ParsePlaylistIdError::BadHost(url.to_string()));
+                }
+                None => (),
+            }
+        }
+
+        let parts = s.split(":").collect::<Vec<_>>();
+
+        match parts.as_slice() {
+            &["spotify", "playlist", id] | &["spotify", "user", _, "playlist", id] => {
+                Ok(PlaylistId::SpotifyPlaylist(id.to_string()))
+            }
+            &["spotify", "album", id] => Ok(PlaylistId::SpotifyAlbum(id.to_string())),
+            &["youtube", "playlist", id] => Ok(PlaylistId::YouTube(id.to_string())),
+            _ => Err(ParsePlaylistIdError::BadUri(s.to_string())),
+        }
+    }
+}
+
 impl<DB> diesel::serialize::ToSql<diesel::sql_types::Text, DB> for TrackId
 where
     DB: diesel::backend::Backend,
@@ -210,3 +298,63 @@ impl<'de> serde::Deserialize<'de> for TrackId {
         TrackId::parse_with_prefix_fallback(&s).map_err(serde::de::Error::custom)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::PlaylistId;
+
+    #[test]
+    fn test_parse_playlist_urls() {
+        let parse = |s| PlaylistId::parse_with_urls(s).ok();
+
+        assert_eq!(
+            Some(PlaylistId::SpotifyPlaylist(String::from("abc"))),
+            parse("https://open.spotify.com/playlist/abc")
+        );
+        assert_eq!(
+            Some(PlaylistId::SpotifyPlaylist(String::from("abc"))),
+            parse("https://open.spotify.com/user/someone/playlist/abc")
+        );
+        assert_eq!(
+            Some(PlaylistId::SpotifyAlbum(String::from("abc"))),
+            parse("https://open.spotify.com/album/abc")
+        );
+        assert_eq!(
+            Some(PlaylistId::YouTube(String::from("PLabc"))),
+            parse("https://www.youtube.com/playlist?list=PLabc")
+        );
+        assert_eq!(
+            Some(PlaylistId::YouTube(String::from("PLabc"))),
+            parse("https://youtube.com/watch?v=xyz&list=PLabc")
+        );
+
+        assert_eq!(None, parse("https://open.spotify.com/track/abc"));
+        assert_eq!(None, parse("https://youtube.com/watch?v=xyz"));
+        assert_eq!(None, parse("https://example.com/playlist/abc"));
+    }
+
+    #[test]
+    fn test_parse_playlist_uris() {
+        let parse = |s| PlaylistId::parse_with_urls(s).ok();
+
+        assert_eq!(
+            Some(PlaylistId::SpotifyPlaylist(String::from("abc"))),
+            parse("spotify:playlist:abc")
+        );
+        assert_eq!(
+            Some(PlaylistId::SpotifyPlaylist(String::from("abc"))),
+            parse("spotify:user:someone:playlist:abc")
+        );
+        assert_eq!(
+            Some(PlaylistId::SpotifyAlbum(String::from("abc"))),
+            parse("spotify:album:abc")
+        );
+        assert_eq!(
+            Some(PlaylistId::YouTube(String::from("PLabc"))),
+            parse("youtube:playlist:PLabc")
+        );
+
+        assert_eq!(None, parse("spotify:track:abc"));
+        assert_eq!(None, parse("abc"));
+    }
+}
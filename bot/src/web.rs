@@ -751,8 +751,57 @@ impl Auth {
 struct Api {
     player: Arc<RwLock<Option<player::Player>>>,
     after_streams: db::AfterStreams,
+    history: db::History,
+    song_stats: db::SongStats,
+    moderation_log: db::ModerationLog,
     db: db::Database,
     currency: Arc<RwLock<Option<Currency>>>,
+    channel: Arc<RwLock<Option<String>>>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct HistoryQuery {
+    #[serde(default)]
+    offset: Option<u32>,
+    #[serde(default)]
+    limit: Option<u32>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ModerationQuery {
+    /// Only list actions in the given channel.
+    #[serde(default)]
+    channel: Option<String>,
+    /// Only list actions against the given user.
+    #[serde(default)]
+    user: Option<String>,
+    #[serde(default)]
+    offset: Option<u32>,
+    #[serde(default)]
+    limit: Option<u32>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct LocalFileQuery {
+    /// The id of the local track.
+    id: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct StatsQuery {
+    #[serde(default)]
+    limit: Option<u32>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct DenyPending {
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct AddQueueItem {
+    track_id: TrackId,
 }
 
 #[derive(Clone, serde::Serialize, serde::Deserialize)]
@@ -844,6 +893,311 @@ impl Api {
         Ok(warp::reply::json(&after_streams))
     }
 
+    /// Get statistics over requested, rated, and played songs.
+    fn stats(&self, query: StatsQuery) -> Result<impl warp::Reply, failure::Error> {
+        let limit = u32::min(query.limit.unwrap_or(10), 100) as i64;
+
+        return Ok(warp::reply::json(&Stats {
+            most_requested: self.song_stats.most_requested(limit)?,
+            most_liked: self.song_stats.most_liked(limit)?,
+            most_played: self.song_stats.most_played(limit)?,
+            top_requesters: self.song_stats.top_requesters(limit)?,
+        }));
+
+        #[derive(serde::Serialize)]
+        struct Stats {
+            most_requested: Vec<db::TrackCount>,
+            most_liked: Vec<db::TrackCount>,
+            most_played: Vec<db::TrackCount>,
+            top_requesters: Vec<db::UserCount>,
+        }
+    }
+
+    /// Get a page of the song history, with the most recently played songs first.
+    fn history(&self, query: HistoryQuery) -> Result<impl warp::Reply, failure::Error> {
+        let limit = u32::min(query.limit.unwrap_or(50), 500) as i64;
+        let offset = query.offset.unwrap_or_default() as i64;
+
+        let entries = self.history.list(offset, limit)?;
+        let total = self.history.count()?;
+
+        return Ok(warp::reply::json(&History {
+            entries,
+            offset,
+            limit,
+            total,
+        }));
+
+        #[derive(serde::Serialize)]
+        struct History {
+            entries: Vec<db::HistoryEntry>,
+            offset: i64,
+            limit: i64,
+            total: i64,
+        }
+    }
+
+    /// Get a page of the moderation log, with the most recent actions first.
+    fn moderation(&self, query: ModerationQuery) -> Result<impl warp::Reply, failure::Error> {
+        let limit = u32::min(query.limit.unwrap_or(50), 500) as i64;
+        let offset = query.offset.unwrap_or_default() as i64;
+        let channel = query.channel.as_ref().map(String::as_str);
+        let user = query.user.as_ref().map(String::as_str);
+
+        let entries = self.moderation_log.list(channel, user, offset, limit)?;
+        let total = self.moderation_log.count(channel, user)?;
+
+        return Ok(warp::reply::json(&Moderation {
+            entries,
+            offset,
+            limit,
+            total,
+        }));
+
+        #[derive(serde::Serialize)]
+        struct Moderation {
+            entries: Vec<db::ModerationEntry>,
+            offset: i64,
+            limit: i64,
+            total: i64,
+        }
+    }
+
+    /// Get the player, or fail the request if it isn't configured.
+    fn player(&self) -> Result<player::Player, Error> {
+        match self.player.read().clone() {
+            Some(player) => Ok(player),
+            None => Err(Error::NotFound),
+        }
+    }
+
+    /// Serve the audio file of a track in the local library.
+    ///
+    /// The file is streamed, and a single range of bytes can be requested so that the overlay can
+    /// seek in it.
+    async fn local_file(
+        self,
+        query: LocalFileQuery,
+        range: Option<String>,
+    ) -> Result<impl warp::Reply, Error> {
+        use warp::http::{Response, StatusCode};
+
+        let path = match self.player()?.local_path(&query.id) {
+            Some(path) => path,
+            None => return Err(Error::NotFound),
+        };
+
+        let content_type = match path.extension().and_then(|e| e.to_str()) {
+            Some("flac") => "audio/flac",
+            _ => "audio/mpeg",
+        };
+
+        let file = tokio::fs::File::open(path)
+            .compat()
+            .await
+            .map_err(failure::Error::from)?;
+
+        let (file, metadata) = file
+            .metadata()
+            .compat()
+            .await
+            .map_err(failure::Error::from)?;
+        let len = metadata.len();
+
+        let range = match range.as_ref() {
+            Some(range) => parse_range(range, len),
+            None => Range::Full,
+        };
+
+        let (status, start, length) = match range {
+            Range::Full => (StatusCode::OK, 0, len),
+            Range::Partial(start, end) => (StatusCode::PARTIAL_CONTENT, start, end - start + 1),
+            Range::Unsatisfiable => {
+                let response = Response::builder()
+                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header("content-range", format!("bytes */{}", len))
+                    .body(hyper::Body::empty())
+                    .map_err(failure::Error::from)?;
+
+                return Ok(response);
+            }
+        };
+
+        let (file, _) = file
+            .seek(std::io::SeekFrom::Start(start))
+            .compat()
+            .await
+            .map_err(failure::Error::from)?;
+
+        let stream = tokio::codec::FramedRead::new(
+            std::io::Read::take(file, length),
+            tokio::codec::BytesCodec::new(),
+        );
+
+        let mut response = Response::builder();
+
+        response
+            .status(status)
+            .header("content-type", content_type)
+            .header("content-length", length)
+            .header("accept-ranges", "bytes");
+
+        if status == StatusCode::PARTIAL_CONTENT {
+            response.header(
+                "content-range",
+                format!("bytes {}-{}/{}", start, start + length - 1, len),
+            );
+        }
+
+        let response = response
+            .body(hyper::Body::wrap_stream(stream.map(|b| b.freeze())))
+            .map_err(failure::Error::from)?;
+
+        Ok(response)
+    }
+
+    /// List the songs in the queue, not including the current song.
+    fn queue(&self) -> Result<impl warp::Reply, Error> {
+        let player = self.player()?;
+        let mut items = Vec::new();
+
+        for (position, item) in player.queue().into_iter().enumerate() {
+            items.push(QueueItem {
+                position,
+                track_id: item.track_id.clone(),
+                name: item.track.name(),
+                artists: item.track.artists(),
+                user: item.user.clone(),
+                duration: item.duration.as_secs(),
+            });
+        }
+
+        return Ok(warp::reply::json(&items));
+
+        #[derive(serde::Serialize)]
+        struct QueueItem {
+            position: usize,
+            track_id: TrackId,
+            name: String,
+            artists: Option<String>,
+            user: Option<String>,
+            duration: u64,
+        }
+    }
+
+    /// Add a song to the queue on behalf of the streamer.
+    async fn queue_add(self, body: AddQueueItem) -> Result<impl warp::Reply, Error> {
+        let player = self.player()?;
+
+        let channel = match self.channel.read().clone() {
+            Some(channel) => channel,
+            None => return Err(Error::BadRequest),
+        };
+
+        let requester = player::Requester {
+            name: channel.trim_start_matches('#').to_string(),
+            is_moderator: true,
+            is_subscriber: false,
+            is_vip: false,
+            enforce_bans: false,
+        };
+
+        let result = player
+            .add_track(None, channel, requester, body.track_id, None, None, None)
+            .await;
+
+        // NB: requests on behalf of the streamer are made as a moderator, which are never held
+        // for approval.
+        return match result {
+            Ok((player::Added::Queue(position), _)) => Ok(warp::reply::json(&Added { position })),
+            Ok((player::Added::Pending(..), _)) => Err(Error::BadRequest),
+            Err(player::AddTrackError::Error(e)) => Err(Error::Custom(e)),
+            Err(_) => Err(Error::BadRequest),
+        };
+
+        #[derive(serde::Serialize)]
+        struct Added {
+            position: usize,
+        }
+    }
+
+    /// Get the songs which are waiting to be approved.
+    fn pending(&self) -> Result<impl warp::Reply, Error> {
+        let player = self.player()?;
+        let mut items = Vec::new();
+
+        for (position, item) in player.pending().into_iter().enumerate() {
+            items.push(PendingItem {
+                position,
+                track_id: item.track_id.clone(),
+                name: item.track.name(),
+                artists: item.track.artists(),
+                user: item.user.clone(),
+                duration: item.duration.as_secs(),
+            });
+        }
+
+        return Ok(warp::reply::json(&items));
+
+        #[derive(serde::Serialize)]
+        struct PendingItem {
+            position: usize,
+            track_id: TrackId,
+            name: String,
+            artists: Option<String>,
+            user: Option<String>,
+            duration: u64,
+        }
+    }
+
+    /// Approve the pending song at the given position, adding it to the queue.
+    async fn pending_approve(self, position: usize) -> Result<impl warp::Reply, Error> {
+        let selector = player::PendingSelector::Index(position);
+
+        match self.player()?.approve(selector).await {
+            Ok(Some(_)) => Ok(warp::reply::json(&EMPTY)),
+            Ok(None) => Err(Error::NotFound),
+            Err(player::AddTrackError::Error(e)) => Err(Error::Custom(e)),
+            Err(_) => Err(Error::BadRequest),
+        }
+    }
+
+    /// Deny the pending song at the given position.
+    fn pending_deny(&self, position: usize, body: DenyPending) -> Result<impl warp::Reply, Error> {
+        let selector = player::PendingSelector::Index(position);
+
+        match self.player()?.deny(selector, body.reason) {
+            Some(_) => Ok(warp::reply::json(&EMPTY)),
+            None => Err(Error::NotFound),
+        }
+    }
+
+    /// Remove the song at the given position in the queue.
+    fn queue_delete(&self, position: usize) -> Result<impl warp::Reply, Error> {
+        match self.player()?.remove_at(position)? {
+            Some(_) => Ok(warp::reply::json(&EMPTY)),
+            None => Err(Error::NotFound),
+        }
+    }
+
+    /// Remove all songs in the queue.
+    fn queue_purge(&self) -> Result<impl warp::Reply, Error> {
+        self.player()?.purge()?;
+        Ok(warp::reply::json(&EMPTY))
+    }
+
+    /// Reorder the queue to match the given order of track ids.
+    fn queue_order(&self, order: Vec<TrackId>) -> Result<impl warp::Reply, Error> {
+        let player = self.player()?;
+
+        if let Err(e) = player.reorder(&order) {
+            log::warn!("bad queue order: {}", e);
+            return Err(Error::BadRequest);
+        }
+
+        Ok(warp::reply::json(&EMPTY))
+    }
+
     /// Import balances.
     async fn import_balances(
         self,
@@ -872,11 +1226,83 @@ impl Api {
     }
 }
 
+/// Song bans endpoint.
+#[derive(Clone)]
+struct SongBans(db::SongBans);
+
+impl SongBans {
+    fn route(song_bans: db::SongBans) -> filters::BoxedFilter<(impl warp::Reply,)> {
+        let api = SongBans(song_bans);
+
+        let list = warp::get2()
+            .and(path!("song-bans").and(path::end()))
+            .and_then({
+                let api = api.clone();
+                move || api.list().map_err(warp::reject::custom)
+            });
+
+        let delete = warp::delete2()
+            .and(path!("song-bans" / db::BanKind / Fragment).and(path::end()))
+            .and_then({
+                let api = api.clone();
+                move |kind: db::BanKind, id: Fragment| {
+                    api.delete(kind, id.as_str()).map_err(warp::reject::custom)
+                }
+            });
+
+        let edit = warp::put2()
+            .and(path!("song-bans" / db::BanKind / Fragment).and(path::end()))
+            .and(body::json())
+            .and_then({
+                let api = api.clone();
+                move |kind: db::BanKind, id: Fragment, body: PutSongBan| {
+                    api.edit(kind, id.as_str(), body.reason)
+                        .map_err(warp::reject::custom)
+                }
+            });
+
+        return list.or(delete).or(edit).boxed();
+
+        #[derive(serde::Deserialize)]
+        pub struct PutSongBan {
+            #[serde(default)]
+            reason: Option<String>,
+        }
+    }
+
+    /// Get the list of all song bans.
+    fn list(&self) -> Result<impl warp::Reply, failure::Error> {
+        let bans = self.0.list();
+        Ok(warp::reply::json(&bans))
+    }
+
+    /// Ban the given id, replacing any existing ban.
+    fn edit(
+        &self,
+        kind: db::BanKind,
+        id: &str,
+        reason: Option<String>,
+    ) -> Result<impl warp::Reply, failure::Error> {
+        let id = kind.parse_id(id)?;
+        self.0
+            .insert(kind, &id, reason.as_ref().map(|s| s.as_str()), None)?;
+        Ok(warp::reply::json(&EMPTY))
+    }
+
+    /// Remove the ban for the given id.
+    fn delete(&self, kind: db::BanKind, id: &str) -> Result<impl warp::Reply, failure::Error> {
+        let id = kind.parse_id(id)?;
+        self.0.delete(kind, &id)?;
+        Ok(warp::reply::json(&EMPTY))
+    }
+}
+
 /// Set up the web endpoint.
 pub fn setup(
     web_root: Option<&Path>,
     global_bus: Arc<bus::Bus<bus::Global>>,
     youtube_bus: Arc<bus::Bus<bus::YouTube>>,
+    local_bus: Arc<bus::Bus<bus::Local>>,
     after_streams: db::AfterStreams,
     db: db::Database,
     settings: settings::Settings,
@@ -885,6 +1311,10 @@ pub fn setup(
     commands: db::Commands,
     promotions: db::Promotions,
     themes: db::Themes,
+    history: db::History,
+    song_bans: db::SongBans,
+    song_stats: db::SongStats,
+    moderation_log: db::ModerationLog,
     channel: Arc<RwLock<Option<String>>>,
     currency: Arc<RwLock<Option<Currency>>>,
 ) -> Result<
@@ -897,6 +1327,7 @@ pub fn setup(
     let addr: SocketAddr = str::parse(&format!("0.0.0.0:12345"))?;
 
     let player = Arc::new(RwLock::new(None));
+    let youtube_connections = Arc::new(RwLock::new(0));
     let token_callbacks = Arc::new(RwLock::new(HashMap::<String, ExpectedToken>::new()));
 
     let oauth2_redirect = Oauth2Redirect {
@@ -911,8 +1342,12 @@ pub fn setup(
     let api = Api {
         player: player.clone(),
         after_streams,
+        history,
+        song_stats,
+        moderation_log,
         db,
         currency,
+        channel: channel.clone(),
     };
 
     let api = {
@@ -957,6 +1392,145 @@ pub fn setup(
             }))
             .boxed();
 
+        let route = route
+            .or(warp::get2()
+                .and(path!("player" / "history"))
+                .and(warp::query::<HistoryQuery>())
+                .and_then({
+                    let api = api.clone();
+                    move |query: HistoryQuery| api.history(query).map_err(warp::reject::custom)
+                }))
+            .boxed();
+
+        let route = route
+            .or(warp::get2()
+                .and(path!("local" / "file"))
+                .and(warp::query::<LocalFileQuery>())
+                .and(warp::header::optional::<String>("range"))
+                .and_then({
+                    let api = api.clone();
+                    move |query: LocalFileQuery, range: Option<String>| {
+                        api.clone()
+                            .local_file(query, range)
+                            .map_err(warp::reject::custom)
+                            .boxed()
+                            .compat()
+                    }
+                }))
+            .boxed();
+
+        let route = route
+            .or(warp::get2()
+                .and(path!("player" / "stats"))
+                .and(warp::query::<StatsQuery>())
+                .and_then({
+                    let api = api.clone();
+                    move |query: StatsQuery| api.stats(query).map_err(warp::reject::custom)
+                }))
+            .boxed();
+
+        let route = route
+            .or(warp::get2()
+                .and(path!("moderation"))
+                .and(warp::query::<ModerationQuery>())
+                .and_then({
+                    let api = api.clone();
+                    move |query: ModerationQuery| {
+                        api.moderation(query).map_err(warp::reject::custom)
+                    }
+                }))
+            .boxed();
+
+        let route = route
+            .or(warp::get2()
+                .and(path!("player" / "queue").and(path::end()))
+                .and_then({
+                    let api = api.clone();
+                    move || api.queue().map_err(warp::reject::custom)
+                }))
+            .boxed();
+
+        let route = route
+            .or(warp::post2()
+                .and(path!("player" / "queue").and(path::end()))
+                .and(body::json())
+                .and_then({
+                    let api = api.clone();
+                    move |body: AddQueueItem| {
+                        api.clone()
+                            .queue_add(body)
+                            .map_err(warp::reject::custom)
+                            .boxed()
+                            .compat()
+                    }
+                }))
+            .boxed();
+
+        let route = route
+            .or(warp::delete2()
+                .and(path!("player" / "queue").and(path::end()))
+                .and_then({
+                    let api = api.clone();
+                    move || api.queue_purge().map_err(warp::reject::custom)
+                }))
+            .boxed();
+
+        let route = route
+            .or(warp::delete2()
+                .and(path!("player" / "queue" / usize).and(path::end()))
+                .and_then({
+                    let api = api.clone();
+                    move |position: usize| api.queue_delete(position).map_err(warp::reject::custom)
+                }))
+            .boxed();
+
+        let route = route
+            .or(warp::put2()
+                .and(path!("player" / "queue" / "order").and(path::end()))
+                .and(body::json())
+                .and_then({
+                    let api = api.clone();
+                    move |order: Vec<TrackId>| api.queue_order(order).map_err(warp::reject::custom)
+                }))
+            .boxed();
+
+        let route = route
+            .or(warp::get2()
+                .and(path!("player" / "pending").and(path::end()))
+                .and_then({
+                    let api = api.clone();
+                    move || api.pending().map_err(warp::reject::custom)
+                }))
+            .boxed();
+
+        let route = route
+            .or(warp::post2()
+                .and(path!("player" / "pending" / usize / "approve").and(path::end()))
+                .and_then({
+                    let api = api.clone();
+                    move |position: usize| {
+                        api.clone()
+                            .pending_approve(position)
+                            .map_err(warp::reject::custom)
+                            .boxed()
+                            .compat()
+                    }
+                }))
+            .boxed();
+
+        let route = route
+            .or(warp::post2()
+                .and(path!("player" / "pending" / usize / "deny").and(path::end()))
+                .and(body::json())
+                .and_then({
+                    let api = api.clone();
+                    move |position: usize, body: DenyPending| {
+                        api.pending_deny(position, body)
+                            .map_err(warp::reject::custom)
+                    }
+                }))
+            .boxed();
+
         let route = route
             .or(warp::put2()
                 .and(warp::path("balances"))
@@ -993,6 +1567,7 @@ pub fn setup(
         let route = route.or(Commands::route(commands));
         let route = route.or(Promotions::route(promotions));
         let route = route.or(Themes::route(themes));
+        let route = route.or(SongBans::route(song_bans));
         let route = route.or(Settings::route(settings));
 
         let route = route
@@ -1019,13 +1594,18 @@ pub fn setup(
         .and(warp::path!("ws" / "overlay"))
         .and(send_bus(global_bus).recover(recover));
 
-    let ws_youtube = warp::get2()
-        .and(warp::path!("ws" / "youtube"))
-        .and(send_bus(youtube_bus).recover(recover));
+    let ws_youtube = warp::get2().and(warp::path!("ws" / "youtube")).and(
+        youtube_bus_ws(youtube_bus, player.clone(), youtube_connections.clone()).recover(recover),
+    );
+
+    let ws_local = warp::get2()
+        .and(warp::path!("ws" / "local"))
+        .and(send_bus(local_bus).recover(recover));
 
     let routes = oauth2_redirect.recover(recover);
     let routes = routes.or(api.recover(recover));
     let routes = routes.or(ws_youtube.recover(recover));
+    let routes = routes.or(ws_local.recover(recover));
     let routes = routes.or(ws_overlay.recover(recover));
 
     let server_future = if let Some(web_root) = web_root {
@@ -1062,6 +1642,7 @@ pub fn setup(
 
     let server = Server {
         player: player.clone(),
+        youtube_connections,
         token_callbacks: token_callbacks.clone(),
     };
 
@@ -1090,6 +1671,53 @@ impl std::str::FromStr for Fragment {
     }
 }
 
+/// A range of bytes requested through the `Range` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Range {
+    /// The whole file is requested.
+    Full,
+    /// The given range of bytes is requested, where the end is inclusive.
+    Partial(u64, u64),
+    /// The requested range is outside of the file.
+    Unsatisfiable,
+}
+
+/// Parse the value of a `Range` header for a file of the given length.
+///
+/// Only a single range of bytes is supported, anything else is ignored and the full file is
+/// served.
+fn parse_range(header: &str, len: u64) -> Range {
+    let mut it = header.trim().splitn(2, '=');
+
+    let range = match (it.next(), it.next()) {
+        (Some("bytes"), Some(range)) if !range.contains(',') => range.trim(),
+        _ => return Range::Full,
+    };
+
+    let mut it = range.splitn(2, '-');
+
+    let (start, end) = match (it.next(), it.next()) {
+        (Some(start), Some(end)) => (start.trim(), end.trim()),
+        _ => return Range::Full,
+    };
+
+    let (start, end) = match (start.parse::<u64>().ok(), end.parse::<u64>().ok()) {
+        (Some(start), Some(end)) if start <= end => (start, u64::min(end, len.saturating_sub(1))),
+        (Some(start), None) if end.is_empty() => (start, len.saturating_sub(1)),
+        // NB: suffix range, the last `end` bytes of the file.
+        (None, Some(end)) if start.is_empty() && end > 0 => {
+            (len.saturating_sub(end), len.saturating_sub(1))
+        }
+        _ => return Range::Full,
+    };
+
+    if len == 0 || start >= len {
+        return Range::Unsatisfiable;
+    }
+
+    Range::Partial(start, end)
+}
+
 // This function receives a `Rejection` and tries to return a custom
 // value, othewise simply passes the rejection along.
 fn recover(err: warp::Rejection) -> Result<impl warp::Reply, warp::Rejection> {
@@ -1125,6 +1753,8 @@ struct ErrorMessage {
 #[derive(Clone)]
 pub struct Server {
     player: Arc<RwLock<Option<player::Player>>>,
+    /// Number of overlays connected to the YouTube player, which might be before the player is set.
+    youtube_connections: Arc<RwLock<usize>>,
     /// Callbacks for when we have received a token.
     token_callbacks: Arc<RwLock<HashMap<String, ExpectedToken>>>,
 }
@@ -1132,7 +1762,14 @@ pub struct Server {
 impl Server {
     /// Set the player interface.
     pub fn set_player(&self, player: player::Player) {
-        *self.player.write() = Some(player);
+        let mut current = self.player.write();
+
+        // NB: let the player know about overlays which connected before it was set.
+        for _ in 0..*self.youtube_connections.read() {
+            player.youtube_connected();
+        }
+
+        *current = Some(player);
     }
 
     /// Receive an Oauth 2.0 token.
@@ -1170,6 +1807,94 @@ pub struct ReceivedToken {
     pub state: String,
 }
 
+/// Connect the YouTube bus to a websocket connection, and pass on reports from the player in the
+/// overlay.
+///
+/// Overlays are counted in `connections` since they might connect before the player is set.
+fn youtube_bus_ws(
+    bus: Arc<bus::Bus<bus::YouTube>>,
+    player: Arc<RwLock<Option<player::Player>>>,
+    connections: Arc<RwLock<usize>>,
+) -> filters::BoxedFilter<(impl warp::Reply,)> {
+    warp::ws2()
+        .map(move |ws: warp::ws::Ws2| {
+            let bus = bus.clone();
+            let player = player.clone();
+            let connections = connections.clone();
+
+            ws.on_upgrade(move |websocket| {
+                let (tx, rx) = websocket.split();
+
+                {
+                    // NB: the player lock is held so that it can't be set in between.
+                    let player = player.read();
+                    *connections.write() += 1;
+
+                    if let Some(player) = player.as_ref() {
+                        player.youtube_connected();
+                    }
+                }
+
+                let send = stream01::iter_ok(bus.latest())
+                    .chain(bus.add_rx())
+                    .map_err(|_| failure::format_err!("failed to receive notification"))
+                    .and_then(|n| {
+                        serde_json::to_string(&n)
+                            .map(filters::ws::Message::text)
+                            .map_err(failure::Error::from)
+                    })
+                    .forward(tx.sink_map_err(|e| failure::format_err!("error from sink: {}", e)))
+                    .map(|_| ());
+
+                let receive = rx
+                    .map_err(|e| failure::format_err!("error from stream: {}", e))
+                    .for_each({
+                        let player = player.clone();
+
+                        move |m| {
+                            let m = match m.to_str() {
+                                Ok(m) => m,
+                                Err(()) => return Ok(()),
+                            };
+
+                            match serde_json::from_str::<bus::YouTubeReport>(m) {
+                                Ok(report) => {
+                                    if let Some(player) = player.read().as_ref() {
+                                        player.youtube_report(report);
+                                    }
+                                }
+                                Err(e) => {
+                                    log::warn!("bad report from youtube player: {}", e);
+                                }
+                            }
+
+                            Ok(())
+                        }
+                    });
+
+                // NB: the connection is closed as soon as either direction is done.
+                send.select(receive).then(move |result| {
+                    {
+                        let player = player.read();
+                        let mut connections = connections.write();
+                        *connections = connections.saturating_sub(1);
+
+                        if let Some(player) = player.as_ref() {
+                            player.youtube_disconnected();
+                        }
+                    }
+
+                    if let Err((e, _)) = result {
+                        log::error!("websocket error: {}", e);
+                    }
+
+                    Ok::<_, ()>(())
+                })
+            })
+        })
+        .boxed()
+}
+
 /// Connecting a bus to a websocket connection.
 fn send_bus<T>(bus: Arc<bus::Bus<T>>) -> filters::BoxedFilter<(impl warp::Reply,)>
 where
@@ -1205,3 +1930,26 @@ where
         })
         .boxed()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_range, Range};
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(Range::Partial(0, 99), parse_range("bytes=0-99", 1000));
+        assert_eq!(Range::Partial(500, 999), parse_range("bytes=500-", 1000));
+        assert_eq!(Range::Partial(900, 999), parse_range("bytes=-100", 1000));
+        assert_eq!(
+            Range::Partial(900, 999),
+            parse_range("bytes=900-2000", 1000)
+        );
+        assert_eq!(Range::Partial(0, 999), parse_range("bytes=-2000", 1000));
+        assert_eq!(Range::Unsatisfiable, parse_range("bytes=1000-", 1000));
+        // NB: anything but a single range of bytes serves the whole file.
+        assert_eq!(Range::Full, parse_range("bytes=0-1,5-10", 1000));
+        assert_eq!(Range::Full, parse_range("items=0-10", 1000));
+        assert_eq!(Range::Full, parse_range("bytes=10-5", 1000));
+        assert_eq!(Range::Full, parse_range("bytes=abc", 1000));
+    }
+}
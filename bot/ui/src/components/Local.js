@@ -0,0 +1,126 @@
+import React from "react";
+import {websocketUrl} from "../utils.js";
+import Websocket from "react-websocket";
+
+const OBS_CSS = [
+  "body.local-body { background-color: rgba(0, 0, 0, 0); }",
+  ".overlay-hidden { display: none }"
+]
+
+/**
+ * Build the URL to the audio file of a local track.
+ */
+function fileUrl(id) {
+  return `/api/local/file?id=${encodeURIComponent(id)}`;
+}
+
+export default class Local extends React.Component {
+  constructor(props) {
+    super(props);
+
+    this.audioRef = React.createRef();
+
+    this.state = {
+      stopped: true,
+      id: null,
+    };
+  }
+
+  handleData(d) {
+    let data = null;
+
+    try {
+      data = JSON.parse(d);
+    } catch(e) {
+      console.log("failed to deserialize message");
+      return;
+    }
+
+    let audio = this.audioRef.current;
+
+    if (!audio) {
+      return;
+    }
+
+    switch (data.type) {
+      case "local/current":
+        switch (data.event.type) {
+          case "play":
+            if (this.state.id !== data.event.id) {
+              audio.src = fileUrl(data.event.id);
+              audio.currentTime = data.event.elapsed;
+            } else if (Math.abs(data.event.elapsed - audio.currentTime) > 2) {
+              audio.currentTime = data.event.elapsed;
+            }
+
+            if (audio.paused) {
+              audio.play().catch(e => console.log("failed to play audio", e));
+            }
+
+            this.setState({
+              stopped: false,
+              id: data.event.id,
+            });
+            break;
+          case "pause":
+            audio.pause();
+            break;
+          case "stop":
+            audio.pause();
+
+            this.setState({
+              stopped: true,
+              id: null,
+            });
+            break;
+          default:
+            break;
+        }
+
+        break;
+      case "local/volume":
+        audio.volume = Math.min(Math.max(data.volume / 100, 0), 1);
+        break;
+      default:
+        return;
+    }
+  }
+
+  componentWillMount() {
+    document.body.classList.add('local-body');
+  }
+
+  componentWillUnmount() {
+    document.body.classList.remove('local-body');
+  }
+
+  render() {
+    var noAudio = null;
+
+    if (this.state.stopped) {
+      noAudio = (
+        <div className="overlay-hidden p-4 container">
+          <h1>No Song Loaded</h1>
+
+          <p>
+            If you want to embed this into OBS, please add the following Custom CSS:
+          </p>
+
+          <pre><code>
+            {OBS_CSS.join("\n")}
+          </code></pre>
+        </div>
+      );
+    }
+
+    return (
+      <div id="local">
+        <Websocket url={websocketUrl("ws/local")} onMessage={this.handleData.bind(this)} />
+
+        {noAudio}
+
+        <audio ref={this.audioRef} preload="auto" />
+      </div>
+    );
+  }
+}
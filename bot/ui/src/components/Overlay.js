@@ -178,6 +178,11 @@ export default class Overlay extends React.Component {
                 update.artist = null;
               }
 
+              break;
+            case "local":
+              update.track = data.track.track.title;
+              update.artist = data.track.track.artist ? {name: data.track.track.artist} : null;
+              update.albumArt = null;
               break;
             default:
               break;
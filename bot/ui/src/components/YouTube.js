@@ -24,6 +24,7 @@ export default class YouTube extends React.Component {
     this.playerElement = null;
     this.player = null;
     this.playerRef = React.createRef();
+    this.ws = null;
 
     this.state = {
       stopped: true,
@@ -65,9 +66,14 @@ export default class YouTube extends React.Component {
                 this.player.playVideo();
               }
 
-              if (Math.abs(data.event.elapsed - this.player.getCurrentTime()) > 2) {
+              // NB: while buffering the player lags behind, we report where it is instead.
+              let buffering = this.player.getPlayerState() === BUFFERING;
+
+              if (!buffering && Math.abs(data.event.elapsed - this.player.getCurrentTime()) > 2) {
                 this.player.seekTo(data.event.elapsed);
               }
+
+              this.reportState();
             }
 
             this.setState(update);
@@ -104,6 +110,50 @@ export default class YouTube extends React.Component {
     }
   }
 
+  /**
+   * Send a report back to the bot.
+   */
+  report(report) {
+    if (!this.ws || !this.state.videoId) {
+      return;
+    }
+
+    report.video_id = this.state.videoId;
+    this.ws.sendMessage(JSON.stringify(report));
+  }
+
+  /**
+   * Report the current state and position of the player.
+   */
+  reportState() {
+    let state = null;
+
+    switch (this.player.getPlayerState()) {
+      case UNSTARTED:
+        state = "unstarted";
+        break;
+      case ENDED:
+        state = "ended";
+        break;
+      case PLAYING:
+        state = "playing";
+        break;
+      case PAUSED:
+        state = "paused";
+        break;
+      case BUFFERING:
+        state = "buffering";
+        break;
+      case VIDEO_CUED:
+        state = "cued";
+        break;
+      default:
+        return;
+    }
+
+    this.report({type: "state", state, elapsed: this.player.getCurrentTime()});
+  }
+
   setupPlayer() {
     if (!this.playerRef.current) {
       throw new Error("Reference to player is not available");
@@ -122,6 +172,10 @@ export default class YouTube extends React.Component {
         onPlaybackQualityChange: e => {
         },
         onStateChange: e => {
+          this.reportState();
+        },
+        onError: e => {
+          this.report({type: "error", code: e.data});
         },
       }
     });
@@ -170,7 +224,10 @@ export default class YouTube extends React.Component {
         </div>
       );
     } else {
-      ws = <Websocket url={websocketUrl("ws/youtube")} onMessage={this.handleData.bind(this)} />;
+      ws = <Websocket
+        url={websocketUrl("ws/youtube")}
+        onMessage={this.handleData.bind(this)}
+        ref={ws => this.ws = ws} />;
     }
 
     var noVideo = null;
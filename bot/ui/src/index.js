@@ -17,6 +17,7 @@ import Promotions from "./components/Promotions";
 import Aliases from "./components/Aliases";
 import Themes from "./components/Themes";
 import YouTube from "./components/YouTube";
+import Local from "./components/Local";
 import Authorization from "./components/Authorization";
 
 const RouteLayout = withRouter(props => <Layout {...props} />)
@@ -303,6 +304,9 @@ function Layout(props) {
               <NavDropdown.Item as={Link} active={path === "/youtube"} to="/youtube" target="youtube">
                 YouTube Player
               </NavDropdown.Item>
+              <NavDropdown.Item as={Link} active={path === "/local"} to="/local" target="local">
+                Local Player
+              </NavDropdown.Item>
             </NavDropdown>
           </Nav>
         </Navbar.Collapse>
@@ -339,6 +343,7 @@ function AppRouter() {
       )} />
       <Route path="/overlay/" component={Overlay} />
       <Route path="/youtube" component={YouTube} />
+      <Route path="/local" component={Local} />
     </Router>
   );
 }